@@ -28,12 +28,14 @@ let currentTheme = 'light';
 let treeData = null;
 let commitsData = [];
 let branchesData = [];
+const runningOperations = new Set();
 
 // Initialize application
 document.addEventListener('DOMContentLoaded', async () => {
     initializeEventListeners();
     await loadInitialData();
     updateView('tree');
+    await watchStore();
 });
 
 // Event listeners
@@ -62,9 +64,113 @@ function initializeEventListeners() {
 
     // Refresh
     document.getElementById('refresh-btn').addEventListener('click', async () => {
+        await invoke('refresh_heads').catch((error) => console.log('Cache not refreshed:', error));
         await loadInitialData();
         updateCurrentView();
     });
+
+    // Cancel running operations
+    document.getElementById('cancel-operation').addEventListener('click', cancelOperations);
+
+    // Export progress
+    if (window.__TAURI__) {
+        window.__TAURI__.event.listen('export-progress', (event) => showExportProgress(event.payload));
+        window.__TAURI__.event.listen('operation-progress', (event) => showOperationProgress(event.payload));
+        window.__TAURI__.event.listen('branch-updated', (event) => onBranchUpdated(event.payload));
+    }
+}
+
+// Show export progress in the status bar
+function showExportProgress(progress) {
+    const status = document.getElementById('operation-status');
+    if (progress.finished) {
+        status.textContent = `Exported ${progress.total_entries} entries (${formatBytes(progress.total_bytes)})`;
+    } else {
+        status.textContent = `Exporting ${progress.entries_written}/${progress.total_entries}: ${progress.current_path}`;
+    }
+}
+
+// Invoke a long-running command as operation `id`; starting the same id again
+// cancels the earlier run, so only the latest search or diff completes. Every
+// command that reads trees, searches, exports or imports accepts an id.
+async function invokeOperation(id, cmd, args = {}) {
+    runningOperations.add(id);
+    document.getElementById('cancel-operation').hidden = false;
+    try {
+        return await invoke(cmd, { ...args, operationId: id });
+    } finally {
+        runningOperations.delete(id);
+        document.getElementById('cancel-operation').hidden = runningOperations.size === 0;
+    }
+}
+
+// Cancel every operation started from this window
+async function cancelOperations() {
+    for (const id of runningOperations) {
+        await invoke('cancel_operation', { operationId: id });
+    }
+    document.getElementById('operation-status').textContent = 'Cancelled';
+}
+
+// Whether an error is a cancelled operation, which needs no reporting
+function isCancelled(error) {
+    return String(error).includes('was cancelled');
+}
+
+// Show the phase of a long-running operation in the status bar, clearing it
+// when the operation finishes unless something else was shown since
+let operationProgressText = '';
+function showOperationProgress(progress) {
+    const status = document.getElementById('operation-status');
+    if (progress.finished) {
+        if (status.textContent === operationProgressText) {
+            status.textContent = '';
+        }
+        return;
+    }
+    let text = progress.phase;
+    if (progress.total_items) {
+        text += ` ${progress.items_done}/${progress.total_items}`;
+    } else if (progress.items_done) {
+        text += ` ${progress.items_done} items`;
+    }
+    if (progress.bytes) {
+        text += ` (${formatBytes(progress.bytes)})`;
+    }
+    operationProgressText = text;
+    status.textContent = text;
+}
+
+// Ask to be told when branches move outside IrminView
+async function watchStore() {
+    if (!window.__TAURI__) return;
+    try {
+        await invoke('watch_store');
+    } catch (error) {
+        console.log('Not watching the store for changes:', error);
+    }
+}
+
+// Reload the views after a branch moved, once for a burst of updates
+let branchRefreshPending = false;
+async function onBranchUpdated(update) {
+    const status = document.getElementById('branch-status');
+    if (!update.new_head) {
+        status.textContent = `Branch ${update.branch} was deleted`;
+    } else if (!update.old_head) {
+        status.textContent = `Branch ${update.branch} was created`;
+    } else {
+        const plural = update.new_commits === 1 ? '' : 's';
+        status.textContent = `${update.branch}: ${update.new_commits} new commit${plural}`;
+    }
+
+    if (branchRefreshPending) return;
+    branchRefreshPending = true;
+    setTimeout(async () => {
+        branchRefreshPending = false;
+        await loadInitialData();
+        updateCurrentView();
+    }, 100);
 }
 
 // Load initial data
@@ -74,19 +180,72 @@ async function loadInitialData() {
         const irminAvailable = await invoke('check_irmin_availability');
         console.log('Irmin integration available:', irminAvailable);
         
-        treeData = await invoke('get_tree');
+        treeData = canStreamTree() ? await streamTree() : await invokeOperation('tree', 'get_tree');
         commitsData = await invoke('get_commits');
         branchesData = await invoke('get_branches');
         
         // Populate commit selects
         populateCommitSelects();
+
+        // Index the head in the background so searches need not walk the store
+        invoke('build_search_index', { revision: 'main' })
+            .catch((error) => console.log('Search index not built:', error));
         
         console.log('Data loaded successfully');
     } catch (error) {
+        if (isCancelled(error)) return;
         console.error('Error loading data:', error);
     }
 }
 
+// Whether the tree can be streamed over a channel (Tauri, or the web shim)
+function canStreamTree() {
+    return Boolean(window.__TAURI__ && window.__TAURI__.core.Channel);
+}
+
+// Load the tree in batches of flat entries, rendering it as they arrive
+async function streamTree() {
+    const root = { key: '/', node_type: 'Directory', children: {}, metadata: {} };
+    const channel = new window.__TAURI__.core.Channel();
+    let renderPending = false;
+    channel.onmessage = (batch) => {
+        for (const entry of batch.entries) {
+            insertTreeEntry(root, entry);
+        }
+        treeData = root;
+        document.getElementById('operation-status').textContent =
+            batch.finished ? '' : `Loading tree: ${batch.entries_sent} entries`;
+        if (currentView === 'tree' && !renderPending) {
+            renderPending = true;
+            requestAnimationFrame(() => {
+                renderPending = false;
+                renderTree();
+            });
+        }
+    };
+    await invokeOperation('tree', 'stream_tree', { onBatch: channel });
+    return root;
+}
+
+// Add a streamed entry below its parent directory
+function insertTreeEntry(root, entry) {
+    const segments = entry.path.split('/').filter(segment => segment);
+    const key = segments.pop();
+    let parent = root;
+    for (const segment of segments) {
+        parent.children[segment] = parent.children[segment] ||
+            { key: segment, node_type: 'Directory', children: {}, metadata: {} };
+        parent = parent.children[segment];
+    }
+    const existing = parent.children[key];
+    parent.children[key] = {
+        key,
+        node_type: entry.type,
+        children: existing ? existing.children : {},
+        metadata: { size: entry.size },
+    };
+}
+
 // Update view
 function updateView(viewName) {
     // Update navigation
@@ -129,29 +288,39 @@ function renderTree() {
         return;
     }
 
-    container.innerHTML = renderTreeNode(treeData, 0);
+    container.innerHTML = renderTreeNode(treeData, 0, '');
+    container.querySelectorAll('.tree-node-content').forEach(element => {
+        element.addEventListener('click', () => {
+            if (element.dataset.path === undefined) {
+                toggleTreeNode(element);
+            } else {
+                showValue(element.dataset.path);
+            }
+        });
+    });
     updateItemCount(countTreeNodes(treeData));
 }
 
 // Render tree node recursively
-function renderTreeNode(node, level) {
+function renderTreeNode(node, level, path) {
     const isDirectory = node.node_type === 'Directory';
+    const pathAttribute = isDirectory ? '' : ` data-path="${escapeHtml(path)}"`;
     const icon = isDirectory ? '📁' : '📄';
     const sizeInfo = node.metadata.size ? `${formatBytes(node.metadata.size)}` : '';
     
     let html = `
         <div class="tree-node ${isDirectory ? 'expandable' : ''}" style="margin-left: ${level * 20}px">
-            <div class="tree-node-content" onclick="toggleTreeNode(this)">
+            <div class="tree-node-content"${pathAttribute}>
                 <span class="tree-node-icon">${icon}</span>
-                <span class="tree-node-name">${node.key}</span>
+                <span class="tree-node-name">${escapeHtml(node.key)}</span>
                 <span class="tree-node-size">${sizeInfo}</span>
             </div>
     `;
 
     if (isDirectory && Object.keys(node.children).length > 0) {
         html += '<div class="tree-node-children">';
-        for (const child of Object.values(node.children)) {
-            html += renderTreeNode(child, level + 1);
+        for (const [key, child] of Object.entries(node.children)) {
+            html += renderTreeNode(child, level + 1, `${path}/${key}`);
         }
         html += '</div>';
     }
@@ -166,6 +335,40 @@ function toggleTreeNode(element) {
     node.classList.toggle('expanded');
 }
 
+// Show a value with syntax highlighting and parse errors
+async function showValue(path) {
+    const viewer = document.getElementById('value-viewer');
+    try {
+        const rendered = await invoke('render_value', { path });
+        if (!rendered) {
+            viewer.innerHTML = '';
+            return;
+        }
+        const code = rendered.tokens
+            .map(token => `<span class="token-${token.kind.toLowerCase()}">${escapeHtml(token.text)}</span>`)
+            .join('');
+        const errors = rendered.errors
+            .map(error => `<div class="value-error">Line ${error.line}, column ${error.column}: ${escapeHtml(error.message)}</div>`)
+            .join('');
+        viewer.innerHTML = `
+            <div class="value-header">${escapeHtml(path)} <span class="value-type">${rendered.content_type}</span></div>
+            ${errors}
+            <pre class="value-code">${code}</pre>
+        `;
+    } catch (error) {
+        viewer.innerHTML = `<div class="value-error">${escapeHtml(String(error))}</div>`;
+    }
+}
+
+function escapeHtml(text) {
+    return text
+        .replace(/&/g, '&amp;')
+        .replace(/</g, '&lt;')
+        .replace(/>/g, '&gt;')
+        .replace(/"/g, '&quot;')
+        .replace(/'/g, '&#39;');
+}
+
 // Render commits view
 function renderCommits() {
     const container = document.getElementById('commits-container');
@@ -233,9 +436,10 @@ async function showDiff() {
     }
 
     try {
-        const diff = await invoke('get_commit_diff', { from_commit: fromCommit, to_commit: toCommit });
+        const diff = await invokeOperation('diff', 'get_commit_diff', { from_commit: fromCommit, to_commit: toCommit });
         renderDiff(diff);
     } catch (error) {
+        if (isCancelled(error)) return;
         console.error('Error getting diff:', error);
     }
 }
@@ -301,9 +505,10 @@ async function performSearch() {
     if (!query) return;
 
     try {
-        const results = await invoke('search_keys', { query });
+        const results = await invokeOperation('search', 'search_keys', { query });
         renderSearchResults(results);
     } catch (error) {
+        if (isCancelled(error)) return;
         console.error('Search error:', error);
     }
 }
@@ -320,14 +525,26 @@ function renderSearchResults(results) {
     const html = results.map(result => {
         const typeIcon = result.node.node_type === 'Directory' ? '📁' : '📄';
         return `
-            <div class="search-result" onclick="highlightTreeNode('${result.path}')">
-                <div class="search-result-path">${typeIcon} ${result.path}</div>
+            <div class="search-result" data-path="${escapeHtml(result.path)}">
+                <div class="search-result-path">${typeIcon} ${highlightMatches(result.path, result.matched_indices)}</div>
                 <div class="search-result-type">${result.node.node_type}</div>
             </div>
         `;
     }).join('');
 
     container.innerHTML = html;
+    container.querySelectorAll('.search-result').forEach(element => {
+        element.addEventListener('click', () => highlightTreeNode(element.dataset.path));
+    });
+}
+
+// Wrap the characters matched by the fuzzy search in <mark> tags
+function highlightMatches(path, indices) {
+    if (!indices || !indices.length) return escapeHtml(path);
+    const matched = new Set(indices);
+    return Array.from(path)
+        .map((ch, i) => matched.has(i) ? `<mark>${escapeHtml(ch)}</mark>` : escapeHtml(ch))
+        .join('');
 }
 
 // Highlight tree node (placeholder for future implementation)
@@ -478,7 +695,8 @@ function mockSearchResults(query) {
                 key: "config.json",
                 node_type: "File"
             },
-            relevance_score: 0.8
+            relevance_score: 0.8,
+            matched_indices: []
         }
     ];
 }
@@ -7,16 +7,32 @@ let path_arg =
   let doc = "Path to the Irmin store" in
   Arg.(value & opt string default_path & info ["p"; "path"] ~docv:"PATH" ~doc)
 
+let commit_arg =
+  let doc = "Commit hash to read the tree at (defaults to the head of main)" in
+  Arg.(value & opt (some string) None & info ["commit"] ~docv:"HASH" ~doc)
+
 let get_tree_cmd =
   let doc = "Get the tree structure from the Irmin store" in
   let info = Cmd.info "tree" ~doc in
   let term = 
-    Term.(const (fun path ->
+    Term.(const (fun path commit ->
       Lwt_main.run (
-        let* json = Irmin_bridge.get_tree_json path in
+        let* json = Irmin_bridge.get_tree_json ?commit path in
         Lwt_io.printf "%s\n" json
       )
-    ) $ path_arg) in
+    ) $ path_arg $ commit_arg) in
+  Cmd.v info term
+
+let stream_tree_cmd =
+  let doc = "Stream the tree as NDJSON, one {path, type, size, hash} line per node" in
+  let info = Cmd.info "tree-stream" ~doc in
+  let term =
+    Term.(const (fun path commit ->
+      Lwt_main.run (
+        Irmin_bridge.stream_tree_ndjson ?commit path
+        |> Lwt_stream.iter_s (Lwt_io.write Lwt_io.stdout)
+      )
+    ) $ path_arg $ commit_arg) in
   Cmd.v info term
 
 let get_commits_cmd =
@@ -76,13 +92,35 @@ let get_diff_cmd =
     ) $ path_arg $ from_arg $ to_arg) in
   Cmd.v info term
 
+let apply_cmd =
+  let branch_arg =
+    let doc = "Branch to commit to" in
+    Arg.(value & opt string "main" & info ["branch"] ~docv:"BRANCH" ~doc) in
+  let message_arg =
+    let doc = "Commit message" in
+    Arg.(required & opt (some string) None & info ["m"; "message"] ~docv:"MESSAGE" ~doc) in
+  let author_arg =
+    let doc = "Commit author" in
+    Arg.(value & opt string "IrminView" & info ["author"] ~docv:"AUTHOR" ~doc) in
+  let doc = "Apply a JSON list of changes read from stdin as a single commit" in
+  let info = Cmd.info "apply" ~doc in
+  let term =
+    Term.(const (fun path branch message author ->
+      Lwt_main.run (
+        let* changes = Lwt_io.read Lwt_io.stdin in
+        let* json = Irmin_bridge.apply_changes_json path ~branch ~author ~message changes in
+        Lwt_io.printf "%s\n" json
+      )
+    ) $ path_arg $ branch_arg $ message_arg $ author_arg) in
+  Cmd.v info term
+
 let default_cmd =
   let doc = "Irmin bridge CLI for IrminView" in
   let info = Cmd.info "irmin-bridge-cli" ~doc in
   let term = Term.(ret (const (`Help (`Pager, None)))) in
   Cmd.v info term
 
-let cmds = [get_tree_cmd; get_commits_cmd; get_branches_cmd; search_keys_cmd; get_diff_cmd]
+let cmds = [get_tree_cmd; stream_tree_cmd; get_commits_cmd; get_branches_cmd; search_keys_cmd; get_diff_cmd; apply_cmd]
 
 let () =
   let cmd = Cmd.group default_cmd cmds in
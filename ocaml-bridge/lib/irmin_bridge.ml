@@ -10,6 +10,7 @@ type irmin_node = {
   node_type: string;
   children: (string * irmin_node) list;
   metadata: irmin_metadata;
+  node_hash: string option;  (* Irmin node or contents hash, as "hash" *)
 }
 
 and irmin_metadata = {
@@ -62,13 +63,14 @@ let metadata_to_json metadata =
   ]
 
 let rec node_to_json node =
-  `Assoc [
+  let hash = match node.node_hash with Some h -> [("hash", `String h)] | None -> [] in
+  `Assoc ([
     ("key", `String node.key);
     ("value", match node.value with Some v -> `String v | None -> `Null);
     ("node_type", `String node.node_type);
     ("children", `Assoc (List.map (fun (k, v) -> (k, node_to_json v)) node.children));
     ("metadata", metadata_to_json node.metadata);
-  ]
+  ] @ hash)
 
 let commit_to_json commit =
   `Assoc [
@@ -115,9 +117,22 @@ let init_store ?(path="./irmin_store") () =
   let config = Irmin_git.config path in
   Store.Repo.init config
 
+(* Open the store at a given commit hash, or at the head of main *)
+let store_at ?commit repo =
+  match commit with
+  | None -> Store.of_branch repo "main"
+  | Some hash ->
+      match Irmin.Type.of_string Store.Hash.t hash with
+      | Error (`Msg msg) -> Lwt.fail_with (Printf.sprintf "Invalid commit hash %s: %s" hash msg)
+      | Ok h ->
+          let* commit_opt = Store.Commit.of_hash repo h in
+          match commit_opt with
+          | Some c -> Store.of_commit c
+          | None -> Lwt.fail_with (Printf.sprintf "Unknown commit: %s" hash)
+
 (* Get tree structure from Irmin store *)
-let get_tree_from_store repo =
-  let* main = Store.of_branch repo "main" in
+let get_tree_from_store ?commit repo =
+  let* main = store_at ?commit repo in
   let* tree = Store.tree main in
   let rec build_node_tree path key =
     let* node_opt = Store.Tree.find_tree tree path in
@@ -129,12 +144,14 @@ let get_tree_from_store repo =
           size = (match value_opt with Some v -> Some (String.length v) | None -> None);
           permissions = Some "644";
         } in
+        let node_hash = Option.map (fun v -> Store.Contents.hash v |> Irmin.Type.to_string Store.Hash.t) value_opt in
         Lwt.return {
           key = key;
           value = value_opt;
           node_type = "File";
           children = [];
           metadata;
+          node_hash;
         }
     | Some subtree ->
         let* children_keys = Store.Tree.list subtree [] in
@@ -154,6 +171,7 @@ let get_tree_from_store repo =
           node_type = "Directory";
           children = children;
           metadata;
+          node_hash = Some (Store.Tree.hash subtree |> Irmin.Type.to_string Store.Hash.t);
         }
   in
   build_node_tree [] "root"
@@ -228,6 +246,7 @@ let search_keys_in_store repo query =
               node_type = "Directory";
               children = [];
               metadata;
+              node_hash = None;
             }
         | `Contents ->
             let* value = Store.Tree.find tree full_path in
@@ -242,6 +261,7 @@ let search_keys_in_store repo query =
               node_type = "File";
               children = [];
               metadata;
+              node_hash = None;
             }
       in
       
@@ -271,10 +291,87 @@ let get_diff_between_commits repo from_commit to_commit =
     ];
   }
 
+(* Parse a change as produced by IrminView *)
+let diff_change_of_json json =
+  let open Yojson.Safe.Util in
+  {
+    path = json |> member "path" |> to_string;
+    change_type = json |> member "change_type" |> to_string;
+    old_value = json |> member "old_value" |> to_string_option;
+    new_value = json |> member "new_value" |> to_string_option;
+  }
+
+let split_path path =
+  String.split_on_char '/' path |> List.filter (fun segment -> segment <> "")
+
+(* Apply changes to a branch as a single commit and return the new head *)
+let apply_changes repo ~branch ~author ~message changes =
+  let* store = Store.of_branch repo branch in
+  let* tree = Store.tree store in
+  let* tree = Lwt_list.fold_left_s (fun tree (change : diff_change) ->
+    let key = split_path change.path in
+    match (change.change_type, change.new_value) with
+    | ("Deleted", _) -> Store.Tree.remove tree key
+    | (_, Some value) -> Store.Tree.add tree key value
+    | (_, None) -> Lwt.fail_with (Printf.sprintf "Missing new_value for %s" change.path)
+  ) tree changes in
+  let info () = Store.Info.v ~author ~message (Int64.of_float (Unix.gettimeofday ())) in
+  let* () = Store.set_tree_exn ~info store [] tree in
+  let* head = Store.Head.get store in
+  Lwt.return (Store.Commit.hash head |> Store.Hash.to_string)
+
+(* Flat entry of the streaming tree transfer *)
+let entry_to_json ~path ~kind ~size ~hash =
+  `Assoc [
+    ("path", `String path);
+    ("type", `String kind);
+    ("size", match size with Some s -> `Int s | None -> `Null);
+    ("hash", `String hash);
+  ]
+
+(* Lines the tree walk may run ahead of the consumer *)
+let stream_buffer = 256
+
+(* Stream the tree as NDJSON lines of {path, type, size, hash}, parents before
+   children, without building the whole tree in memory. The walk waits while
+   [stream_buffer] lines are unread, so a slow consumer holds it back. A
+   failure ends the stream with an {"error": ...} line. *)
+let stream_tree_ndjson ?commit path =
+  let stream, push = Lwt_stream.create_bounded stream_buffer in
+  let emit json = push#push (to_string json ^ "\n") in
+  let rec walk prefix subtree =
+    let* children = Store.Tree.list subtree [] in
+    Lwt_list.iter_s (fun (key, child) ->
+      let child_path = prefix ^ "/" ^ key in
+      let hash = Store.Tree.hash child |> Irmin.Type.to_string Store.Hash.t in
+      let* kind = Store.Tree.kind child [] in
+      match kind with
+      | Some `Node ->
+          let* () = emit (entry_to_json ~path:child_path ~kind:"Directory" ~size:None ~hash) in
+          walk child_path child
+      | _ ->
+          let* value = Store.Tree.find child [] in
+          emit (entry_to_json ~path:child_path ~kind:"File" ~size:(Option.map String.length value) ~hash)
+    ) children
+  in
+  let transfer () =
+    let* repo = init_store ~path () in
+    let* store = store_at ?commit repo in
+    let* tree = Store.tree store in
+    walk "" tree
+  in
+  Lwt.async (fun () ->
+    Lwt.finalize
+      (fun () ->
+        Lwt.catch transfer (fun exn ->
+          emit (`Assoc [("error", `String (Printexc.to_string exn))])))
+      (fun () -> push#close; Lwt.return_unit));
+  stream
+
 (* Command-line interface functions *)
-let get_tree_json path =
+let get_tree_json ?commit path =
   let* repo = init_store ~path () in
-  let* tree = get_tree_from_store repo in
+  let* tree = get_tree_from_store ?commit repo in
   let json = node_to_json tree in
   Lwt.return (to_string json)
 
@@ -300,4 +397,13 @@ let get_diff_json path from_commit to_commit =
   let* repo = init_store ~path () in
   let* diff = get_diff_between_commits repo from_commit to_commit in
   let json = diff_to_json diff in
-  Lwt.return (to_string json)
\ No newline at end of file
+  Lwt.return (to_string json)
+
+let apply_changes_json path ~branch ~author ~message changes_json =
+  let* repo = init_store ~path () in
+  let changes =
+    Yojson.Safe.from_string changes_json
+    |> Yojson.Safe.Util.to_list
+    |> List.map diff_change_of_json in
+  let* hash = apply_changes repo ~branch ~author ~message changes in
+  Lwt.return (to_string (`Assoc [("hash", `String hash)]))
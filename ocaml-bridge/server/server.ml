@@ -23,10 +23,19 @@ let error_response ?(status=`Internal_server_error) message =
   json_response ~status error_json
 
 (* Route handlers *)
-let handle_tree store_path _req =
-  let* result = Irmin_bridge.get_tree_json store_path in
+let handle_tree store_path req =
+  let uri = Cohttp.Request.uri req in
+  let commit = Uri.get_query_param uri "commit" in
+  let* result = Irmin_bridge.get_tree_json ?commit store_path in
   json_response result
 
+let handle_tree_stream store_path req =
+  let uri = Cohttp.Request.uri req in
+  let commit = Uri.get_query_param uri "commit" in
+  let lines = Irmin_bridge.stream_tree_ndjson ?commit store_path in
+  let headers = Cohttp.Header.of_list (("Content-Type", "application/x-ndjson") :: cors_headers) in
+  Server.respond ~status:`OK ~headers ~body:(Cohttp_lwt.Body.of_stream lines) ()
+
 let handle_commits store_path _req =
   let* result = Irmin_bridge.get_commits_json store_path in
   json_response result
@@ -56,6 +65,20 @@ let handle_diff store_path req =
   | _ ->
       error_response ~status:`Bad_request "Missing 'from' or 'to' query parameters"
 
+let handle_commit store_path body =
+  let* body = Cohttp_lwt.Body.to_string body in
+  let open Yojson.Safe.Util in
+  match Yojson.Safe.from_string body with
+  | exception Yojson.Json_error msg ->
+      error_response ~status:`Bad_request ("Invalid JSON body: " ^ msg)
+  | json ->
+      let branch = json |> member "branch" |> to_string_option |> Option.value ~default:"main" in
+      let message = json |> member "message" |> to_string_option |> Option.value ~default:"Commit from IrminView" in
+      let author = json |> member "author" |> to_string_option |> Option.value ~default:"IrminView" in
+      let changes = json |> member "changes" |> Yojson.Safe.to_string in
+      let* result = Irmin_bridge.apply_changes_json store_path ~branch ~author ~message changes in
+      json_response result
+
 let handle_options _store_path _req =
   let headers = Cohttp.Header.of_list cors_headers in
   Server.respond_string ~status ~headers ~body:"" ()
@@ -65,7 +88,7 @@ let handle_health _store_path _req =
   json_response health_json
 
 (* Main request router *)
-let callback store_path _conn req _body =
+let callback store_path _conn req body =
   let uri = Cohttp.Request.uri req in
   let path = Uri.path uri in
   let meth = Cohttp.Request.meth req in
@@ -77,10 +100,12 @@ let callback store_path _conn req _body =
     | (`OPTIONS, _) -> handle_options store_path req
     | (`GET, "/health") -> handle_health store_path req
     | (`GET, "/api/tree") -> handle_tree store_path req
+    | (`GET, "/api/tree/stream") -> handle_tree_stream store_path req
     | (`GET, "/api/commits") -> handle_commits store_path req
     | (`GET, "/api/branches") -> handle_branches store_path req
     | (`GET, "/api/search") -> handle_search store_path req
     | (`GET, "/api/diff") -> handle_diff store_path req
+    | (`POST, "/api/commit") -> handle_commit store_path body
     | _ -> 
         error_response ~status:`Not_found "Endpoint not found"
   with
@@ -107,11 +132,13 @@ let server_cmd =
       Printf.printf "Using Irmin store at: %s\n" store_path;
       Printf.printf "Available endpoints:\n";
       Printf.printf "  GET /health - Health check\n";
-      Printf.printf "  GET /api/tree - Get tree structure\n";
+      Printf.printf "  GET /api/tree[?commit=<hash>] - Get tree structure\n";
+      Printf.printf "  GET /api/tree/stream[?commit=<hash>] - Stream the tree as NDJSON entries\n";
       Printf.printf "  GET /api/commits - Get commit history\n";
       Printf.printf "  GET /api/branches - Get branches\n";
       Printf.printf "  GET /api/search?q=<query> - Search keys\n";
       Printf.printf "  GET /api/diff?from=<hash>&to=<hash> - Get diff\n";
+      Printf.printf "  POST /api/commit - Apply changes as a single commit\n";
       Printf.printf "\n";
       
       let callback = callback store_path in
@@ -0,0 +1,573 @@
+//! Command line companion to the IrminView desktop app, sharing its store
+//! configuration (`IRMIN_USE_HTTP`, `IRMIN_SERVER_URL`, `IRMIN_FIXTURES`)
+//! and bridge. Reading commands print text for people or, with
+//! `--format json`, the same JSON the desktop app receives.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use irmin_view::irmin::{
+    cache, commit_export,
+    integration::{self, IrminConfig},
+    patch, snapshot, stats, tree,
+    types::{ChangeType, IrminNode, NodeType},
+    validation::SchemaRules,
+    wire,
+};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "irmin-view-cli", version, about = "Explore Irmin stores from the command line")]
+struct Cli {
+    /// Path to the Irmin store used by the bridge CLI
+    #[arg(long, global = true)]
+    store: Option<String>,
+
+    /// URL of an Irmin bridge HTTP server to use instead of the bridge CLI
+    #[arg(long, global = true)]
+    server: Option<String>,
+
+    /// Serve a fixture scenario (built-in name, file, or directory#name) instead of a store
+    #[arg(long, global = true)]
+    fixtures: Option<String>,
+
+    /// Serve an offline snapshot file instead of a store
+    #[arg(long, global = true)]
+    snapshot: Option<String>,
+
+    /// Always query the store instead of the response cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+struct Output {
+    /// Output format
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+}
+
+impl Output {
+    /// Print `value` as JSON, or as text with `text`
+    fn print<T: Serialize>(&self, value: &T, text: impl FnOnce(&T) -> String) -> anyhow::Result<()> {
+        let mut out = io::stdout().lock();
+        match self.format {
+            OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(value)?)?,
+            OutputFormat::Text => write!(out, "{}", text(value))?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the tree below a path
+    Tree {
+        /// Directory to print
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Branch or commit (head of main when omitted)
+        #[arg(long)]
+        revision: Option<String>,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// List the entries of a directory
+    Ls {
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Branch or commit (head of main when omitted)
+        #[arg(long)]
+        revision: Option<String>,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Print the value of a key
+    Cat {
+        path: String,
+
+        /// Branch or commit (head of main when omitted)
+        #[arg(long)]
+        revision: Option<String>,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Show the commit log, newest first
+    Log {
+        /// Only show commits of this branch
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// FROM..TO range of commit hashes or dates (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        range: Option<String>,
+
+        /// Show at most this many commits
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// List branches
+    Branches {
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Show the changes between two commits
+    Diff {
+        from: String,
+        to: String,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Fuzzy search over the keys of the head tree
+    Search {
+        query: String,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Sizes, key counts and largest values below a path
+    Stats {
+        #[arg(default_value = "/")]
+        path: String,
+
+        /// Branch or commit (head of main when omitted)
+        #[arg(long)]
+        revision: Option<String>,
+
+        /// Number of largest values to list
+        #[arg(long, default_value_t = stats::DEFAULT_LARGEST)]
+        largest: usize,
+
+        #[command(flatten)]
+        output: Output,
+    },
+
+    /// Export the commit log as CSV, JSON or NDJSON
+    ExportCommits {
+        /// Only export commits of this branch
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// FROM..TO range of commit hashes or dates (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        range: Option<String>,
+
+        /// Output format: csv, json or ndjson
+        #[arg(long, default_value = "csv")]
+        format: String,
+
+        /// Add per-commit added/modified/deleted counts
+        #[arg(long)]
+        stats: bool,
+
+        /// Output file (stdout when omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write the diff between two commits as a portable patch
+    Patch {
+        /// Commit to diff from
+        from: String,
+
+        /// Commit to diff to
+        to: String,
+
+        /// Output file (stdout when omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Apply a patch to a branch as one commit
+    Apply {
+        /// Patch file written by `patch`
+        patch: PathBuf,
+
+        /// Branch to commit to
+        #[arg(long, default_value = "main")]
+        branch: String,
+
+        /// Undo the patch instead of applying it
+        #[arg(long)]
+        reverse: bool,
+
+        /// Check the patch and list its changes without committing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Commit the applicable paths even if others are rejected
+        #[arg(long)]
+        partial: bool,
+
+        /// Commit message
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+
+    /// Check every value against the schema rules of irmin-view.toml
+    Validate {
+        /// Branch or commit to validate
+        #[arg(long, default_value = "main")]
+        revision: String,
+    },
+
+    /// Download branches, commits and trees into an offline snapshot file
+    Snapshot {
+        /// Snapshot file to write
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Include the tree of every commit, not only of the branch heads
+        #[arg(long)]
+        full_history: bool,
+    },
+
+    /// Remove the on-disk response cache (IRMIN_CACHE_DIR)
+    ClearCache,
+
+    /// Write JSON Schemas of the bridge wire protocol
+    Schema {
+        /// Directory to write `<type>.schema.json` files to
+        #[arg(long, default_value = "schemas")]
+        out_dir: PathBuf,
+    },
+}
+
+impl Cli {
+    fn config(&self) -> IrminConfig {
+        let mut config = IrminConfig::new();
+        if let Some(store) = &self.store {
+            config = config.with_path(store.clone());
+        }
+        if let Some(server) = &self.server {
+            config = config.with_http_server(server.clone());
+        }
+        if let Some(fixtures) = &self.fixtures {
+            config = config.with_fixtures(fixtures.clone());
+        }
+        if let Some(snapshot) = &self.snapshot {
+            config = config.with_snapshot(snapshot.clone());
+        }
+        if self.no_cache {
+            config = config.without_cache();
+        }
+        config
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        // The reader stopped early, as with `| head`
+        Err(e) if is_broken_pipe(&e) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe))
+}
+
+/// Tree at a revision, or at the head of the store
+async fn tree_at(config: &IrminConfig, revision: Option<&str>) -> anyhow::Result<(String, IrminNode)> {
+    match revision {
+        Some(revision) => {
+            let commit = integration::resolve_irmin_revision(config, revision).await?;
+            let node = integration::get_irmin_tree_at(config, &commit).await?;
+            Ok((commit, node))
+        }
+        None => Ok(("HEAD".to_string(), integration::get_irmin_tree(config).await?)),
+    }
+}
+
+fn node_at<'a>(root: &'a IrminNode, path: &str) -> anyhow::Result<&'a IrminNode> {
+    tree::find_node(root, path).ok_or_else(|| anyhow::anyhow!("{} does not exist", tree::normalize_path(path)))
+}
+
+fn size_of(node: &IrminNode) -> u64 {
+    node.metadata
+        .size
+        .unwrap_or_else(|| node.value.as_ref().map(|v| v.len() as u64).unwrap_or_default())
+}
+
+fn render_tree(node: &IrminNode, depth: usize, out: &mut String) {
+    let mut children: Vec<(&String, &IrminNode)> = node.children.iter().collect();
+    children.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, child) in children {
+        let indent = "  ".repeat(depth);
+        if child.node_type == NodeType::Directory {
+            out.push_str(&format!("{}{}/\n", indent, key));
+            render_tree(child, depth + 1, out);
+        } else {
+            out.push_str(&format!("{}{} ({} B)\n", indent, key, size_of(child)));
+        }
+    }
+}
+
+/// Entry of a directory listing
+#[derive(Serialize)]
+struct ListEntry {
+    name: String,
+    path: String,
+    node_type: NodeType,
+    size: Option<u64>,
+    last_modified: chrono::DateTime<chrono::Utc>,
+}
+
+async fn run(cli: &Cli) -> anyhow::Result<()> {
+    let config = cli.config();
+    match &cli.command {
+        Command::Tree { path, revision, output } => {
+            let (_, root) = tree_at(&config, revision.as_deref()).await?;
+            let node = node_at(&root, path)?;
+            output.print(node, |node| {
+                let mut text = format!("{}\n", tree::normalize_path(path));
+                render_tree(node, 1, &mut text);
+                text
+            })?;
+        }
+        Command::Ls { path, revision, output } => {
+            let (_, root) = tree_at(&config, revision.as_deref()).await?;
+            let node = node_at(&root, path)?;
+            let mut entries: Vec<ListEntry> = node
+                .children
+                .iter()
+                .map(|(key, child)| ListEntry {
+                    name: key.clone(),
+                    path: tree::join_path(&tree::normalize_path(path), key),
+                    node_type: child.node_type.clone(),
+                    size: (child.node_type != NodeType::Directory).then(|| size_of(child)),
+                    last_modified: child.metadata.last_modified,
+                })
+                .collect();
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+            output.print(&entries, |entries| {
+                entries
+                    .iter()
+                    .map(|e| match e.size {
+                        Some(size) => format!("{:>10}  {}  {}\n", size, e.last_modified.format("%Y-%m-%d %H:%M"), e.name),
+                        None => format!("{:>10}  {}  {}/\n", "-", e.last_modified.format("%Y-%m-%d %H:%M"), e.name),
+                    })
+                    .collect()
+            })?;
+        }
+        Command::Cat { path, revision, output } => {
+            let (commit, root) = tree_at(&config, revision.as_deref()).await?;
+            let node = node_at(&root, path)?;
+            let value = match (&node.node_type, &node.value) {
+                (NodeType::Directory, _) => anyhow::bail!("{} is a directory", tree::normalize_path(path)),
+                (_, value) => value.clone().unwrap_or_default(),
+            };
+            let shown = serde_json::json!({ "path": tree::normalize_path(path), "commit": commit, "value": value });
+            output.print(&shown, |_| if value.ends_with('\n') { value.clone() } else { format!("{}\n", value) })?;
+        }
+        Command::Log { branch, range, limit, output } => {
+            let commits = integration::get_irmin_commits(&config).await?;
+            let mut selected = commit_export::select_commits(&commits, branch.as_deref(), range.as_deref())?;
+            selected.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
+            if let Some(limit) = limit {
+                selected.truncate(*limit);
+            }
+            output.print(&selected, |commits| {
+                commits
+                    .iter()
+                    .map(|c| {
+                        let short = &c.hash[..c.hash.len().min(8)];
+                        format!("{}  {}  {:<16} {}\n", short, c.timestamp.format("%Y-%m-%d %H:%M"), c.author, c.message)
+                    })
+                    .collect()
+            })?;
+        }
+        Command::Branches { output } => {
+            let branches = integration::get_irmin_branches(&config).await?;
+            output.print(&branches, |branches| {
+                branches
+                    .iter()
+                    .map(|b| {
+                        let short = &b.head_commit[..b.head_commit.len().min(8)];
+                        format!("{:<24} {}  {} commits, updated {}\n", b.name, short, b.commit_count, b.last_updated.format("%Y-%m-%d %H:%M"))
+                    })
+                    .collect()
+            })?;
+        }
+        Command::Diff { from, to, output } => {
+            let diff = integration::get_irmin_diff(&config, from, to).await?;
+            output.print(&diff, |diff| {
+                diff.changes
+                    .iter()
+                    .map(|c| {
+                        let tag = match c.change_type {
+                            ChangeType::Added => 'A',
+                            ChangeType::Modified => 'M',
+                            ChangeType::Deleted => 'D',
+                        };
+                        format!("{} {}\n", tag, c.path)
+                    })
+                    .collect()
+            })?;
+        }
+        Command::Search { query, output } => {
+            let results = integration::search_irmin_keys(&config, query).await?;
+            output.print(&results, |results| {
+                results
+                    .iter()
+                    .map(|r| format!("{:>6.2}  {}\n", r.relevance_score, r.path))
+                    .collect()
+            })?;
+        }
+        Command::Stats { path, revision, largest, output } => {
+            let (commit, root) = tree_at(&config, revision.as_deref()).await?;
+            let report = stats::StatsCache::new()
+                .store_stats(&commit, &root, path, *largest)
+                .map_err(anyhow::Error::msg)?;
+            output.print(&report, |report| {
+                let mut text = format!(
+                    "{} at {}: {} keys, {} directories, {} B\n",
+                    report.path, report.commit, report.key_count, report.directory_count, report.total_size
+                );
+                if !report.file_types.is_empty() {
+                    text.push_str("\nBy type:\n");
+                    for t in &report.file_types {
+                        let name = if t.extension.is_empty() { "(none)" } else { &t.extension };
+                        text.push_str(&format!("  {:<10} {:>6} keys {:>10} B\n", name, t.key_count, t.total_size));
+                    }
+                }
+                if !report.largest_values.is_empty() {
+                    text.push_str("\nLargest values:\n");
+                    for v in &report.largest_values {
+                        text.push_str(&format!("  {:>10} B  {}\n", v.size, v.path));
+                    }
+                }
+                text
+            })?;
+        }
+        Command::ExportCommits { branch, range, format, stats, output } => {
+            let format = commit_export::CommitExportFormat::parse(format).map_err(anyhow::Error::msg)?;
+            let writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout().lock()),
+            };
+            let count = commit_export::export_commits(
+                &config,
+                branch.as_deref(),
+                range.as_deref(),
+                format,
+                *stats,
+                writer,
+            )
+            .await?;
+            if output.is_some() {
+                eprintln!("Exported {} commits", count);
+            }
+        }
+        Command::Patch { from, to, output } => {
+            let text = patch::diff_patch(&config, from, to).await?.to_string();
+            match output {
+                Some(path) => std::fs::write(path, text)?,
+                None => io::stdout().lock().write_all(text.as_bytes())?,
+            }
+        }
+        Command::Apply { patch: patch_file, branch, reverse, dry_run, partial, message } => {
+            let parsed = patch::Patch::parse(&std::fs::read_to_string(patch_file)?)?;
+            let options = patch::ApplyOptions {
+                reverse: *reverse,
+                dry_run: *dry_run,
+                partial: *partial,
+                message: message.clone(),
+            };
+            let report = patch::apply_to_branch(&config, &parsed, branch, &options).await?;
+            for change in &report.changes {
+                let tag = match change.change_type {
+                    ChangeType::Added => 'A',
+                    ChangeType::Modified => 'M',
+                    ChangeType::Deleted => 'D',
+                };
+                println!("{} {}", tag, change.path);
+            }
+            for reject in &report.rejects {
+                eprintln!("rejected {}: {}", reject.path, reject.reason);
+            }
+            match &report.commit {
+                Some(commit) => println!("Committed {} to {}", commit, report.branch),
+                None if report.dry_run => println!("Dry run, nothing committed"),
+                None => println!("Nothing committed"),
+            }
+            if !report.rejects.is_empty() {
+                anyhow::bail!("{} path(s) rejected", report.rejects.len());
+            }
+        }
+        Command::Validate { revision } => {
+            let rules = SchemaRules::load()?;
+            if rules.is_empty() {
+                anyhow::bail!("No schema rules configured in {}", irmin_view::irmin::project::PROJECT_CONFIG_FILE);
+            }
+            let commit = integration::resolve_irmin_revision(&config, revision).await?;
+            let tree = integration::get_irmin_tree_at(&config, &commit).await?;
+            let report = rules.validate_tree(&commit, &tree);
+            for violation in &report.violations {
+                println!("{}{}: {} ({})", violation.path, violation.pointer, violation.message, violation.schema);
+            }
+            println!("Checked {} keys at {}", report.keys_checked, report.commit);
+            if !report.violations.is_empty() {
+                anyhow::bail!("{} schema violation(s)", report.violations.len());
+            }
+        }
+        Command::Snapshot { output, full_history } => {
+            let taken = snapshot::Snapshot::take(&config, *full_history).await?;
+            taken.write(output)?;
+            let info = taken.info(&output.display().to_string());
+            println!(
+                "Saved {} commits, {} branches and {} trees of {} to {}",
+                info.commit_count, info.branch_count, info.tree_count, info.source, info.path
+            );
+        }
+        Command::ClearCache => {
+            let mut cache = cache::shared().lock().map_err(|e| anyhow::anyhow!("{}", e))?;
+            cache.clear()?;
+            match cache.metrics().disk_dir {
+                Some(dir) => println!("Cleared {}", dir),
+                None => println!("No IRMIN_CACHE_DIR set, nothing to clear"),
+            }
+        }
+        Command::Schema { out_dir } => {
+            for path in wire::write_schemas(out_dir)? {
+                println!("{}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
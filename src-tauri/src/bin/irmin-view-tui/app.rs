@@ -0,0 +1,430 @@
+//! State of the terminal UI and its response to keys, independent of drawing.
+
+use irmin_view::irmin::{
+    fuzzy,
+    integration::{self, IrminConfig},
+    render::RendererRegistry,
+    tree,
+    types::*,
+};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashSet;
+
+/// Rows moved by PageUp and PageDown
+const PAGE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Tree,
+    Log,
+    Branches,
+    Diff,
+    Search,
+}
+
+impl View {
+    pub const ALL: [View; 5] = [View::Tree, View::Log, View::Branches, View::Diff, View::Search];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            View::Tree => "Tree",
+            View::Log => "Log",
+            View::Branches => "Branches",
+            View::Diff => "Diff",
+            View::Search => "Search",
+        }
+    }
+
+    fn index(self) -> usize {
+        View::ALL.iter().position(|v| *v == self).unwrap_or_default()
+    }
+}
+
+/// A visible line of the tree pane
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeRow {
+    pub path: String,
+    pub key: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+pub struct App {
+    config: IrminConfig,
+    renderers: RendererRegistry,
+    pub view: View,
+    /// Branch or commit being browsed, the head of the store when `None`
+    pub revision: Option<String>,
+    pub root: Option<IrminNode>,
+    expanded: HashSet<String>,
+    pub tree_selected: usize,
+    pub commits: Vec<IrminCommit>,
+    pub log_selected: usize,
+    pub branches: Vec<IrminBranch>,
+    pub branch_selected: usize,
+    pub diff: Option<IrminDiff>,
+    pub diff_selected: usize,
+    pub query: String,
+    /// Whether keys go to the search box
+    pub editing_query: bool,
+    pub results: Vec<SearchResult>,
+    pub result_selected: usize,
+    pub status: String,
+    pub should_quit: bool,
+}
+
+impl App {
+    pub fn new(config: IrminConfig) -> Self {
+        Self {
+            config,
+            renderers: RendererRegistry::default(),
+            view: View::Tree,
+            revision: None,
+            root: None,
+            expanded: HashSet::new(),
+            tree_selected: 0,
+            commits: Vec::new(),
+            log_selected: 0,
+            branches: Vec::new(),
+            branch_selected: 0,
+            diff: None,
+            diff_selected: 0,
+            query: String::new(),
+            editing_query: false,
+            results: Vec::new(),
+            result_selected: 0,
+            status: String::new(),
+            should_quit: false,
+        }
+    }
+
+    /// (Re)load the tree, commit log and branches from the store
+    pub async fn load(&mut self) {
+        let loaded = async {
+            let root = match &self.revision {
+                Some(revision) => {
+                    let commit = integration::resolve_irmin_revision(&self.config, revision).await?;
+                    integration::get_irmin_tree_at(&self.config, &commit).await?
+                }
+                None => integration::get_irmin_tree(&self.config).await?,
+            };
+            let mut commits = integration::get_irmin_commits(&self.config).await?;
+            commits.sort_by_key(|c| std::cmp::Reverse(c.timestamp));
+            let branches = integration::get_irmin_branches(&self.config).await?;
+            anyhow::Ok((root, commits, branches))
+        }
+        .await;
+        match loaded {
+            Ok((root, commits, branches)) => {
+                self.root = Some(root);
+                self.commits = commits;
+                self.branches = branches;
+                self.clamp_selections();
+                self.status = format!("Loaded {}", self.revision_label());
+            }
+            Err(e) => self.status = format!("Error: {}", e),
+        }
+    }
+
+    pub fn revision_label(&self) -> String {
+        match &self.revision {
+            Some(revision) => revision.clone(),
+            None => "HEAD".to_string(),
+        }
+    }
+
+    /// Visible rows of the tree pane, directories first within each level
+    pub fn tree_rows(&self) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        if let Some(root) = &self.root {
+            self.push_rows(root, "/", 0, &mut rows);
+        }
+        rows
+    }
+
+    fn push_rows(&self, node: &IrminNode, path: &str, depth: usize, rows: &mut Vec<TreeRow>) {
+        let mut children: Vec<(&String, &IrminNode)> = node.children.iter().collect();
+        children.sort_by_key(|(key, child)| (child.node_type != NodeType::Directory, key.as_str()));
+        for (key, child) in children {
+            let child_path = tree::join_path(path, key);
+            let is_dir = child.node_type == NodeType::Directory;
+            let expanded = is_dir && self.expanded.contains(&child_path);
+            rows.push(TreeRow {
+                path: child_path.clone(),
+                key: key.clone(),
+                depth,
+                is_dir,
+                expanded,
+            });
+            if expanded {
+                self.push_rows(child, &child_path, depth + 1, rows);
+            }
+        }
+    }
+
+    /// The node the user is looking at in the current view
+    pub fn selected_node(&self) -> Option<(String, &IrminNode)> {
+        let path = match self.view {
+            View::Tree => self.tree_rows().get(self.tree_selected)?.path.clone(),
+            View::Search => self.results.get(self.result_selected)?.path.clone(),
+            _ => return None,
+        };
+        let node = tree::find_node(self.root.as_ref()?, &path)?;
+        Some((path, node))
+    }
+
+    /// Rendered value of the selected key
+    pub fn preview(&self) -> Option<RenderedValue> {
+        let (path, node) = self.selected_node()?;
+        if node.node_type == NodeType::Directory {
+            return None;
+        }
+        Some(self.renderers.render(&path, node.value.as_deref().unwrap_or_default()))
+    }
+
+    pub fn selected_change(&self) -> Option<&DiffChange> {
+        self.diff.as_ref()?.changes.get(self.diff_selected)
+    }
+
+    pub async fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.should_quit = true;
+            return;
+        }
+        if self.editing_query {
+            match key.code {
+                KeyCode::Enter => {
+                    self.editing_query = false;
+                    self.run_search();
+                }
+                KeyCode::Esc => self.editing_query = false,
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                KeyCode::Char(c) => self.query.push(c),
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.should_quit = true,
+            KeyCode::Tab => self.view = View::ALL[(self.view.index() + 1) % View::ALL.len()],
+            KeyCode::BackTab => self.view = View::ALL[(self.view.index() + View::ALL.len() - 1) % View::ALL.len()],
+            KeyCode::Char(c @ '1'..='5') => self.view = View::ALL[c as usize - '1' as usize],
+            KeyCode::Char('/') => {
+                self.view = View::Search;
+                self.editing_query = true;
+            }
+            KeyCode::Char('r') => self.load().await,
+            KeyCode::Char('H') => {
+                self.revision = None;
+                self.load().await;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(PAGE as isize)),
+            KeyCode::PageDown => self.move_selection(PAGE as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open_selected().await,
+            KeyCode::Left | KeyCode::Char('h') | KeyCode::Backspace => self.collapse_selected(),
+            KeyCode::Char('d') if self.view == View::Log => self.diff_selected_commit().await,
+            _ => {}
+        }
+    }
+
+    fn selection_len(&self) -> usize {
+        match self.view {
+            View::Tree => self.tree_rows().len(),
+            View::Log => self.commits.len(),
+            View::Branches => self.branches.len(),
+            View::Diff => self.diff.as_ref().map(|d| d.changes.len()).unwrap_or_default(),
+            View::Search => self.results.len(),
+        }
+    }
+
+    fn selection_mut(&mut self) -> &mut usize {
+        match self.view {
+            View::Tree => &mut self.tree_selected,
+            View::Log => &mut self.log_selected,
+            View::Branches => &mut self.branch_selected,
+            View::Diff => &mut self.diff_selected,
+            View::Search => &mut self.result_selected,
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.selection_len().saturating_sub(1) as isize;
+        let selected = self.selection_mut();
+        *selected = (*selected as isize + delta).clamp(0, last.max(0)) as usize;
+    }
+
+    fn clamp_selections(&mut self) {
+        let view = self.view;
+        for v in View::ALL {
+            self.view = v;
+            self.move_selection(0);
+        }
+        self.view = view;
+    }
+
+    async fn open_selected(&mut self) {
+        match self.view {
+            View::Tree => {
+                if let Some(row) = self.tree_rows().get(self.tree_selected) {
+                    if row.is_dir && !self.expanded.remove(&row.path) {
+                        self.expanded.insert(row.path.clone());
+                    }
+                }
+            }
+            View::Log => {
+                if let Some(commit) = self.commits.get(self.log_selected) {
+                    self.open_revision(commit.hash.clone()).await;
+                }
+            }
+            View::Branches => {
+                if let Some(branch) = self.branches.get(self.branch_selected) {
+                    self.open_revision(branch.name.clone()).await;
+                }
+            }
+            View::Search => {
+                if let Some(result) = self.results.get(self.result_selected) {
+                    let path = result.path.clone();
+                    self.reveal(&path);
+                }
+            }
+            View::Diff => {}
+        }
+    }
+
+    fn collapse_selected(&mut self) {
+        if self.view != View::Tree {
+            return;
+        }
+        let rows = self.tree_rows();
+        let Some(row) = rows.get(self.tree_selected) else { return };
+        if row.expanded {
+            self.expanded.remove(&row.path);
+        } else if let Some(parent) = rows.iter().position(|r| r.is_dir && tree::join_path(&r.path, &row.key) == row.path) {
+            self.expanded.remove(&rows[parent].path);
+            self.tree_selected = parent;
+        }
+    }
+
+    async fn open_revision(&mut self, revision: String) {
+        self.revision = Some(revision);
+        self.view = View::Tree;
+        self.load().await;
+    }
+
+    async fn diff_selected_commit(&mut self) {
+        let Some(commit) = self.commits.get(self.log_selected).cloned() else { return };
+        let Some(parent) = commit.parents.first() else {
+            self.status = format!("{} has no parent to diff against", commit.hash);
+            return;
+        };
+        match integration::get_irmin_diff(&self.config, parent, &commit.hash).await {
+            Ok(diff) => {
+                self.status = format!("{} changes in {}", diff.changes.len(), commit.hash);
+                self.diff = Some(diff);
+                self.diff_selected = 0;
+                self.view = View::Diff;
+            }
+            Err(e) => self.status = format!("Error: {}", e),
+        }
+    }
+
+    /// Fuzzy search over the keys of the tree being browsed
+    fn run_search(&mut self) {
+        self.results = match &self.root {
+            Some(root) if !self.query.is_empty() => fuzzy::search_tree(root, &self.query),
+            _ => Vec::new(),
+        };
+        self.result_selected = 0;
+        self.status = format!("{} matches for '{}'", self.results.len(), self.query);
+    }
+
+    /// Expand the parents of `path` and select it in the tree pane
+    fn reveal(&mut self, path: &str) {
+        let mut parent = String::new();
+        for segment in path.trim_start_matches('/').split('/') {
+            parent = tree::join_path(if parent.is_empty() { "/" } else { &parent }, segment);
+            if parent != path {
+                self.expanded.insert(parent.clone());
+            }
+        }
+        if let Some(index) = self.tree_rows().iter().position(|r| r.path == path) {
+            self.tree_selected = index;
+        }
+        self.view = View::Tree;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    async fn sample_app() -> App {
+        let mut app = App::new(IrminConfig::new().with_fixtures("sample".to_string()));
+        app.load().await;
+        app
+    }
+
+    #[tokio::test]
+    async fn test_expand_and_collapse_directories() {
+        let mut app = sample_app().await;
+        let keys: Vec<String> = app.tree_rows().iter().map(|r| r.key.clone()).collect();
+        assert_eq!(keys, vec!["logs", "users", "README.md", "config.json"]);
+
+        app.handle_key(press(KeyCode::Down)).await;
+        app.handle_key(press(KeyCode::Enter)).await;
+        assert_eq!(app.tree_rows().len(), 7);
+        app.handle_key(press(KeyCode::Down)).await;
+        assert_eq!(app.selected_node().unwrap().0, "/users/alice.json");
+        assert!(app.preview().unwrap().text.contains("Alice"));
+
+        app.handle_key(press(KeyCode::Left)).await;
+        assert_eq!(app.tree_selected, 1);
+        assert_eq!(app.tree_rows().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_log_opens_commit_and_diff() {
+        let mut app = sample_app().await;
+        app.handle_key(press(KeyCode::Char('2'))).await;
+        app.handle_key(press(KeyCode::End)).await;
+        assert_eq!(app.commits[app.log_selected].hash, "b2a1f6e5d4c3");
+        app.handle_key(press(KeyCode::Up)).await;
+        app.handle_key(press(KeyCode::Char('d'))).await;
+        assert_eq!(app.view, View::Diff);
+        assert_eq!(app.diff.as_ref().unwrap().changes.len(), 3);
+
+        app.handle_key(press(KeyCode::Char('2'))).await;
+        app.handle_key(press(KeyCode::End)).await;
+        app.handle_key(press(KeyCode::Enter)).await;
+        assert_eq!(app.view, View::Tree);
+        assert_eq!(app.revision.as_deref(), Some("b2a1f6e5d4c3"));
+        assert_eq!(app.tree_rows().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_reveals_result_in_tree() {
+        let mut app = sample_app().await;
+        app.handle_key(press(KeyCode::Char('/'))).await;
+        for c in "charlie".chars() {
+            app.handle_key(press(KeyCode::Char(c))).await;
+        }
+        app.handle_key(press(KeyCode::Enter)).await;
+        assert_eq!(app.results[0].path, "/users/charlie.json");
+
+        app.handle_key(press(KeyCode::Enter)).await;
+        assert_eq!(app.view, View::Tree);
+        assert_eq!(app.selected_node().unwrap().0, "/users/charlie.json");
+    }
+}
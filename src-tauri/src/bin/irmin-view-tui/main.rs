@@ -0,0 +1,90 @@
+//! Terminal UI for browsing Irmin stores where there is no display, e.g.
+//! over SSH. It reads stores through the same configuration as the desktop
+//! app and `irmin-view-cli`: the bridge CLI, an HTTP server, a snapshot or
+//! fixture data.
+
+mod app;
+mod view;
+
+use app::App;
+use clap::Parser;
+use irmin_view::irmin::integration::IrminConfig;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "irmin-view-tui", version, about = "Browse Irmin stores in the terminal")]
+struct Cli {
+    /// Path to the Irmin store used by the bridge CLI
+    #[arg(long)]
+    store: Option<String>,
+
+    /// URL of an Irmin bridge HTTP server to use instead of the bridge CLI
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Serve a fixture scenario (built-in name, file, or directory#name) instead of a store
+    #[arg(long)]
+    fixtures: Option<String>,
+
+    /// Serve an offline snapshot file instead of a store
+    #[arg(long)]
+    snapshot: Option<String>,
+
+    /// Branch or commit to open (head of main when omitted)
+    #[arg(long)]
+    revision: Option<String>,
+}
+
+impl Cli {
+    fn config(&self) -> IrminConfig {
+        let mut config = IrminConfig::new();
+        if let Some(store) = &self.store {
+            config = config.with_path(store.clone());
+        }
+        if let Some(server) = &self.server {
+            config = config.with_http_server(server.clone());
+        }
+        if let Some(fixtures) = &self.fixtures {
+            config = config.with_fixtures(fixtures.clone());
+        }
+        if let Some(snapshot) = &self.snapshot {
+            config = config.with_snapshot(snapshot.clone());
+        }
+        config
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut app = App::new(cli.config());
+    app.revision = cli.revision.clone();
+    app.load().await;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app).await;
+    ratatui::restore();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(terminal: &mut ratatui::DefaultTerminal, app: &mut App) -> std::io::Result<()> {
+    while !app.should_quit {
+        terminal.draw(|frame| view::draw(frame, app))?;
+        if event::poll(Duration::from_millis(250))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key).await;
+                }
+            }
+        }
+    }
+    Ok(())
+}
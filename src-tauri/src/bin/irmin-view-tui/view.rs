@@ -0,0 +1,289 @@
+//! Drawing of the terminal UI.
+
+use crate::app::{App, View};
+use irmin_view::irmin::types::*;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    Frame,
+};
+
+const HELP: &str = "Tab/1-5 views  ↑↓ move  Enter open  ← collapse  d diff  / search  r reload  H head  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [header, body, footer] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(2)])
+        .areas(frame.area());
+
+    let titles: Vec<String> = View::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("{} {}", i + 1, v.title()))
+        .collect();
+    let selected = View::ALL.iter().position(|v| *v == app.view).unwrap_or_default();
+    let tabs = Tabs::new(titles)
+        .select(selected)
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .block(Block::default().borders(Borders::ALL).title(format!(" IrminView @ {} ", app.revision_label())));
+    frame.render_widget(tabs, header);
+
+    match app.view {
+        View::Tree => draw_tree(frame, app, body),
+        View::Log => draw_log(frame, app, body),
+        View::Branches => draw_branches(frame, app, body),
+        View::Diff => draw_diff(frame, app, body),
+        View::Search => draw_search(frame, app, body),
+    }
+
+    let footer_text = vec![
+        Line::from(app.status.clone()),
+        Line::from(Span::styled(HELP, Style::default().fg(Color::DarkGray))),
+    ];
+    frame.render_widget(Paragraph::new(footer_text), footer);
+}
+
+fn split(area: Rect) -> [Rect; 2] {
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .areas(area)
+}
+
+fn selectable_list<'a>(items: Vec<ListItem<'a>>, title: &'a str) -> List<'a> {
+    List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+}
+
+fn render_list(frame: &mut Frame, list: List, selected: usize, area: Rect) {
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_tree(frame: &mut Frame, app: &App, area: Rect) {
+    let [left, right] = split(area);
+    let items: Vec<ListItem> = app
+        .tree_rows()
+        .into_iter()
+        .map(|row| {
+            let indent = "  ".repeat(row.depth);
+            let line = match (row.is_dir, row.expanded) {
+                (true, true) => format!("{}▾ {}/", indent, row.key),
+                (true, false) => format!("{}▸ {}/", indent, row.key),
+                (false, _) => format!("{}  {}", indent, row.key),
+            };
+            let style = if row.is_dir { Style::default().fg(Color::Blue) } else { Style::default() };
+            ListItem::new(Span::styled(line, style))
+        })
+        .collect();
+    render_list(frame, selectable_list(items, " Tree "), app.tree_selected, left);
+    draw_preview(frame, app, right);
+}
+
+fn draw_preview(frame: &mut Frame, app: &App, area: Rect) {
+    let (title, text) = match (app.selected_node(), app.preview()) {
+        (Some((path, _)), Some(rendered)) => (format!(" {} ({:?}) ", path, rendered.content_type), highlighted(&rendered)),
+        (Some((path, node)), None) => (format!(" {} ", path), Text::from(format!("{} entries", node.children.len()))),
+        _ => (" Preview ".to_string(), Text::default()),
+    };
+    let preview = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(preview, area);
+}
+
+/// Lines of a rendered value, styled by token kind
+fn highlighted(rendered: &RenderedValue) -> Text<'static> {
+    let mut lines = vec![Line::default()];
+    for token in &rendered.tokens {
+        let style = token_style(token.kind);
+        for (i, part) in token.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Line::default());
+            }
+            if !part.is_empty() {
+                lines.last_mut().expect("lines is not empty").push_span(Span::styled(part.to_string(), style));
+            }
+        }
+    }
+    for error in &rendered.errors {
+        lines.push(Line::from(Span::styled(
+            format!("{}:{}: {}", error.line, error.column, error.message),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    Text::from(lines)
+}
+
+fn token_style(kind: TokenKind) -> Style {
+    let style = Style::default();
+    match kind {
+        TokenKind::Key | TokenKind::Attribute => style.fg(Color::Cyan),
+        TokenKind::String => style.fg(Color::Green),
+        TokenKind::Number | TokenKind::Boolean | TokenKind::Null => style.fg(Color::Yellow),
+        TokenKind::Punctuation | TokenKind::Offset => style.fg(Color::DarkGray),
+        TokenKind::Comment => style.fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        TokenKind::Tag | TokenKind::Heading => style.fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        TokenKind::Emphasis => style.add_modifier(Modifier::ITALIC),
+        TokenKind::Code => style.fg(Color::Yellow),
+        TokenKind::Link => style.fg(Color::Blue).add_modifier(Modifier::UNDERLINED),
+        _ => style,
+    }
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(8)]
+}
+
+fn draw_log(frame: &mut Frame, app: &App, area: Rect) {
+    let [left, right] = split(area);
+    let items: Vec<ListItem> = app
+        .commits
+        .iter()
+        .map(|c| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", short(&c.hash)), Style::default().fg(Color::Yellow)),
+                Span::raw(c.message.clone()),
+            ]))
+        })
+        .collect();
+    render_list(frame, selectable_list(items, " Commits "), app.log_selected, left);
+
+    let details = match app.commits.get(app.log_selected) {
+        Some(c) => Text::from(vec![
+            Line::from(format!("commit  {}", c.hash)),
+            Line::from(format!("parents {}", c.parents.join(", "))),
+            Line::from(format!("branch  {}", c.branch)),
+            Line::from(format!("author  {}", c.author)),
+            Line::from(format!("date    {}", c.timestamp.format("%Y-%m-%d %H:%M:%S"))),
+            Line::default(),
+            Line::from(c.message.clone()),
+        ]),
+        None => Text::default(),
+    };
+    let paragraph = Paragraph::new(details)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(" Commit "));
+    frame.render_widget(paragraph, right);
+}
+
+fn draw_branches(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .branches
+        .iter()
+        .map(|b| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<24}", b.name), Style::default().fg(Color::Green)),
+                Span::styled(format!(" {} ", short(&b.head_commit)), Style::default().fg(Color::Yellow)),
+                Span::raw(format!(
+                    " {} commits, updated {}",
+                    b.commit_count,
+                    b.last_updated.format("%Y-%m-%d %H:%M")
+                )),
+            ]))
+        })
+        .collect();
+    render_list(frame, selectable_list(items, " Branches "), app.branch_selected, area);
+}
+
+fn draw_diff(frame: &mut Frame, app: &App, area: Rect) {
+    let [left, right] = split(area);
+    let Some(diff) = &app.diff else {
+        let hint = Paragraph::new("Select a commit in the log and press d")
+            .block(Block::default().borders(Borders::ALL).title(" Diff "));
+        frame.render_widget(hint, area);
+        return;
+    };
+    let items: Vec<ListItem> = diff
+        .changes
+        .iter()
+        .map(|c| {
+            let (tag, color) = match c.change_type {
+                ChangeType::Added => ('A', Color::Green),
+                ChangeType::Modified => ('M', Color::Yellow),
+                ChangeType::Deleted => ('D', Color::Red),
+            };
+            ListItem::new(Span::styled(format!("{} {}", tag, c.path), Style::default().fg(color)))
+        })
+        .collect();
+    let title = format!(" {}..{} ", short(&diff.from_commit), short(&diff.to_commit));
+    render_list(frame, selectable_list(items, &title), app.diff_selected, left);
+
+    let mut lines = Vec::new();
+    if let Some(change) = app.selected_change() {
+        for line in change.old_value.as_deref().unwrap_or_default().lines() {
+            lines.push(Line::from(Span::styled(format!("- {}", line), Style::default().fg(Color::Red))));
+        }
+        for line in change.new_value.as_deref().unwrap_or_default().lines() {
+            lines.push(Line::from(Span::styled(format!("+ {}", line), Style::default().fg(Color::Green))));
+        }
+    }
+    let values = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(" Values "));
+    frame.render_widget(values, right);
+}
+
+fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
+    let [input, results] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .areas(area);
+    let cursor = if app.editing_query { "▏" } else { "" };
+    let border = if app.editing_query { Style::default().fg(Color::Cyan) } else { Style::default() };
+    let query = Paragraph::new(format!("{}{}", app.query, cursor)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(" Search (/ to edit, Enter to run) "),
+    );
+    frame.render_widget(query, input);
+
+    let [left, right] = split(results);
+    let items: Vec<ListItem> = app
+        .results
+        .iter()
+        .map(|r| {
+            let matched: std::collections::HashSet<usize> = r.matched_indices.iter().copied().collect();
+            let spans: Vec<Span> = r
+                .path
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    let style = if matched.contains(&i) {
+                        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    Span::styled(c.to_string(), style)
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    render_list(frame, selectable_list(items, " Results "), app.result_selected, left);
+    draw_preview(frame, app, right);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use irmin_view::irmin::integration::IrminConfig;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    #[tokio::test]
+    async fn test_draws_every_view() {
+        let mut app = App::new(IrminConfig::new().with_fixtures("sample".to_string()));
+        app.load().await;
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        for view in View::ALL {
+            app.view = view;
+            terminal.draw(|frame| draw(frame, &app)).unwrap();
+        }
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("IrminView @ HEAD"));
+    }
+}
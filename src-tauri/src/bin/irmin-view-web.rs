@@ -0,0 +1,95 @@
+//! Serves the IrminView frontend to plain browsers, without Tauri. The store
+//! is configured as for the desktop app (`IRMIN_USE_HTTP`, `IRMIN_SERVER_URL`,
+//! `IRMIN_FIXTURES`, `IRMIN_SNAPSHOT`) or with the options below.
+
+use clap::Parser;
+use irmin_view::web::{self, WebConfig};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "irmin-view-web", version, about = "Serve IrminView to a browser")]
+struct Cli {
+    /// Address to listen on (IRMIN_WEB_BIND, default 127.0.0.1:8090)
+    #[arg(long)]
+    bind: Option<SocketAddr>,
+
+    /// Directory holding the built frontend (IRMIN_WEB_DIST, default
+    /// frontend/dist beside the executable)
+    #[arg(long)]
+    dist: Option<PathBuf>,
+
+    /// Directory for search indexes
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Allow commands that commit to the store or write files on the server
+    /// (IRMIN_WEB_READ_ONLY=false)
+    #[arg(long)]
+    allow_writes: bool,
+
+    /// URL of an Irmin bridge HTTP server to use instead of the bridge CLI
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Serve a fixture scenario (built-in name, file, or directory#name) instead of a store
+    #[arg(long)]
+    fixtures: Option<String>,
+
+    /// Serve an offline snapshot file instead of a store
+    #[arg(long)]
+    snapshot: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // Commands read the store configuration from the environment; set it
+    // before the runtime starts any threads.
+    if let Some(server) = &cli.server {
+        std::env::set_var("IRMIN_USE_HTTP", "true");
+        std::env::set_var("IRMIN_SERVER_URL", server);
+    }
+    if let Some(fixtures) = &cli.fixtures {
+        std::env::set_var("IRMIN_FIXTURES", fixtures);
+    }
+    if let Some(snapshot) = &cli.snapshot {
+        std::env::set_var("IRMIN_SNAPSHOT", snapshot);
+    }
+
+    let mut config = match WebConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(bind) = cli.bind {
+        config = config.with_bind(bind);
+    }
+    if let Some(dist) = cli.dist {
+        config = config.with_dist_dir(dist);
+    }
+    if let Some(data_dir) = cli.data_dir {
+        config = config.with_data_dir(data_dir);
+    }
+    if cli.allow_writes {
+        config = config.allow_writes();
+    }
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(web::serve(config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
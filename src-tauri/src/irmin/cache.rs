@@ -0,0 +1,261 @@
+//! Response cache in front of the bridge and HTTP backends.
+//!
+//! Data that can never change — the tree at a commit hash, the diff between
+//! two commit hashes — is kept in an in-memory LRU without expiry and, when
+//! `IRMIN_CACHE_DIR` is set, written to disk so it survives restarts. Trees
+//! are stored once per content hash, with each commit pointing at its tree,
+//! so commits sharing a tree share the entry. Everything that moves with the
+//! branch heads (branch list, commit log, head tree, search results) is kept
+//! in memory for a short TTL only.
+//!
+//! | Variable | Default | |
+//! |----------|---------|-|
+//! | `IRMIN_CACHE` | `on` | `off` bypasses the cache |
+//! | `IRMIN_CACHE_ENTRIES` | 256 | In-memory LRU capacity |
+//! | `IRMIN_CACHE_TTL_SECS` | 10 | Lifetime of branch-head entries |
+//! | `IRMIN_CACHE_DIR` | - | Directory of the on-disk cache |
+
+use super::types::*;
+use lru::LruCache;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// In-memory entries kept when `IRMIN_CACHE_ENTRIES` is not set
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Lifetime of branch-head entries when `IRMIN_CACHE_TTL_SECS` is not set
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+/// How long an entry stays valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifetime {
+    /// Addressed by commit or tree hash, valid forever
+    Immutable,
+    /// Depends on branch heads, valid for the cache TTL
+    Head,
+}
+
+struct Entry {
+    json: String,
+    expires: Option<Instant>,
+}
+
+/// LRU of serialized responses, keyed by store and request
+pub struct ResponseCache {
+    entries: LruCache<String, Entry>,
+    ttl: Duration,
+    disk_dir: Option<PathBuf>,
+    hits: u64,
+    disk_hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+            ttl,
+            disk_dir: None,
+            hits: 0,
+            disk_hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Also keep immutable entries as files in `dir`
+    pub fn with_disk_dir(mut self, dir: PathBuf) -> Self {
+        self.disk_dir = Some(dir);
+        self
+    }
+
+    /// Cache configured from the `IRMIN_CACHE_*` environment variables
+    pub fn from_env() -> Self {
+        let capacity = env::var("IRMIN_CACHE_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        let ttl = env::var("IRMIN_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+        let cache = Self::new(capacity, ttl);
+        match env::var("IRMIN_CACHE_DIR").ok().filter(|d| !d.is_empty()) {
+            Some(dir) => cache.with_disk_dir(PathBuf::from(dir)),
+            None => cache,
+        }
+    }
+
+    /// The cached value of `key`, counting a hit or a miss
+    pub fn get<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        let now = Instant::now();
+        if let Some(entry) = self.entries.get(key) {
+            if entry.expires.is_none_or(|expires| expires > now) {
+                if let Ok(value) = serde_json::from_str(&entry.json) {
+                    self.hits += 1;
+                    return Some(value);
+                }
+            }
+            self.entries.pop(key);
+        }
+        if let Some(json) = self.disk_dir.as_ref().and_then(|dir| std::fs::read_to_string(disk_path(dir, key)).ok()) {
+            if let Ok(value) = serde_json::from_str(&json) {
+                self.disk_hits += 1;
+                self.insert(key, json, None);
+                return Some(value);
+            }
+        }
+        self.misses += 1;
+        None
+    }
+
+    pub fn put<T: Serialize>(&mut self, key: &str, value: &T, lifetime: Lifetime) {
+        let json = match serde_json::to_string(value) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        let expires = match lifetime {
+            Lifetime::Immutable => None,
+            Lifetime::Head => Some(Instant::now() + self.ttl),
+        };
+        if lifetime == Lifetime::Immutable {
+            if let Some(dir) = &self.disk_dir {
+                let written = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(disk_path(dir, key), &json));
+                if let Err(e) = written {
+                    eprintln!("Failed to write cache entry to {}: {}", dir.display(), e);
+                }
+            }
+        }
+        self.insert(key, json, expires);
+    }
+
+    fn insert(&mut self, key: &str, json: String, expires: Option<Instant>) {
+        if let Some((evicted, _)) = self.entries.push(key.to_string(), Entry { json, expires }) {
+            if evicted != key {
+                self.evictions += 1;
+            }
+        }
+    }
+
+    /// Drop the branch-head entries of one store, e.g. after committing to it
+    pub fn invalidate_heads(&mut self, scope: &str) {
+        let prefix = format!("{}\n", scope);
+        let stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| entry.expires.is_some() && key.starts_with(&prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            self.entries.pop(&key);
+        }
+    }
+
+    /// Drop every entry, in memory and on disk, and reset the counters
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.entries.clear();
+        self.hits = 0;
+        self.disk_hits = 0;
+        self.misses = 0;
+        self.evictions = 0;
+        if let Some(dir) = &self.disk_dir {
+            if dir.exists() {
+                for entry in std::fs::read_dir(dir)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|ext| ext == "json") {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            hits: self.hits,
+            disk_hits: self.disk_hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            entries: self.entries.len(),
+            capacity: self.entries.cap().get(),
+            ttl_secs: self.ttl.as_secs(),
+            disk_dir: self.disk_dir.as_ref().map(|d| d.display().to_string()),
+        }
+    }
+}
+
+/// Cache key of `request` against the store identified by `scope`
+pub fn key(scope: &str, request: &str) -> String {
+    format!("{}\n{}", scope, request)
+}
+
+fn disk_path(dir: &std::path::Path, key: &str) -> PathBuf {
+    let digest = Sha256::digest(key.as_bytes());
+    let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    dir.join(format!("{}.json", name))
+}
+
+/// Cache shared by every store access of the process
+pub fn shared() -> &'static Mutex<ResponseCache> {
+    static CACHE: OnceLock<Mutex<ResponseCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(ResponseCache::from_env()))
+}
+
+/// Whether `IRMIN_CACHE` leaves the cache switched on
+pub fn enabled_by_env() -> bool {
+    !matches!(env::var("IRMIN_CACHE").as_deref(), Ok("off") | Ok("false") | Ok("0"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_head_entries_expire_and_immutable_entries_do_not() {
+        let mut cache = ResponseCache::new(8, Duration::ZERO);
+        cache.put(&key("s", "branches"), &vec!["main"], Lifetime::Head);
+        cache.put(&key("s", "tree:abc1234"), &"tree", Lifetime::Immutable);
+
+        assert_eq!(cache.get::<Vec<String>>(&key("s", "branches")), None);
+        assert_eq!(cache.get::<String>(&key("s", "tree:abc1234")).as_deref(), Some("tree"));
+        let metrics = cache.metrics();
+        assert_eq!((metrics.hits, metrics.misses, metrics.entries), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_lru_eviction_falls_back_to_disk() {
+        let dir = env::temp_dir().join(format!("irmin-cache-{}", uuid::Uuid::new_v4()));
+        let mut cache = ResponseCache::new(1, DEFAULT_TTL).with_disk_dir(dir.clone());
+        cache.put(&key("s", "diff:a..b"), &1, Lifetime::Immutable);
+        cache.put(&key("s", "diff:b..c"), &2, Lifetime::Immutable);
+        assert_eq!(cache.metrics().evictions, 1);
+
+        assert_eq!(cache.get::<i32>(&key("s", "diff:a..b")), Some(1));
+        assert_eq!(cache.metrics().disk_hits, 1);
+
+        cache.clear().unwrap();
+        assert_eq!(cache.get::<i32>(&key("s", "diff:b..c")), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalidate_heads_keeps_other_stores_and_immutable_entries() {
+        let mut cache = ResponseCache::new(8, DEFAULT_TTL);
+        cache.put(&key("a", "branches"), &1, Lifetime::Head);
+        cache.put(&key("b", "branches"), &2, Lifetime::Head);
+        cache.put(&key("a", "tree:abc1234"), &3, Lifetime::Immutable);
+        cache.invalidate_heads("a");
+
+        assert_eq!(cache.get::<i32>(&key("a", "branches")), None);
+        assert_eq!(cache.get::<i32>(&key("b", "branches")), Some(2));
+        assert_eq!(cache.get::<i32>(&key("a", "tree:abc1234")), Some(3));
+    }
+}
@@ -1,114 +1,720 @@
-use super::{mock_data, integration, demo, types::*};
-use tauri::command;
+use super::{cache, integration, commit_export, export, filter::Filter, fixtures::FixtureStore, history_search, import, operations, patch, query, render, snapshot, stats, tree, types::*, validation::SchemaRules, watcher};
+use super::search_index::{SearchIndex, SearchIndexInfo, SearchIndexStore};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "desktop")]
+use tauri::{command, ipc::Channel, AppHandle, Emitter, Manager};
 
 /// Get the tree structure for display (from real Irmin store)
-#[command]
-pub async fn get_tree() -> Result<IrminNode, String> {
+///
+/// Like the other long-running commands it can be stopped with
+/// `cancel_operation(operation_id)`.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn get_tree(operation_id: Option<String>) -> Result<IrminNode, String> {
     let config = integration::IrminConfig::new();
-    match integration::get_irmin_tree(&config).await {
+    match operations::run(operation_id, integration::get_irmin_tree(&config)).await {
         Ok(tree) => Ok(tree),
+        Err(e) if operations::is_cancelled(&e) => Err(e.to_string()),
         Err(e) => {
             eprintln!("Failed to get tree from Irmin store: {}. Using Irmin-like demo data.", e);
-            // Use demo data that represents what real Irmin data would look like
-            Ok(demo::generate_demo_irmin_tree())
+            FixtureStore::fallback().tree().map_err(|e| e.to_string())
         }
     }
 }
 
-/// Get commits for the commit history view (from real Irmin store)
+/// Entries per message of `stream_tree`
+const STREAM_BATCH: usize = 500;
+
+/// Stream the tree at `commit` (head of main when omitted) to `on_batch` in
+/// batches, for stores too large for one `get_tree` response. Returns the
+/// number of entries sent.
+#[cfg(feature = "desktop")]
 #[command]
+pub async fn stream_tree(
+    commit: Option<String>,
+    on_batch: Channel<TreeBatch>,
+    operation_id: Option<String>,
+) -> Result<usize, String> {
+    stream_tree_with(commit, operation_id, |batch| on_batch.send(batch).map_err(anyhow::Error::from)).await
+}
+
+/// `stream_tree` handing each batch to `on_batch`
+pub async fn stream_tree_with(
+    commit: Option<String>,
+    operation_id: Option<String>,
+    on_batch: impl FnMut(TreeBatch) -> anyhow::Result<()>,
+) -> Result<usize, String> {
+    let config = integration::IrminConfig::new();
+    let mut batches = Batcher::new(on_batch);
+    let stream = integration::stream_irmin_tree(&config, commit.as_deref(), |entries| batches.push(entries));
+    match operations::run(operation_id, stream).await {
+        Ok(_) => {}
+        Err(e) if operations::is_cancelled(&e) => return Err(e.to_string()),
+        Err(e) if batches.sent == 0 => {
+            eprintln!("Failed to stream tree from Irmin store: {}. Using Irmin-like demo data.", e);
+            let fallback = FixtureStore::fallback();
+            let tree = match &commit {
+                Some(commit) => fallback.tree_at(commit).map_err(|_| e.to_string())?,
+                None => fallback.tree().map_err(|e| e.to_string())?,
+            };
+            batches.push(tree::entries(&tree)).map_err(|e| e.to_string())?;
+        }
+        Err(e) => return Err(e.to_string()),
+    }
+    batches.finish().map_err(|e| e.to_string())
+}
+
+/// Regroups streamed entries into batches of `STREAM_BATCH`
+struct Batcher<F> {
+    send: F,
+    pending: Vec<TreeEntry>,
+    sent: usize,
+}
+
+impl<F: FnMut(TreeBatch) -> anyhow::Result<()>> Batcher<F> {
+    fn new(send: F) -> Self {
+        Self { send, pending: Vec::new(), sent: 0 }
+    }
+
+    fn push(&mut self, entries: Vec<TreeEntry>) -> anyhow::Result<()> {
+        self.pending.extend(entries);
+        while self.pending.len() >= STREAM_BATCH {
+            let rest = self.pending.split_off(STREAM_BATCH);
+            let batch = std::mem::replace(&mut self.pending, rest);
+            self.send(batch, false)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<usize> {
+        let batch = std::mem::take(&mut self.pending);
+        self.send(batch, true)?;
+        Ok(self.sent)
+    }
+
+    fn send(&mut self, entries: Vec<TreeEntry>, finished: bool) -> anyhow::Result<()> {
+        self.sent += entries.len();
+        (self.send)(TreeBatch { entries, entries_sent: self.sent, finished })
+    }
+}
+
+/// Get commits for the commit history view (from real Irmin store)
+#[cfg_attr(feature = "desktop", command)]
 pub async fn get_commits() -> Result<Vec<IrminCommit>, String> {
     let config = integration::IrminConfig::new();
     match integration::get_irmin_commits(&config).await {
         Ok(commits) => Ok(commits),
         Err(e) => {
             eprintln!("Failed to get commits from Irmin store: {}. Using Irmin-like demo data.", e);
-            Ok(demo::generate_demo_irmin_commits())
+            Ok(FixtureStore::fallback().commits())
         }
     }
 }
 
 /// Get branches (from real Irmin store)
-#[command]
+#[cfg_attr(feature = "desktop", command)]
 pub async fn get_branches() -> Result<Vec<IrminBranch>, String> {
     let config = integration::IrminConfig::new();
     match integration::get_irmin_branches(&config).await {
         Ok(branches) => Ok(branches),
         Err(e) => {
             eprintln!("Failed to get branches from Irmin store: {}. Using Irmin-like demo data.", e);
-            Ok(demo::generate_demo_irmin_branches())
+            Ok(FixtureStore::fallback().branches())
         }
     }
 }
 
 /// Get a diff between two commits (from real Irmin store)
-#[command]
-pub async fn get_commit_diff(from_commit: String, to_commit: String) -> Result<IrminDiff, String> {
+#[cfg_attr(feature = "desktop", command)]
+pub async fn get_commit_diff(
+    from_commit: String,
+    to_commit: String,
+    operation_id: Option<String>,
+) -> Result<IrminDiff, String> {
     let config = integration::IrminConfig::new();
-    match integration::get_irmin_diff(&config, &from_commit, &to_commit).await {
+    match operations::run(operation_id, integration::get_irmin_diff(&config, &from_commit, &to_commit)).await {
         Ok(diff) => Ok(diff),
+        Err(e) if operations::is_cancelled(&e) => Err(e.to_string()),
         Err(e) => {
-            eprintln!("Failed to get diff from Irmin store: {}. Falling back to mock data.", e);
-            Ok(mock_data::generate_mock_diff(&from_commit, &to_commit))
+            eprintln!("Failed to get diff from Irmin store: {}. Using Irmin-like demo data.", e);
+            FixtureStore::fallback()
+                .diff(&from_commit, &to_commit)
+                .map_err(|e| e.to_string())
         }
     }
 }
 
 /// Search for keys in the tree (from real Irmin store)
+///
+/// The query is answered from the search index of `revision` (head of main
+/// when omitted), which is built on the first search of a commit and derived
+/// from its parent's index when there is one. Without an index store the
+/// store is searched directly. An optional metadata filter (see
+/// `filter_tree`) narrows the results.
+#[cfg(feature = "desktop")]
 #[command]
-pub async fn search_keys(query: String) -> Result<Vec<SearchResult>, String> {
+pub async fn search_keys(
+    app: AppHandle,
+    query: String,
+    revision: Option<String>,
+    filter: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<SearchResult>, String> {
+    let index_store = search_index_store(&app)
+        .map_err(|e| eprintln!("Search index unavailable: {}", e))
+        .ok();
+    search_keys_with(index_store.as_ref(), query, revision, filter, operation_id).await
+}
+
+/// `search_keys` answering from the search indexes in `index_store`, if given
+pub async fn search_keys_with(
+    index_store: Option<&SearchIndexStore>,
+    query: String,
+    revision: Option<String>,
+    filter: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<SearchResult>, String> {
+    let filter = parse_filter(filter.as_deref())?;
+    let operation = operations::Operation::start(operation_id);
+    let results = search_keys_unfiltered(&operation, index_store, &query, revision).await?;
+    Ok(match filter {
+        Some(filter) => results
+            .into_iter()
+            .filter(|r| filter.matches(&r.path, &r.node))
+            .collect(),
+        None => results,
+    })
+}
+
+async fn search_keys_unfiltered(
+    operation: &operations::Operation,
+    index_store: Option<&SearchIndexStore>,
+    query: &str,
+    revision: Option<String>,
+) -> Result<Vec<SearchResult>, String> {
     let config = integration::IrminConfig::new();
-    match integration::search_irmin_keys(&config, &query).await {
+    if let Some(store) = index_store {
+        let revision = revision.unwrap_or_else(|| "main".to_string());
+        match operation.run(load_or_build_index(store, &config, &revision)).await {
+            Ok(index) => return Ok(index.search(query)),
+            Err(e) if operations::is_cancelled(&e) => return Err(e.to_string()),
+            Err(e) => eprintln!("Search index for {} unavailable: {}. Searching the store.", revision, e),
+        }
+    }
+
+    match operation.run(integration::search_irmin_keys(&config, query)).await {
         Ok(results) => Ok(results),
+        Err(e) if operations::is_cancelled(&e) => Err(e.to_string()),
         Err(e) => {
-            eprintln!("Failed to search in Irmin store: {}. Falling back to mock data.", e);
-            let tree = mock_data::generate_mock_tree();
-            let results = search_tree_recursive(&tree, &query, "");
-            Ok(results)
+            eprintln!("Failed to search in Irmin store: {}. Using Irmin-like demo data.", e);
+            FixtureStore::fallback().search(query).map_err(|e| e.to_string())
         }
     }
 }
 
-/// Recursive function to search through the tree
-fn search_tree_recursive(node: &IrminNode, query: &str, current_path: &str) -> Vec<SearchResult> {
-    let mut results = Vec::new();
-    let path = if current_path.is_empty() {
-        node.key.clone()
-    } else {
-        format!("{}/{}", current_path, node.key)
-    };
-    
-    // Check if current node matches
-    if node.key.to_lowercase().contains(&query.to_lowercase()) {
-        let relevance_score = if node.key.to_lowercase() == query.to_lowercase() {
-            1.0
-        } else if node.key.to_lowercase().starts_with(&query.to_lowercase()) {
-            0.8
-        } else {
-            0.5
+fn parse_filter(filter: Option<&str>) -> Result<Option<Filter>, String> {
+    match filter.map(str::trim).filter(|f| !f.is_empty()) {
+        Some(input) => Filter::parse(input)
+            .map(Some)
+            .map_err(|e| format!("Invalid filter: {}", e.display_with_input(input))),
+        None => Ok(None),
+    }
+}
+
+/// Run `work` as the operation `operation_id`, keeping its error messages
+async fn run_as<T>(operation_id: Option<String>, work: impl std::future::Future<Output = Result<T, String>>) -> Result<T, String> {
+    operations::run(operation_id, async { work.await.map_err(anyhow::Error::msg) })
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List every node matching a metadata filter, e.g.
+/// `type = file and ext = json and size > 1MB and age < 7d and path under /logs`
+#[cfg_attr(feature = "desktop", command)]
+pub async fn filter_tree(
+    filter: String,
+    revision: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<FilterMatch>, String> {
+    let filter = parse_filter(Some(&filter))?.ok_or("Filter is empty")?;
+    let tree = run_as(operation_id, async { Trees::open().await.at_revision(revision.as_deref()).await }).await?;
+
+    let mut matches = Vec::new();
+    tree::walk(&tree, &mut |path, depth, node| {
+        if filter.matches(path, node) {
+            matches.push(FilterMatch {
+                path: path.to_string(),
+                depth,
+                node: node.clone(),
+            });
+        }
+    });
+    Ok(matches)
+}
+
+/// Run a JSONPath (`$.role`) or jq-style (`.role | length`) expression against
+/// every value whose path matches `path_glob`, e.g. `users/*.json`
+#[cfg_attr(feature = "desktop", command)]
+pub async fn query_values(
+    path_glob: String,
+    expression: String,
+    revision: Option<String>,
+    max_keys: Option<usize>,
+    operation_id: Option<String>,
+) -> Result<QueryTable, String> {
+    let query = query::Query::parse(&expression).map_err(|e| format!("Invalid query: {}", e))?;
+    let tree = run_as(operation_id, async { Trees::open().await.at_revision(revision.as_deref()).await }).await?;
+    Ok(query::query_tree(&tree, &path_glob, &query, max_keys.unwrap_or(query::DEFAULT_MAX_KEYS)))
+}
+
+/// Trees read by commands that work on revisions and commits. When the store
+/// cannot be reached at all every read is answered from the demo data, like
+/// `get_tree`; once the store has answered, failed reads are errors and are
+/// never filled in with demo data.
+struct Trees {
+    config: integration::IrminConfig,
+    demo: Option<std::sync::Arc<FixtureStore>>,
+}
+
+impl Trees {
+    async fn open() -> Self {
+        let config = integration::IrminConfig::new();
+        let demo = match integration::get_irmin_branches(&config).await {
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Failed to reach Irmin store: {}. Using Irmin-like demo data.", e);
+                Some(FixtureStore::fallback())
+            }
         };
-        
-        results.push(SearchResult {
-            path: path.clone(),
-            node: node.clone(),
-            relevance_score,
+        Self { config, demo }
+    }
+
+    async fn commits(&self) -> Result<Vec<IrminCommit>, String> {
+        match &self.demo {
+            Some(demo) => Ok(demo.commits()),
+            None => integration::get_irmin_commits(&self.config)
+                .await
+                .map_err(|e| format!("Failed to get commits: {}", e)),
+        }
+    }
+
+    /// Commit hash of a branch, or `revision` itself when it names no branch
+    async fn resolve(&self, revision: &str) -> Result<String, String> {
+        let branches = match &self.demo {
+            Some(demo) => demo.branches(),
+            None => integration::get_irmin_branches(&self.config)
+                .await
+                .map_err(|e| format!("Failed to resolve revision {}: {}", revision, e))?,
+        };
+        Ok(branches
+            .into_iter()
+            .find(|b| b.name == revision)
+            .map(|b| b.head_commit)
+            .unwrap_or_else(|| revision.to_string()))
+    }
+
+    async fn at_commit(&self, commit: &str) -> Result<IrminNode, String> {
+        let tree = match &self.demo {
+            Some(demo) => demo.tree_at(commit),
+            None => integration::get_irmin_tree_at(&self.config, commit).await,
+        };
+        tree.map_err(|e| format!("Failed to load tree at {}: {}", commit, e))
+    }
+
+    /// Tree at a revision (branch or commit), or at the head when none is given
+    async fn at_revision(&self, revision: Option<&str>) -> Result<IrminNode, String> {
+        match (revision, &self.demo) {
+            (Some(revision), _) => self.at_commit(&self.resolve(revision).await?).await,
+            (None, Some(demo)) => demo.tree().map_err(|e| format!("Failed to load tree: {}", e)),
+            (None, None) => integration::get_irmin_tree(&self.config)
+                .await
+                .map_err(|e| format!("Failed to load tree: {}", e)),
+        }
+    }
+}
+
+/// Search every commit of `branch` between `since` and `until` (RFC 3339 or
+/// `YYYY-MM-DD`), reporting for each matching key the first and last commit
+/// where the key or its value contained the query
+#[cfg_attr(feature = "desktop", command)]
+pub async fn search_history(
+    query: String,
+    branch: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    operation_id: Option<String>,
+) -> Result<Vec<HistoryMatch>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let since = since.as_deref().map(history_search::parse_time_bound).transpose()?;
+    let until = until.as_deref().map(history_search::parse_time_bound).transpose()?;
+
+    run_as(operation_id, async {
+        let trees = Trees::open().await;
+        let commits = trees.commits().await?;
+        let mut search = history_search::HistorySearch::new(query.trim());
+        for commit in history_search::commits_in_range(&commits, branch.as_deref(), since, until) {
+            let tree = trees.at_commit(&commit.hash).await?;
+            search.scan_commit(&commit, &tree);
+        }
+        Ok(search.finish())
+    })
+    .await
+}
+
+/// Check every value of a revision against the JSON Schema rules of the
+/// project config (`irmin-view.toml`), reporting violations per key
+#[cfg_attr(feature = "desktop", command)]
+pub async fn validate_store(revision: Option<String>) -> Result<ValidationReport, String> {
+    let rules = SchemaRules::load().map_err(|e| e.to_string())?;
+    let trees = Trees::open().await;
+    let commit = trees.resolve(revision.as_deref().unwrap_or("main")).await?;
+    let tree = trees.at_commit(&commit).await?;
+    Ok(rules.validate_tree(&commit, &tree))
+}
+
+/// Detect the content type of the value at `path` of a revision and render
+/// it for display: pretty-printed with syntax tokens, with parse errors, or
+/// as a hex dump for binary content
+#[cfg_attr(feature = "desktop", command)]
+pub async fn render_value(path: String, revision: Option<String>) -> Result<RenderedValue, String> {
+    let tree = Trees::open().await.at_revision(revision.as_deref()).await?;
+    let node = tree::find_node(&tree, &path)
+        .filter(|n| n.node_type != NodeType::Directory)
+        .ok_or_else(|| format!("No value at {}", path))?;
+    let value = node.value.as_deref().unwrap_or_default();
+    Ok(render::RendererRegistry::default().render(&path, value))
+}
+
+/// Size statistics of the directory at `path` of a revision: aggregate
+/// sizes of every subtree, keys per depth, the `largest` biggest values,
+/// a breakdown by file extension and the size over the last `history` commits
+#[cfg_attr(feature = "desktop", command)]
+pub async fn store_stats(
+    revision: Option<String>,
+    path: Option<String>,
+    largest: Option<usize>,
+    history: Option<usize>,
+    operation_id: Option<String>,
+) -> Result<StoreStats, String> {
+    let path = path.unwrap_or_else(|| "/".to_string());
+    let (commit, root, trees) = run_as(operation_id, async {
+        let reader = Trees::open().await;
+        let commit = reader.resolve(revision.as_deref().unwrap_or("main")).await?;
+
+        let commits = reader.commits().await?;
+        let mut trees = Vec::new();
+        for past in stats::recent_history(&commits, &commit, history.unwrap_or(stats::DEFAULT_HISTORY)) {
+            let tree = reader.at_commit(&past.hash).await?;
+            trees.push((past, tree));
+        }
+        let root = match trees.last() {
+            Some((last, tree)) if last.hash == commit => tree.clone(),
+            _ => reader.at_commit(&commit).await?,
+        };
+        Ok((commit, root, trees))
+    })
+    .await?;
+
+    let mut cache = stats_cache().lock().map_err(|e| e.to_string())?;
+    let mut result = cache.store_stats(&commit, &root, &path, largest.unwrap_or(stats::DEFAULT_LARGEST))?;
+    result.growth = trees
+        .iter()
+        .map(|(past, tree)| cache.growth_point(past, tree, &result.path))
+        .collect();
+    Ok(result)
+}
+
+/// Statistics cache shared by every `store_stats` call
+fn stats_cache() -> &'static std::sync::Mutex<stats::StatsCache> {
+    static CACHE: std::sync::OnceLock<std::sync::Mutex<stats::StatsCache>> = std::sync::OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Export the subtree at `path` of a revision to `destination` as a
+/// directory, tar, tar.gz or zip archive, with a manifest naming the source
+/// commit. Progress is reported through `export-progress` events; a
+/// cancelled export stops before its next entry.
+#[cfg(feature = "desktop")]
+#[command]
+pub async fn export_tree(
+    app: AppHandle,
+    path: String,
+    revision: String,
+    format: String,
+    destination: String,
+    operation_id: Option<String>,
+) -> Result<ExportManifest, String> {
+    export_tree_with(path, revision, format, destination, operation_id, move |progress| {
+        if let Err(e) = app.emit("export-progress", progress) {
+            eprintln!("Failed to emit export progress: {}", e);
+        }
+    })
+    .await
+}
+
+/// `export_tree` reporting progress to `on_progress`
+pub async fn export_tree_with(
+    path: String,
+    revision: String,
+    format: String,
+    destination: String,
+    operation_id: Option<String>,
+    mut on_progress: impl FnMut(&ExportProgress) + Send + 'static,
+) -> Result<ExportManifest, String> {
+    let format = export::ExportFormat::parse(&format)?;
+    let config = integration::IrminConfig::new();
+    let operation = operations::Operation::start(operation_id);
+    let commit = operation
+        .run(integration::resolve_irmin_revision(&config, &revision))
+        .await
+        .map_err(|e| format!("Failed to resolve revision {}: {}", revision, e))?;
+    let root = operation
+        .run(integration::get_irmin_tree_at(&config, &commit))
+        .await
+        .map_err(|e| format!("Failed to load tree at {}: {}", commit, e))?;
+    let subtree = tree::find_node(&root, &path)
+        .cloned()
+        .ok_or_else(|| format!("Path {} does not exist at {}", path, commit))?;
+
+    let source = export::ExportSource { commit, revision, path };
+    let destination = PathBuf::from(destination);
+    let cancel = operation.handle();
+    let reporter = operation.progress();
+    tokio::task::spawn_blocking(move || {
+        export::export_tree(&subtree, &source, format, &destination, &mut |progress| {
+            if progress.entries_written <= 1 {
+                reporter.phase("Exporting", Some(progress.total_entries as u64));
+            }
+            reporter.set(progress.entries_written as u64, progress.bytes_written);
+            on_progress(progress);
+            cancel.check()
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Export failed: {}", e))
+}
+
+/// Write the commit log of `branch` (all branches when omitted) within
+/// `range` to `destination` as CSV, pretty JSON or NDJSON, optionally with
+/// per-commit change counts. Returns the number of commits written.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn export_commits(
+    branch: Option<String>,
+    range: Option<String>,
+    format: String,
+    destination: String,
+    stats: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<usize, String> {
+    let format = commit_export::CommitExportFormat::parse(&format)?;
+    let config = integration::IrminConfig::new();
+    let export = commit_export::export_commits_to_file(
+        &config,
+        branch.as_deref(),
+        range.as_deref(),
+        format,
+        stats.unwrap_or(false),
+        Path::new(&destination),
+    );
+    run_as(operation_id, async { export.await.map_err(|e| format!("Failed to export commits: {}", e)) }).await
+}
+
+/// Import a local directory, tar(.gz)/zip archive or nested JSON file into
+/// `branch` as one commit, mounted under `prefix` (the root by default).
+///
+/// With `replace`, files below the prefix that the source lacks are deleted.
+/// A dry run returns the changes without committing them. Cancelling while
+/// the commit is being written does not undo it if the store already took it.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn import_tree(
+    source: String,
+    branch: String,
+    prefix: Option<String>,
+    message: Option<String>,
+    replace: Option<bool>,
+    dry_run: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<ImportReport, String> {
+    run_as(operation_id, import_tree_from(source, branch, prefix, message, replace, dry_run)).await
+}
+
+async fn import_tree_from(
+    source: String,
+    branch: String,
+    prefix: Option<String>,
+    message: Option<String>,
+    replace: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, String> {
+    let source_path = PathBuf::from(&source);
+    let files = tokio::task::spawn_blocking(move || import::read_source(&source_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to read {}: {}", source, e))?;
+
+    let config = integration::IrminConfig::new();
+    let current = integration::get_irmin_branch_tree(&config, &branch)
+        .await
+        .map_err(|e| format!("Failed to load tree of {}: {}", branch, e))?
+        .unwrap_or_else(|| IrminNode {
+            key: "root".to_string(),
+            value: None,
+            node_type: NodeType::Directory,
+            children: Default::default(),
+            metadata: NodeMetadata {
+                last_modified: chrono::Utc::now(),
+                size: None,
+                permissions: None,
+            },
+            hash: None,
         });
+
+    let changes = import::plan_import(&current, &files, prefix.as_deref().unwrap_or("/"), replace.unwrap_or(false));
+    let dry_run = dry_run.unwrap_or(false);
+    let mut report = ImportReport { branch, dry_run, changes, commit: None };
+    if dry_run || report.changes.is_empty() {
+        return Ok(report);
     }
-    
-    // Search in children
-    for child in node.children.values() {
-        results.extend(search_tree_recursive(child, query, &path));
+
+    let request = CommitRequest {
+        branch: report.branch.clone(),
+        message: message.unwrap_or_else(|| format!("Import {}", source)),
+        author: integration::DEFAULT_AUTHOR.to_string(),
+        changes: report.changes.clone(),
+    };
+    let result = integration::commit_irmin_changes(&config, &request)
+        .await
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+    report.commit = Some(result.hash);
+    Ok(report)
+}
+
+/// Serialize the diff between two commits as a portable patch, also writing
+/// it to `destination` when given
+#[cfg_attr(feature = "desktop", command)]
+pub async fn export_patch(
+    from_commit: String,
+    to_commit: String,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let config = integration::IrminConfig::new();
+    let text = patch::diff_patch(&config, &from_commit, &to_commit)
+        .await
+        .map_err(|e| format!("Failed to diff {}..{}: {}", from_commit, to_commit, e))?
+        .to_string();
+    if let Some(destination) = destination {
+        std::fs::write(&destination, &text).map_err(|e| format!("Failed to write {}: {}", destination, e))?;
     }
-    
-    // Sort by relevance score (highest first)
-    results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
-    
-    results
+    Ok(text)
 }
 
-/// Initialize or connect to an Irmin store
+/// Apply a patch file to `branch` as one commit after checking that every
+/// path still holds the value the patch expects. Rejected paths block the
+/// commit unless `partial` is set; a dry run only reports. As with
+/// `import_tree`, cancelling does not undo a commit the store already took.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn apply_patch(
+    patch_file: String,
+    branch: String,
+    reverse: Option<bool>,
+    dry_run: Option<bool>,
+    partial: Option<bool>,
+    message: Option<String>,
+    operation_id: Option<String>,
+) -> Result<PatchReport, String> {
+    let text = std::fs::read_to_string(&patch_file).map_err(|e| format!("Failed to read {}: {}", patch_file, e))?;
+    let parsed = patch::Patch::parse(&text).map_err(|e| format!("Invalid patch {}: {}", patch_file, e))?;
+    let options = patch::ApplyOptions {
+        reverse: reverse.unwrap_or(false),
+        dry_run: dry_run.unwrap_or(false),
+        partial: partial.unwrap_or(false),
+        message,
+    };
+    let config = integration::IrminConfig::new();
+    let apply = patch::apply_to_branch(&config, &parsed, &branch, &options);
+    run_as(operation_id, async { apply.await.map_err(|e| format!("Failed to apply patch: {}", e)) }).await
+}
+
+/// Location of the persistent search indexes in the app data dir
+#[cfg(feature = "desktop")]
+fn search_index_store(app: &AppHandle) -> Result<SearchIndexStore, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(SearchIndexStore::new(data_dir.join("search-index")))
+}
+
+/// Build (or reuse) the search index for a revision.
+///
+/// If the index of a parent commit already exists, the new index is derived
+/// from it by applying the parent-to-child diff instead of loading the tree.
+#[cfg(feature = "desktop")]
 #[command]
+pub async fn build_search_index(app: AppHandle, revision: String) -> Result<SearchIndexInfo, String> {
+    build_search_index_in(&search_index_store(&app)?, revision).await
+}
+
+/// `build_search_index` keeping indexes in `store`
+pub async fn build_search_index_in(store: &SearchIndexStore, revision: String) -> Result<SearchIndexInfo, String> {
+    let config = integration::IrminConfig::new();
+    load_or_build_index(store, &config, &revision)
+        .await
+        .map(|index| index.info())
+        .map_err(|e| e.to_string())
+}
+
+/// The search index of `revision`, derived from a parent's index or built
+/// from the tree and saved to `store` when there is none yet
+async fn load_or_build_index(
+    store: &SearchIndexStore,
+    config: &integration::IrminConfig,
+    revision: &str,
+) -> anyhow::Result<Arc<SearchIndex>> {
+    let commit = integration::resolve_irmin_revision(config, revision)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to resolve revision {}: {}", revision, e))?;
+
+    if let Some(index) = store.load(&commit)? {
+        return Ok(index);
+    }
+
+    let commits = integration::get_irmin_commits(config).await?;
+    let mut index = None;
+    if let Some(child) = commits.iter().find(|c| c.hash == commit) {
+        for parent in &child.parents {
+            let Some(parent_index) = store.load(parent)? else {
+                continue;
+            };
+            match integration::get_irmin_diff(config, parent, &commit).await {
+                Ok(diff) => {
+                    index = Some(parent_index.derive(child, &diff));
+                    break;
+                }
+                Err(e) => eprintln!("Failed to diff {}..{}: {}. Trying next parent.", parent, commit, e),
+            }
+        }
+    }
+
+    let index = match index {
+        Some(index) => index,
+        None => {
+            let tree = integration::get_irmin_tree_at(config, &commit)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to load tree at {}: {}", commit, e))?;
+            SearchIndex::build(&commit, &tree)
+        }
+    };
+
+    store
+        .save(index)
+        .map_err(|e| anyhow::anyhow!("Failed to save search index: {}", e))
+}
+
+/// Initialize or connect to an Irmin store
+#[cfg_attr(feature = "desktop", command)]
 pub async fn connect_to_irmin_store(store_path: Option<String>) -> Result<String, String> {
+    snapshot::set_active(None);
     let config = match store_path {
         Some(path) => integration::IrminConfig::new().with_path(path),
         None => integration::IrminConfig::new(),
@@ -120,12 +726,107 @@ pub async fn connect_to_irmin_store(store_path: Option<String>) -> Result<String
     }
 }
 
+/// Download the branches, commit log and trees of the current connection
+/// into a self-contained snapshot file. Without `full_history` only the
+/// trees at the branch heads are included.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn create_snapshot(
+    destination: String,
+    full_history: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<SnapshotInfo, String> {
+    let config = integration::IrminConfig::new();
+    let take = snapshot::Snapshot::take(&config, full_history.unwrap_or(false));
+    let taken = run_as(operation_id, async { take.await.map_err(|e| format!("Failed to take snapshot: {}", e)) }).await?;
+    taken
+        .write(&PathBuf::from(&destination))
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+    Ok(taken.info(&destination))
+}
+
+/// Open a snapshot file as a read-only local connection; every read command
+/// is served from it until another store is connected
+#[cfg_attr(feature = "desktop", command)]
+pub async fn open_snapshot(path: String) -> Result<IrminConnection, String> {
+    let store = snapshot::open(&path).map_err(|e| e.to_string())?;
+    snapshot::set_active(Some(path.clone()));
+    Ok(IrminConnection {
+        id: uuid::Uuid::new_v4(),
+        name: store.description.clone(),
+        connection_type: ConnectionType::Local,
+        url: None,
+        local_path: Some(path),
+        status: ConnectionStatus::Connected,
+    })
+}
+
 /// Check if Irmin integration is available
-#[command]
+#[cfg_attr(feature = "desktop", command)]
 pub async fn check_irmin_availability() -> Result<bool, String> {
     let config = integration::IrminConfig::new();
     match integration::initialize_irmin_store(&config).await {
         Ok(_) => Ok(true),
         Err(_) => Ok(false),
     }
-}
\ No newline at end of file
+}
+
+/// Cancel the operation started under `operation_id`. Returns false when no
+/// such operation is running, e.g. because it already finished.
+#[cfg_attr(feature = "desktop", command)]
+pub async fn cancel_operation(operation_id: String) -> Result<bool, String> {
+    Ok(operations::cancel(&operation_id))
+}
+
+/// Watch the current store for branches moved outside IrminView and emit a
+/// `branch-updated` event for each. Replaces the watch of an earlier call,
+/// e.g. after connecting to another store. Returns whether heads are polled
+/// rather than watched through filesystem notifications.
+#[cfg(feature = "desktop")]
+#[command]
+pub async fn watch_store(app: AppHandle) -> Result<bool, String> {
+    watch_store_with(move |update| {
+        if let Err(e) = app.emit("branch-updated", &update) {
+            eprintln!("Failed to emit branch update: {}", e);
+        }
+    })
+    .await
+}
+
+/// `watch_store` handing each update to `on_update`
+pub async fn watch_store_with(on_update: impl Fn(BranchUpdate) + Send + Sync + 'static) -> Result<bool, String> {
+    let handle = watcher::Watcher::new(integration::IrminConfig::new())
+        .start(on_update)
+        .await
+        .map_err(|e| format!("Failed to watch store: {}", e))?;
+    let polling = handle.is_polling();
+    *store_watch().lock().map_err(|e| e.to_string())? = Some(handle);
+    Ok(polling)
+}
+
+/// Watch started by the last `watch_store` call
+fn store_watch() -> &'static std::sync::Mutex<Option<watcher::WatchHandle>> {
+    static WATCH: std::sync::OnceLock<std::sync::Mutex<Option<watcher::WatchHandle>>> = std::sync::OnceLock::new();
+    WATCH.get_or_init(Default::default)
+}
+
+/// Hit/miss metrics of the response cache
+#[cfg_attr(feature = "desktop", command)]
+pub async fn cache_stats() -> Result<CacheMetrics, String> {
+    let cache = cache::shared().lock().map_err(|e| e.to_string())?;
+    Ok(cache.metrics())
+}
+
+/// Drop the cached responses that move with the branch heads, so an
+/// explicit refresh reads the store again
+#[cfg_attr(feature = "desktop", command)]
+pub async fn refresh_heads() -> Result<(), String> {
+    integration::invalidate_cached_heads(&integration::IrminConfig::new());
+    Ok(())
+}
+
+/// Drop every cached response, in memory and on disk
+#[cfg_attr(feature = "desktop", command)]
+pub async fn clear_cache() -> Result<(), String> {
+    let mut cache = cache::shared().lock().map_err(|e| e.to_string())?;
+    cache.clear().map_err(|e| format!("Failed to clear cache: {}", e))
+}
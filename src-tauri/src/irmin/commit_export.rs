@@ -0,0 +1,423 @@
+use super::{history_search, integration, tree, types::*};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Column order shared by every export format
+pub const COLUMNS: &[&str] = &["hash", "branch", "timestamp", "author", "message", "parents", "added", "modified", "deleted"];
+
+/// Output formats supported by `export_commits`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl CommitExportFormat {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input.to_lowercase().as_str() {
+            "csv" => Ok(CommitExportFormat::Csv),
+            "json" => Ok(CommitExportFormat::Json),
+            "ndjson" | "jsonl" => Ok(CommitExportFormat::Ndjson),
+            other => Err(format!("Unknown commit export format '{}', expected csv, json or ndjson", other)),
+        }
+    }
+}
+
+/// One exported commit; fields are declared in `COLUMNS` order
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitRecord {
+    pub hash: String,
+    pub branch: String,
+    /// ISO-8601 in UTC, e.g. `2024-01-15T10:30:00Z`
+    pub timestamp: String,
+    pub author: String,
+    pub message: String,
+    pub parents: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<usize>,
+}
+
+impl CommitRecord {
+    pub fn new(commit: &IrminCommit) -> Self {
+        Self {
+            hash: commit.hash.clone(),
+            branch: commit.branch.clone(),
+            timestamp: commit.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+            author: commit.author.clone(),
+            message: commit.message.clone(),
+            parents: commit.parents.clone(),
+            added: None,
+            modified: None,
+            deleted: None,
+        }
+    }
+
+    /// Attach per-change-type counts from a diff
+    pub fn with_stats(mut self, changes: &[DiffChange]) -> Self {
+        let count = |t: ChangeType| changes.iter().filter(|c| c.change_type == t).count();
+        self.added = Some(count(ChangeType::Added));
+        self.modified = Some(count(ChangeType::Modified));
+        self.deleted = Some(count(ChangeType::Deleted));
+        self
+    }
+
+    fn csv_fields(&self) -> [String; 9] {
+        let stat = |s: Option<usize>| s.map(|n| n.to_string()).unwrap_or_default();
+        [
+            self.hash.clone(),
+            self.branch.clone(),
+            self.timestamp.clone(),
+            self.author.clone(),
+            self.message.clone(),
+            self.parents.join(" "),
+            stat(self.added),
+            stat(self.modified),
+            stat(self.deleted),
+        ]
+    }
+}
+
+/// Writes commit records one at a time, so exports never hold the whole
+/// log in memory
+pub struct CommitWriter<W: Write> {
+    writer: W,
+    format: CommitExportFormat,
+    written: usize,
+}
+
+impl<W: Write> CommitWriter<W> {
+    pub fn new(writer: W, format: CommitExportFormat) -> Self {
+        Self { writer, format, written: 0 }
+    }
+
+    pub fn write(&mut self, record: &CommitRecord) -> io::Result<()> {
+        match self.format {
+            CommitExportFormat::Csv => {
+                if self.written == 0 {
+                    self.write_csv_row(COLUMNS.iter().map(|c| c.to_string()))?;
+                }
+                self.write_csv_row(record.csv_fields().into_iter())?;
+            }
+            CommitExportFormat::Json => {
+                self.writer.write_all(if self.written == 0 { b"[\n" } else { b",\n" })?;
+                let pretty = serde_json::to_string_pretty(record)?;
+                let indented: Vec<String> = pretty.lines().map(|line| format!("  {}", line)).collect();
+                self.writer.write_all(indented.join("\n").as_bytes())?;
+            }
+            CommitExportFormat::Ndjson => {
+                serde_json::to_writer(&mut self.writer, record)?;
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        self.written += 1;
+        self.writer.flush()
+    }
+
+    fn write_csv_row(&mut self, fields: impl Iterator<Item = String>) -> io::Result<()> {
+        let row: Vec<String> = fields.map(|f| csv_escape(&f)).collect();
+        self.writer.write_all(row.join(",").as_bytes())?;
+        self.writer.write_all(b"\r\n")
+    }
+
+    /// Close the output (the JSON array, or the CSV header of an empty
+    /// export) and return the number of records written
+    pub fn finish(mut self) -> io::Result<usize> {
+        match self.format {
+            CommitExportFormat::Csv if self.written == 0 => {
+                self.write_csv_row(COLUMNS.iter().map(|c| c.to_string()))?;
+            }
+            CommitExportFormat::Json if self.written == 0 => self.writer.write_all(b"[]\n")?,
+            CommitExportFormat::Json => self.writer.write_all(b"\n]\n")?,
+            _ => {}
+        }
+        self.writer.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) || field.starts_with(' ') || field.ends_with(' ') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Select the commits of `branch` (all branches when `None`) in `range`,
+/// oldest first.
+///
+/// A range is `FROM..TO` where either side may be empty. A side that names a
+/// commit (or a unique hash prefix) selects the commits after FROM up to and
+/// including TO, as in `git log FROM..TO`. Any other side is read as a time:
+/// FROM is inclusive, and TO includes the whole day when given as a date.
+pub fn select_commits(commits: &[IrminCommit], branch: Option<&str>, range: Option<&str>) -> Result<Vec<IrminCommit>> {
+    let mut selected = history_search::commits_in_range(commits, branch, None, None);
+    let Some(range) = range.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(selected);
+    };
+    let Some((from, to)) = range.split_once("..") else {
+        bail!("Invalid range '{}', expected FROM..TO", range);
+    };
+
+    if !to.is_empty() {
+        match find_commit(&selected, to)? {
+            Some(index) => selected.truncate(index + 1),
+            None => {
+                let until = end_of_bound(to)?;
+                selected.retain(|c| c.timestamp < until);
+            }
+        }
+    }
+    if !from.is_empty() {
+        match find_commit(&selected, from)? {
+            Some(index) => {
+                selected.drain(..=index);
+            }
+            None => {
+                let since = history_search::parse_time_bound(from).map_err(anyhow::Error::msg)?;
+                selected.retain(|c| c.timestamp >= since);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Index of the commit named by a full hash or unique prefix
+fn find_commit(commits: &[IrminCommit], name: &str) -> Result<Option<usize>> {
+    if let Some(index) = commits.iter().position(|c| c.hash == name) {
+        return Ok(Some(index));
+    }
+    let matches: Vec<usize> = (0..commits.len()).filter(|&i| commits[i].hash.starts_with(name)).collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [index] => Ok(Some(*index)),
+        _ => bail!("Commit prefix '{}' is ambiguous", name),
+    }
+}
+
+/// Exclusive upper time bound; a plain date covers the whole day
+fn end_of_bound(input: &str) -> Result<DateTime<Utc>> {
+    let time = history_search::parse_time_bound(input).map_err(anyhow::Error::msg)?;
+    if DateTime::parse_from_rfc3339(input).is_ok() {
+        Ok(time + Duration::nanoseconds(1))
+    } else {
+        Ok(time + Duration::days(1))
+    }
+}
+
+/// Stream the commits of `branch` in `range` from the store to `writer`,
+/// optionally with per-commit added/modified/deleted counts against the
+/// first parent. Returns the number of commits written.
+pub async fn export_commits<W: Write>(
+    config: &integration::IrminConfig,
+    branch: Option<&str>,
+    range: Option<&str>,
+    format: CommitExportFormat,
+    with_stats: bool,
+    writer: W,
+) -> Result<usize> {
+    let commits = integration::get_irmin_commits(config).await?;
+    let selected = select_commits(&commits, branch, range)?;
+    write_commits(config, selected, format, with_stats, writer).await
+}
+
+/// `export_commits` to the file `destination`. The range is resolved before
+/// anything is written, and the export goes to a temporary file beside
+/// `destination` that replaces it only once complete, so a failed or
+/// cancelled export leaves an existing file untouched.
+pub async fn export_commits_to_file(
+    config: &integration::IrminConfig,
+    branch: Option<&str>,
+    range: Option<&str>,
+    format: CommitExportFormat,
+    with_stats: bool,
+    destination: &Path,
+) -> Result<usize> {
+    let commits = integration::get_irmin_commits(config).await?;
+    let selected = select_commits(&commits, branch, range)?;
+    let partial = PartialFile::beside(destination)?;
+    let file = File::create(&partial.path).with_context(|| format!("Failed to create {}", partial.path.display()))?;
+    let written = write_commits(config, selected, format, with_stats, io::BufWriter::new(file)).await?;
+    partial.persist(destination)?;
+    Ok(written)
+}
+
+async fn write_commits<W: Write>(
+    config: &integration::IrminConfig,
+    commits: Vec<IrminCommit>,
+    format: CommitExportFormat,
+    with_stats: bool,
+    writer: W,
+) -> Result<usize> {
+    let mut writer = CommitWriter::new(writer, format);
+    for commit in commits {
+        let mut record = CommitRecord::new(&commit);
+        if with_stats {
+            let changes = match commit.parents.first() {
+                Some(parent) => integration::get_irmin_diff(config, parent, &commit.hash).await?.changes,
+                None => initial_changes(&integration::get_irmin_tree_at(config, &commit.hash).await?),
+            };
+            record = record.with_stats(&changes);
+        }
+        writer.write(&record)?;
+    }
+    Ok(writer.finish()?)
+}
+
+/// Temporary file that is removed when dropped unless persisted
+struct PartialFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartialFile {
+    fn beside(destination: &Path) -> Result<Self> {
+        let Some(name) = destination.file_name() else {
+            bail!("{} is not a file path", destination.display());
+        };
+        let name = format!(".{}.{}.part", name.to_string_lossy(), uuid::Uuid::new_v4());
+        Ok(Self { path: destination.with_file_name(name), persisted: false })
+    }
+
+    fn persist(mut self, destination: &Path) -> Result<()> {
+        fs::rename(&self.path, destination).with_context(|| format!("Failed to write {}", destination.display()))?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Every file of a root commit counts as added
+fn initial_changes(root: &IrminNode) -> Vec<DiffChange> {
+    let mut changes = Vec::new();
+    tree::walk(root, &mut |path, _, node| {
+        if node.node_type != NodeType::Directory {
+            changes.push(DiffChange {
+                path: path.to_string(),
+                change_type: ChangeType::Added,
+                old_value: None,
+                new_value: node.value.clone(),
+            });
+        }
+    });
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::{fixtures::FixtureStore, mock_data};
+
+    fn export(format: CommitExportFormat, records: &[CommitRecord]) -> String {
+        let mut buffer = Vec::new();
+        let mut writer = CommitWriter::new(&mut buffer, format);
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), records.len());
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn records() -> Vec<CommitRecord> {
+        let mut commit = mock_data::generate_mock_commits().remove(0);
+        commit.message = "Fix \"quoted\", comma\nand newline".to_string();
+        commit.timestamp = DateTime::parse_from_rfc3339("2024-01-15T10:30:00.123+02:00").unwrap().with_timezone(&Utc);
+        vec![CommitRecord::new(&commit).with_stats(&mock_data::generate_mock_diff("a", "b").changes)]
+    }
+
+    #[test]
+    fn test_csv_has_stable_header_and_quoting() {
+        let csv = export(CommitExportFormat::Csv, &records());
+        let mut lines = csv.split("\r\n");
+        assert_eq!(lines.next().unwrap(), COLUMNS.join(","));
+        let row = lines.next().unwrap();
+        assert!(row.contains(",2024-01-15T08:30:00Z,"));
+        assert!(row.contains("\"Fix \"\"quoted\"\", comma\nand newline\""));
+
+        assert_eq!(export(CommitExportFormat::Csv, &[]), format!("{}\r\n", COLUMNS.join(",")));
+    }
+
+    #[test]
+    fn test_json_and_ndjson_output() {
+        let records = records();
+        let json: serde_json::Value = serde_json::from_str(&export(CommitExportFormat::Json, &records)).unwrap();
+        assert_eq!(json[0]["timestamp"], "2024-01-15T08:30:00Z");
+        assert_eq!(json[0]["added"], 1);
+        let keys: Vec<&String> = json[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys.len(), COLUMNS.len());
+
+        let empty: serde_json::Value = serde_json::from_str(&export(CommitExportFormat::Json, &[])).unwrap();
+        assert_eq!(empty, serde_json::json!([]));
+
+        let ndjson = export(CommitExportFormat::Ndjson, &[records[0].clone(), records[0].clone()]);
+        assert_eq!(ndjson.lines().count(), 2);
+        assert!(ndjson.lines().all(|l| serde_json::from_str::<serde_json::Value>(l).is_ok()));
+    }
+
+    #[test]
+    fn test_stats_follow_sample_scenario() {
+        let store = FixtureStore::load("sample").unwrap();
+        let head = store.commits().remove(0);
+        let diff = store.diff(&head.parents[0], &head.hash).unwrap();
+        let record = CommitRecord::new(&head).with_stats(&diff.changes);
+        assert_eq!((record.added, record.modified, record.deleted), (Some(2), Some(1), Some(0)));
+    }
+
+    #[test]
+    fn test_select_commits_by_hash_and_date() {
+        let commits = mock_data::generate_mock_commits();
+        let main = select_commits(&commits, Some("main"), None).unwrap();
+        let hashes: Vec<&str> = main.iter().map(|c| c.hash.as_str()).collect();
+
+        let after_first = select_commits(&commits, Some("main"), Some(&format!("{}..", hashes[0]))).unwrap();
+        assert_eq!(after_first.len(), main.len() - 1);
+        let up_to_second = select_commits(&commits, Some("main"), Some(&format!("..{}", hashes[1]))).unwrap();
+        assert_eq!(up_to_second.len(), 2);
+
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        let until_today = select_commits(&commits, Some("main"), Some(&format!("2000-01-01..{}", today))).unwrap();
+        assert_eq!(until_today.len(), main.len());
+        assert!(select_commits(&commits, None, Some("yesterday")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_export_replaces_destination_only_when_complete() {
+        let dir = std::env::temp_dir().join(format!("irmin-view-export-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let destination = dir.join("commits.csv");
+        fs::write(&destination, "previous export").unwrap();
+        let config = integration::IrminConfig::new().with_fixtures("sample".to_string());
+
+        let export = |range: &'static str| {
+            let (config, destination) = (config.clone(), destination.clone());
+            async move {
+                export_commits_to_file(&config, None, Some(range), CommitExportFormat::Csv, false, &destination).await
+            }
+        };
+        assert!(export("no-such-commit..").await.is_err());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "previous export");
+
+        let written = export("..").await.unwrap();
+        let csv = fs::read_to_string(&destination).unwrap();
+        assert_eq!(csv.lines().count(), written + 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,452 @@
+use super::{tree, types::*};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use flate2::{write::GzEncoder, Compression};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest written next to the exported subtree
+pub const MANIFEST_FILE: &str = "irmin-export.json";
+
+const DEFAULT_FILE_MODE: u32 = 0o644;
+const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// Output formats supported by `export_tree`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Directory,
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ExportFormat {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input.to_lowercase().as_str() {
+            "dir" | "directory" => Ok(ExportFormat::Directory),
+            "tar" => Ok(ExportFormat::Tar),
+            "tar.gz" | "tgz" | "targz" => Ok(ExportFormat::TarGz),
+            "zip" => Ok(ExportFormat::Zip),
+            other => Err(format!("Unknown export format '{}', expected dir, tar, tar.gz or zip", other)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Directory => "dir",
+            ExportFormat::Tar => "tar",
+            ExportFormat::TarGz => "tar.gz",
+            ExportFormat::Zip => "zip",
+        }
+    }
+}
+
+/// Where an exported subtree came from
+#[derive(Debug, Clone)]
+pub struct ExportSource {
+    pub commit: String,
+    pub revision: String,
+    pub path: String,
+}
+
+/// One file, directory or link to write, relative to the export root
+struct Entry {
+    path: String,
+    node_type: NodeType,
+    data: Vec<u8>,
+    mode: u32,
+}
+
+/// Write `subtree` to `destination` in the given format.
+///
+/// Entries are placed under the subtree's own key (`config/...` when
+/// exporting `/config`) with the manifest at the top level. `progress` is
+/// called after every entry that is written; an error from it stops the
+/// export and is returned, leaving what was written so far in place.
+pub fn export_tree(
+    subtree: &IrminNode,
+    source: &ExportSource,
+    format: ExportFormat,
+    destination: &Path,
+    progress: &mut dyn FnMut(&ExportProgress) -> Result<()>,
+) -> Result<ExportManifest> {
+    let entries = collect_entries(subtree, &source.path)?;
+    let manifest = ExportManifest {
+        source_commit: source.commit.clone(),
+        revision: source.revision.clone(),
+        path: tree::normalize_path(&source.path),
+        format: format.name().to_string(),
+        exported_at: Utc::now(),
+        file_count: entries.iter().filter(|e| e.node_type != NodeType::Directory).count(),
+        directory_count: entries.iter().filter(|e| e.node_type == NodeType::Directory).count(),
+        total_bytes: entries.iter().map(|e| e.data.len() as u64).sum(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+    let mut state = ExportProgress {
+        destination: destination.display().to_string(),
+        current_path: None,
+        entries_written: 0,
+        total_entries: entries.len(),
+        bytes_written: 0,
+        total_bytes: manifest.total_bytes,
+        finished: false,
+    };
+    let mut on_entry = |entry: &Entry| {
+        state.current_path = Some(entry.path.clone());
+        state.entries_written += 1;
+        state.bytes_written += entry.data.len() as u64;
+        progress(&state)
+    };
+
+    match format {
+        ExportFormat::Directory => write_directory(destination, &entries, &manifest_json, &mut on_entry)?,
+        ExportFormat::Tar => {
+            let file = create_file(destination)?;
+            write_tar(tar::Builder::new(file), &entries, &manifest_json, &mut on_entry)?.sync_all()?;
+        }
+        ExportFormat::TarGz => {
+            let encoder = GzEncoder::new(create_file(destination)?, Compression::default());
+            write_tar(tar::Builder::new(encoder), &entries, &manifest_json, &mut on_entry)?
+                .finish()?
+                .sync_all()?;
+        }
+        ExportFormat::Zip => write_zip(create_file(destination)?, &entries, &manifest_json, &mut on_entry)?,
+    }
+
+    state.current_path = None;
+    state.finished = true;
+    progress(&state)?;
+
+    Ok(manifest)
+}
+
+/// Flatten the subtree into entries, parents before children
+fn collect_entries(subtree: &IrminNode, path: &str) -> Result<Vec<Entry>> {
+    let prefix = match tree::normalize_path(path).as_str() {
+        "/" => String::new(),
+        _ => safe_segment(&subtree.key)?.to_string(),
+    };
+
+    let mut entries = Vec::new();
+    if !prefix.is_empty() {
+        entries.push(entry(prefix.clone(), subtree)?);
+    }
+    if subtree.node_type != NodeType::Directory {
+        return Ok(entries);
+    }
+
+    let mut error = None;
+    tree::walk(subtree, &mut |child_path, _, node| {
+        if error.is_some() {
+            return;
+        }
+        let relative = child_path.trim_start_matches('/');
+        let result = relative
+            .split('/')
+            .try_for_each(|segment| safe_segment(segment).map(|_| ()))
+            .and_then(|_| match prefix.as_str() {
+                "" => entry(relative.to_string(), node),
+                prefix => entry(format!("{}/{}", prefix, relative), node),
+            });
+        match result {
+            Ok(e) => entries.push(e),
+            Err(e) => error = Some(e),
+        }
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(entries),
+    }
+}
+
+fn entry(path: String, node: &IrminNode) -> Result<Entry> {
+    let default_mode = match node.node_type {
+        NodeType::Directory => DEFAULT_DIR_MODE,
+        _ => DEFAULT_FILE_MODE,
+    };
+    let mode = node
+        .metadata
+        .permissions
+        .as_deref()
+        .and_then(parse_mode)
+        .unwrap_or(default_mode);
+    Ok(Entry {
+        path,
+        node_type: node.node_type.clone(),
+        data: node.value.clone().unwrap_or_default().into_bytes(),
+        mode,
+    })
+}
+
+/// Reject keys that would escape the export root or be misread as paths
+fn safe_segment(segment: &str) -> Result<&str> {
+    if segment.is_empty() || segment == "." || segment == ".." || segment.contains(['/', '\\', '\0']) {
+        bail!("Key '{}' cannot be exported as a file name", segment);
+    }
+    Ok(segment)
+}
+
+/// Parse `permissions` metadata given as octal ("644", "0755") or as a
+/// symbolic mode ("rw-r--r--")
+pub fn parse_mode(permissions: &str) -> Option<u32> {
+    let permissions = permissions.trim();
+    if permissions.len() == 9 && permissions.chars().all(|c| "rwx-".contains(c)) {
+        let mode = permissions
+            .chars()
+            .zip("rwxrwxrwx".chars())
+            .fold(0, |mode, (actual, expected)| {
+                (mode << 1) | u32::from(actual == expected)
+            });
+        return Some(mode);
+    }
+    u32::from_str_radix(permissions, 8).ok().filter(|mode| *mode <= 0o7777)
+}
+
+fn create_file(destination: &Path) -> Result<File> {
+    if let Some(parent) = destination.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(destination).with_context(|| format!("Failed to create {}", destination.display()))
+}
+
+fn write_directory(
+    destination: &Path,
+    entries: &[Entry],
+    manifest: &[u8],
+    on_entry: &mut dyn FnMut(&Entry) -> Result<()>,
+) -> Result<()> {
+    fs::create_dir_all(destination)
+        .with_context(|| format!("Failed to create {}", destination.display()))?;
+
+    // Directory modes are applied last so read-only directories can still be filled
+    let mut directories: Vec<(PathBuf, u32)> = Vec::new();
+    for entry in entries {
+        let target = destination.join(&entry.path);
+        match entry.node_type {
+            NodeType::Directory => {
+                fs::create_dir_all(&target)?;
+                directories.push((target, entry.mode));
+            }
+            NodeType::File => {
+                fs::write(&target, &entry.data)
+                    .with_context(|| format!("Failed to write {}", target.display()))?;
+                set_mode(&target, entry.mode)?;
+            }
+            NodeType::Link => write_link(&target, &entry.data)?,
+        }
+        on_entry(entry)?;
+    }
+    fs::write(destination.join(MANIFEST_FILE), manifest)?;
+    for (dir, mode) in directories.iter().rev() {
+        set_mode(dir, *mode)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions on {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn write_link(target: &Path, link: &[u8]) -> Result<()> {
+    let link = String::from_utf8_lossy(link).into_owned();
+    std::os::unix::fs::symlink(&link, target)
+        .with_context(|| format!("Failed to create link {}", target.display()))
+}
+
+/// Without symlink support the link target is written as the file content
+#[cfg(not(unix))]
+fn write_link(target: &Path, link: &[u8]) -> Result<()> {
+    fs::write(target, link).with_context(|| format!("Failed to write {}", target.display()))
+}
+
+fn write_tar<W: Write>(
+    mut builder: tar::Builder<W>,
+    entries: &[Entry],
+    manifest: &[u8],
+    on_entry: &mut dyn FnMut(&Entry) -> Result<()>,
+) -> Result<W> {
+    let mtime = Utc::now().timestamp().max(0) as u64;
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(entry.mode);
+        header.set_mtime(mtime);
+        match entry.node_type {
+            NodeType::Directory => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, format!("{}/", entry.path), std::io::empty())?;
+            }
+            NodeType::File => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(entry.data.len() as u64);
+                builder.append_data(&mut header, &entry.path, entry.data.as_slice())?;
+            }
+            NodeType::Link => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                let link = String::from_utf8_lossy(&entry.data).into_owned();
+                builder.append_link(&mut header, &entry.path, link)?;
+            }
+        }
+        on_entry(entry)?;
+    }
+
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(DEFAULT_FILE_MODE);
+    header.set_mtime(mtime);
+    header.set_size(manifest.len() as u64);
+    builder.append_data(&mut header, MANIFEST_FILE, manifest)?;
+    builder.into_inner().map_err(|e| anyhow!("Failed to finish tar archive: {}", e))
+}
+
+fn write_zip(
+    file: File,
+    entries: &[Entry],
+    manifest: &[u8],
+    on_entry: &mut dyn FnMut(&Entry) -> Result<()>,
+) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for entry in entries {
+        let options = options.unix_permissions(entry.mode);
+        match entry.node_type {
+            NodeType::Directory => zip.add_directory(entry.path.as_str(), options)?,
+            NodeType::File => {
+                zip.start_file(entry.path.as_str(), options)?;
+                zip.write_all(&entry.data)?;
+            }
+            NodeType::Link => {
+                let link = String::from_utf8_lossy(&entry.data).into_owned();
+                zip.add_symlink(entry.path.as_str(), link, options)?;
+            }
+        }
+        on_entry(entry)?;
+    }
+    zip.start_file(MANIFEST_FILE, options.unix_permissions(DEFAULT_FILE_MODE))?;
+    zip.write_all(manifest)?;
+    zip.finish()?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::mock_data;
+    use std::io::Read;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("irmin-export-{}-{}", uuid::Uuid::new_v4(), name))
+    }
+
+    fn export(format: ExportFormat, destination: &Path) -> (ExportManifest, Vec<ExportProgress>) {
+        let root = mock_data::generate_mock_tree();
+        let source = ExportSource {
+            commit: "abc123".to_string(),
+            revision: "main".to_string(),
+            path: "/users".to_string(),
+        };
+        let mut events = Vec::new();
+        let manifest = export_tree(&root.children["users"], &source, format, destination, &mut |p| {
+            events.push(p.clone());
+            Ok(())
+        })
+        .unwrap();
+        (manifest, events)
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("644"), Some(0o644));
+        assert_eq!(parse_mode("0755"), Some(0o755));
+        assert_eq!(parse_mode("rwxr-x---"), Some(0o750));
+        assert_eq!(parse_mode("rw-"), None);
+        assert_eq!(parse_mode("99999"), None);
+    }
+
+    #[test]
+    fn test_export_directory_with_manifest_and_progress() {
+        let destination = temp_path("dir");
+        let (manifest, events) = export(ExportFormat::Directory, &destination);
+
+        assert_eq!(manifest.source_commit, "abc123");
+        assert_eq!(manifest.path, "/users");
+        let alice = fs::read_to_string(destination.join("users/alice.json")).unwrap();
+        assert!(alice.contains("alice@example.com"));
+        let written: ExportManifest =
+            serde_json::from_slice(&fs::read(destination.join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(written.source_commit, "abc123");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(destination.join("users/alice.json")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o644);
+        }
+
+        let last = events.last().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.entries_written, last.total_entries);
+        assert_eq!(events.len(), last.total_entries + 1);
+        fs::remove_dir_all(destination).unwrap();
+    }
+
+    #[test]
+    fn test_export_archives() {
+        let tar_gz = temp_path("users.tar.gz");
+        export(ExportFormat::TarGz, &tar_gz);
+        let decoder = flate2::read::GzDecoder::new(File::open(&tar_gz).unwrap());
+        let mut archive = tar::Archive::new(decoder);
+        let mut names = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            names.push(entry.path().unwrap().display().to_string());
+            if entry.path().unwrap().ends_with("alice.json") {
+                assert_eq!(entry.header().mode().unwrap(), 0o644);
+            }
+        }
+        assert!(names.contains(&"users/alice.json".to_string()));
+        assert!(names.contains(&MANIFEST_FILE.to_string()));
+        fs::remove_file(tar_gz).unwrap();
+
+        let zip_path = temp_path("users.zip");
+        export(ExportFormat::Zip, &zip_path);
+        let mut archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let mut manifest = String::new();
+        archive.by_name(MANIFEST_FILE).unwrap().read_to_string(&mut manifest).unwrap();
+        assert!(manifest.contains("abc123"));
+        assert_eq!(archive.by_name("users/bob.json").unwrap().unix_mode().map(|m| m & 0o777), Some(0o644));
+        fs::remove_file(zip_path).unwrap();
+    }
+
+    #[test]
+    fn test_progress_error_stops_export() {
+        let destination = temp_path("stopped");
+        let source = ExportSource {
+            commit: "abc123".to_string(),
+            revision: "main".to_string(),
+            path: "/".to_string(),
+        };
+        let mut calls = 0;
+        let result = export_tree(&mock_data::generate_mock_tree(), &source, ExportFormat::Directory, &destination, &mut |_| {
+            calls += 1;
+            if calls == 2 {
+                anyhow::bail!("stop");
+            }
+            Ok(())
+        });
+        assert_eq!(result.unwrap_err().to_string(), "stop");
+        assert_eq!(calls, 2);
+        fs::remove_dir_all(destination).unwrap();
+    }
+}
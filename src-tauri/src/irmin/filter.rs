@@ -0,0 +1,613 @@
+//! Small filter language over node metadata.
+//!
+//! ```text
+//! type = file and ext = json and size > 1MB and age < 7d and path under /logs
+//! (name ~ "*.toml" or perm = 600) and not depth > 3
+//! modified >= 2024-01-01
+//! ```
+//!
+//! Comparisons are joined with `and`, `or` and `not` (adjacent comparisons are
+//! implicitly joined with `and`) and can be grouped with parentheses.
+
+use super::{tree, types::*};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const FIELDS: &[&str] = &["size", "modified", "age", "perm", "type", "depth", "path", "name", "ext"];
+
+/// Syntax error in a filter expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterError {
+    pub message: String,
+    /// Character offset of the offending token in the input
+    pub position: usize,
+}
+
+impl FilterError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self { message: message.into(), position }
+    }
+
+    /// Render the error with the input and a caret under the offending token
+    pub fn display_with_input(&self, input: &str) -> String {
+        format!("{}\n  {}\n  {}^", self, input, " ".repeat(self.position))
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn eval<T: PartialOrd + ?Sized>(self, lhs: &T, rhs: &T) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+
+    fn is_equality(self) -> bool {
+        matches!(self, CmpOp::Eq | CmpOp::Ne)
+    }
+}
+
+/// Operators on path-like fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchOp {
+    Eq,
+    Ne,
+    Glob,
+    Under,
+}
+
+/// A single comparison against one metadata field
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Size(CmpOp, u64),
+    Modified(CmpOp, DateTime<Utc>),
+    Age(CmpOp, Duration),
+    Permissions(CmpOp, String),
+    Type(CmpOp, NodeType),
+    Depth(CmpOp, usize),
+    Path(MatchOp, String),
+    Name(MatchOp, String),
+    Extension(CmpOp, String),
+}
+
+/// Parsed filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Predicate(Predicate),
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Filter, FilterError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0, input_len: input.chars().count() };
+        let filter = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) if token.kind == TokenKind::RParen => {
+                Err(FilterError::new("unmatched ')'", token.position))
+            }
+            Some(token) => Err(FilterError::new(
+                format!("unexpected '{}', expected 'and', 'or' or end of filter", token.text),
+                token.position,
+            )),
+        }
+    }
+
+    /// Evaluate the filter for the node at `path`
+    pub fn matches(&self, path: &str, node: &IrminNode) -> bool {
+        self.matches_at(path, node, Utc::now())
+    }
+
+    /// Evaluate the filter with a fixed "now", used for `age` comparisons
+    pub fn matches_at(&self, path: &str, node: &IrminNode, now: DateTime<Utc>) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches_at(path, node, now) && b.matches_at(path, node, now),
+            Filter::Or(a, b) => a.matches_at(path, node, now) || b.matches_at(path, node, now),
+            Filter::Not(inner) => !inner.matches_at(path, node, now),
+            Filter::Predicate(predicate) => predicate.matches(path, node, now),
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, path: &str, node: &IrminNode, now: DateTime<Utc>) -> bool {
+        let metadata = &node.metadata;
+        match self {
+            Predicate::Size(op, size) => metadata.size.is_some_and(|s| op.eval(&s, size)),
+            Predicate::Modified(op, date) => op.eval(&metadata.last_modified, date),
+            // `now - modified <op> age` compared as `now - age <op> modified`,
+            // which cannot overflow for huge ages
+            Predicate::Age(op, age) => {
+                let cutoff = now.checked_sub_signed(*age).unwrap_or(DateTime::<Utc>::MIN_UTC);
+                op.eval(&cutoff, &metadata.last_modified)
+            }
+            Predicate::Permissions(op, perm) => metadata
+                .permissions
+                .as_deref()
+                .is_some_and(|p| op.eval(p, perm.as_str())),
+            Predicate::Type(op, node_type) => op.eval(&(node.node_type == *node_type), &true),
+            Predicate::Depth(op, depth) => op.eval(&tree::path_depth(path), depth),
+            Predicate::Path(op, pattern) => match_path(*op, &tree::normalize_path(path), pattern),
+            Predicate::Name(op, pattern) => match_path(*op, &node.key, pattern),
+            Predicate::Extension(op, ext) => {
+                node.node_type != NodeType::Directory && op.eval(tree::extension(&node.key).as_str(), ext.as_str())
+            }
+        }
+    }
+}
+
+fn match_path(op: MatchOp, actual: &str, pattern: &str) -> bool {
+    match op {
+        MatchOp::Eq => actual == pattern,
+        MatchOp::Ne => actual != pattern,
+        MatchOp::Glob => tree::glob_match(pattern, actual),
+        MatchOp::Under => {
+            let prefix = tree::normalize_path(pattern);
+            prefix == "/" || actual == prefix || actual.starts_with(&format!("{}/", prefix))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word,
+    Str,
+    Op,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    position: usize,
+}
+
+fn is_op_char(c: char) -> bool {
+    matches!(c, '=' | '!' | '<' | '>' | '~')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            let kind = if c == '(' { TokenKind::LParen } else { TokenKind::RParen };
+            tokens.push(Token { kind, text: c.to_string(), position: start });
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(FilterError::new("unterminated string", start)),
+                    Some(&q) if q == c => break,
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token { kind: TokenKind::Str, text, position: start });
+        } else if is_op_char(c) {
+            while i < chars.len() && is_op_char(chars[i]) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            if !matches!(text.as_str(), "=" | "==" | "!=" | "<" | "<=" | ">" | ">=" | "~") {
+                return Err(FilterError::new(
+                    format!("unknown operator '{}', expected one of = != < <= > >= ~", text),
+                    start,
+                ));
+            }
+            tokens.push(Token { kind: TokenKind::Op, text, position: start });
+        } else {
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !is_op_char(chars[i])
+                && !matches!(chars[i], '(' | ')' | '"' | '\'')
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token { kind: TokenKind::Word, text, position: start });
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    input_len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Word && t.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn end_error(&self, expected: &str) -> FilterError {
+        FilterError::new(format!("unexpected end of filter, expected {}", expected), self.input_len)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.next();
+            let right = self.parse_and()?;
+            left = Filter::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, FilterError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.next();
+            } else if self.peek().is_none()
+                || self.peek_keyword("or")
+                || self.peek().is_some_and(|t| t.kind == TokenKind::RParen)
+            {
+                break;
+            }
+            let right = self.parse_unary()?;
+            left = Filter::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, FilterError> {
+        if self.peek_keyword("not") {
+            self.next();
+            return Ok(Filter::Not(Box::new(self.parse_unary()?)));
+        }
+
+        let Some(token) = self.next() else {
+            return Err(self.end_error("a comparison such as 'size > 1MB'"));
+        };
+        match token.kind {
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(t) if t.kind == TokenKind::RParen => Ok(inner),
+                    Some(t) => Err(FilterError::new(format!("expected ')' but found '{}'", t.text), t.position)),
+                    None => Err(FilterError::new("unclosed '('", token.position)),
+                }
+            }
+            TokenKind::Word => self.parse_comparison(token).map(Filter::Predicate),
+            _ => Err(FilterError::new(
+                format!("expected a field name but found '{}'", token.text),
+                token.position,
+            )),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Token) -> Result<Predicate, FilterError> {
+        let name = field.text.to_lowercase();
+        let name = match name.as_str() {
+            "permissions" => "perm".to_string(),
+            "extension" => "ext".to_string(),
+            "last_modified" => "modified".to_string(),
+            _ => name,
+        };
+        if !FIELDS.contains(&name.as_str()) {
+            let hint = suggest_field(&name)
+                .map(|f| format!("did you mean '{}'? ", f))
+                .unwrap_or_default();
+            return Err(FilterError::new(
+                format!("unknown field '{}', {}expected one of {}", field.text, hint, FIELDS.join(", ")),
+                field.position,
+            ));
+        }
+
+        let op_token = self.next().ok_or_else(|| self.end_error("an operator after the field"))?;
+        let is_under = op_token.kind == TokenKind::Word && op_token.text.eq_ignore_ascii_case("under");
+        if op_token.kind != TokenKind::Op && !is_under {
+            return Err(FilterError::new(
+                format!("expected an operator after '{}' but found '{}'", field.text, op_token.text),
+                op_token.position,
+            ));
+        }
+
+        let value = self.next().ok_or_else(|| self.end_error("a value after the operator"))?;
+        if !matches!(value.kind, TokenKind::Word | TokenKind::Str) {
+            return Err(FilterError::new(format!("expected a value but found '{}'", value.text), value.position));
+        }
+
+        if matches!(name.as_str(), "path" | "name") {
+            let op = match op_token.text.as_str() {
+                "=" | "==" => MatchOp::Eq,
+                "!=" => MatchOp::Ne,
+                "~" => MatchOp::Glob,
+                _ if is_under && name == "path" => MatchOp::Under,
+                _ => return Err(unsupported_op(&op_token, &name, "=, !=, ~ or under")),
+            };
+            let pattern = if name == "path" && op != MatchOp::Glob {
+                tree::normalize_path(&value.text)
+            } else {
+                value.text
+            };
+            return Ok(if name == "path" { Predicate::Path(op, pattern) } else { Predicate::Name(op, pattern) });
+        }
+
+        if is_under {
+            return Err(unsupported_op(&op_token, &name, "comparison operators"));
+        }
+        let op = match op_token.text.as_str() {
+            "=" | "==" => CmpOp::Eq,
+            "!=" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Ge,
+            _ => return Err(unsupported_op(&op_token, &name, "= != < <= > >=")),
+        };
+
+        match name.as_str() {
+            "size" => parse_size(&value).map(|s| Predicate::Size(op, s)),
+            "modified" => parse_date(&value).map(|d| Predicate::Modified(op, d)),
+            "age" => parse_duration(&value).map(|d| Predicate::Age(op, d)),
+            "depth" => value
+                .text
+                .parse()
+                .map(|d| Predicate::Depth(op, d))
+                .map_err(|_| FilterError::new(format!("invalid depth '{}', expected a whole number", value.text), value.position)),
+            _ if !op.is_equality() => Err(unsupported_op(&op_token, &name, "= or !=")),
+            "perm" => Ok(Predicate::Permissions(op, value.text)),
+            "ext" => Ok(Predicate::Extension(op, value.text.trim_start_matches('.').to_lowercase())),
+            "type" => parse_node_type(&value).map(|t| Predicate::Type(op, t)),
+            _ => unreachable!("field list checked above"),
+        }
+    }
+}
+
+fn unsupported_op(op: &Token, field: &str, supported: &str) -> FilterError {
+    FilterError::new(
+        format!("operator '{}' is not supported for '{}', use {}", op.text, field, supported),
+        op.position,
+    )
+}
+
+fn parse_size(token: &Token) -> Result<u64, FilterError> {
+    let text = token.text.to_uppercase();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let multiplier: u64 = match unit {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => {
+            return Err(FilterError::new(
+                format!("invalid size unit '{}', expected B, KB, MB, GB or TB", token.text.get(split..).unwrap_or(unit)),
+                token.position + split,
+            ))
+        }
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+        .map(|n| (n * multiplier as f64) as u64)
+        .ok_or_else(|| FilterError::new(format!("invalid size '{}', expected e.g. 512, 10KB or 1.5MB", token.text), token.position))
+}
+
+fn parse_duration(token: &Token) -> Result<Duration, FilterError> {
+    let text = token.text.to_lowercase();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let amount: i64 = number.parse().map_err(|_| {
+        FilterError::new(format!("invalid duration '{}', expected e.g. 30m, 12h or 7d", token.text), token.position)
+    })?;
+    let duration = match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" | "min" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => {
+            return Err(FilterError::new(
+                format!("invalid duration unit '{}', expected s, m, h, d or w", token.text.get(split..).unwrap_or(unit)),
+                token.position + split,
+            ))
+        }
+    };
+    duration.ok_or_else(|| FilterError::new(format!("duration '{}' is too large", token.text), token.position))
+}
+
+fn parse_date(token: &Token) -> Result<DateTime<Utc>, FilterError> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(&token.text) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(&token.text, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+        .ok_or_else(|| {
+            FilterError::new(
+                format!("invalid date '{}', expected YYYY-MM-DD or an RFC 3339 timestamp", token.text),
+                token.position,
+            )
+        })
+}
+
+fn parse_node_type(token: &Token) -> Result<NodeType, FilterError> {
+    match token.text.to_lowercase().as_str() {
+        "file" | "f" => Ok(NodeType::File),
+        "directory" | "dir" | "d" => Ok(NodeType::Directory),
+        "link" | "l" => Ok(NodeType::Link),
+        _ => Err(FilterError::new(
+            format!("invalid type '{}', expected file, directory or link", token.text),
+            token.position,
+        )),
+    }
+}
+
+/// Closest known field name within an edit distance of two
+fn suggest_field(name: &str) -> Option<&'static str> {
+    FIELDS
+        .iter()
+        .map(|f| (edit_distance(name, f), *f))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, f)| f)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb { prev } else { 1 + prev.min(row[j]).min(row[j + 1]) };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn file(key: &str, size: u64, age: Duration, now: DateTime<Utc>) -> IrminNode {
+        IrminNode {
+            key: key.to_string(),
+            value: Some(String::new()),
+            node_type: NodeType::File,
+            children: HashMap::new(),
+            metadata: NodeMetadata {
+                last_modified: now - age,
+                size: Some(size),
+                permissions: Some("644".to_string()),
+            },
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn test_example_query() {
+        let now = Utc::now();
+        let filter = Filter::parse("type = file and ext = json and size > 1MB and age < 7d and path under /logs").unwrap();
+
+        let big_recent = file("events.json", 2 << 20, Duration::days(2), now);
+        assert!(filter.matches_at("/logs/2024/events.json", &big_recent, now));
+        assert!(!filter.matches_at("/data/events.json", &big_recent, now));
+        assert!(!filter.matches_at("/logs-old/events.json", &big_recent, now));
+
+        let small = file("events.json", 512, Duration::days(2), now);
+        assert!(!filter.matches_at("/logs/events.json", &small, now));
+
+        let old = file("events.json", 2 << 20, Duration::days(30), now);
+        assert!(!filter.matches_at("/logs/events.json", &old, now));
+    }
+
+    #[test]
+    fn test_boolean_operators_and_grouping() {
+        let now = Utc::now();
+        let node = file("app.toml", 10, Duration::hours(1), now);
+        let filter = Filter::parse("(name ~ \"*.toml\" or perm = 600) not depth > 3").unwrap();
+        assert!(filter.matches_at("/config/app.toml", &node, now));
+        assert!(!filter.matches_at("/a/b/c/d/app.toml", &node, now));
+
+        let filter = Filter::parse("modified >= 2000-01-01 and type != dir").unwrap();
+        assert!(filter.matches_at("/app.toml", &node, now));
+
+        let dotfile = file(".gitignore", 10, Duration::hours(1), now);
+        assert!(!Filter::parse("ext = gitignore").unwrap().matches_at("/.gitignore", &dotfile, now));
+        assert!(Filter::parse("ext = \"\"").unwrap().matches_at("/.gitignore", &dotfile, now));
+    }
+
+    #[test]
+    fn test_syntax_errors_point_at_token() {
+        let err = Filter::parse("sise > 1MB").unwrap_err();
+        assert_eq!(err.position, 0);
+        assert!(err.message.contains("did you mean 'size'"));
+
+        let err = Filter::parse("size > 1XB").unwrap_err();
+        assert_eq!(err.position, 8);
+        assert!(err.message.contains("size unit"));
+
+        let err = Filter::parse("type < file").unwrap_err();
+        assert_eq!(err.position, 5);
+
+        let err = Filter::parse("(size > 1 or depth = 2").unwrap_err();
+        assert_eq!(err.message, "unclosed '('");
+
+        let err = Filter::parse("size >").unwrap_err();
+        assert!(err.message.contains("unexpected end"));
+        assert_eq!(err.position, 6);
+
+        let rendered = Filter::parse("age < 7y").unwrap_err().display_with_input("age < 7y");
+        assert!(rendered.ends_with("       ^"));
+    }
+
+    #[test]
+    fn test_huge_durations_are_errors_not_panics() {
+        let err = Filter::parse("age < 9999999999999999w").unwrap_err();
+        assert_eq!(err.position, 6);
+        assert!(err.message.contains("too large"));
+
+        let now = Utc::now();
+        let node = file("old.txt", 1, Duration::try_weeks(52 * 100).unwrap(), now);
+        let filter = Filter::parse("age < 1000000000w").unwrap();
+        assert!(filter.matches_at("/old.txt", &node, now));
+        assert!(!Filter::parse("age > 1000000000w").unwrap().matches_at("/old.txt", &node, now));
+    }
+}
@@ -0,0 +1,818 @@
+//! In-memory stores served from JSON fixtures.
+//!
+//! A scenario lists commits (with the changes each one made to its first
+//! parent), branches, optional full-value diffs and optionally the tree at one
+//! commit. The tree at every other commit is rebuilt from that anchor by
+//! applying and reverting changes along the commit graph, so trees, commits,
+//! branches and diffs served from a scenario always agree with each other:
+//!
+//! ```json
+//! {
+//!   "description": "...",
+//!   "tree": { "key": "/", "node_type": "Directory", ... },
+//!   "tree_commit": "a1b2c3",
+//!   "commits": [{ "hash": "a1b2c3", "message": "...", "author": "...",
+//!                 "timestamp": "2024-01-15T10:25:00Z", "parents": ["f6e5d4"],
+//!                 "branch": "main",
+//!                 "changes": [{ "path": "/config.json", "type": "modified",
+//!                               "old_value": "...", "new_value": "..." }] }],
+//!   "branches": [{ "name": "main", "head_commit": "a1b2c3" }],
+//!   "diffs": [{ "from_commit": "f6e5d4", "to_commit": "a1b2c3", "changes": [...] }]
+//! }
+//! ```
+//!
+//! Changes without values get a placeholder naming the path and commit.
+//! Without commits the tree is served as a single commit on `main`; without
+//! branches, each commit's `branch` field names the branch it heads.
+//! Branches whose head is not in the scenario are dropped and commit counts
+//! and update times are derived from the graph.
+//!
+//! The `demo`, `sample` and `enterprise` scenarios are built from the files
+//! in `assets/` at compile time. Others are loaded from a scenario file or a
+//! directory of them, see [`FixtureStore::load`].
+
+use super::{fuzzy, tree, types::*};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Scenario served when nothing else is configured
+pub const DEFAULT_SCENARIO: &str = "demo";
+
+/// Names of the scenarios built into the binary
+pub const EMBEDDED_SCENARIOS: &[&str] = &["demo", "sample", "enterprise"];
+
+const DEMO_COMMITS: &str = include_str!("../../../assets/demo-data/demo-commits.json");
+const DEMO_BRANCHES: &str = include_str!("../../../assets/demo-data/demo-branches.json");
+const DEMO_DIFFS: &str = include_str!("../../../assets/demo-data/demo-diffs.json");
+const ENTERPRISE_TREE: &str = include_str!("../../../assets/demo-data/enterprise-tree.json");
+const SAMPLE_TREE: &str = include_str!("../../../assets/mock-data/sample-tree.json");
+const SAMPLE_HISTORY: &str = include_str!("../../../assets/mock-data/sample-history.json");
+
+/// Scenario file as written on disk
+#[derive(Debug, Default, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tree: Option<IrminNode>,
+    /// Commit the tree belongs to, the head of the default branch when omitted
+    #[serde(default)]
+    pub tree_commit: Option<String>,
+    #[serde(default)]
+    pub commits: Vec<FixtureCommit>,
+    #[serde(default)]
+    pub branches: Vec<FixtureBranch>,
+    #[serde(default)]
+    pub diffs: Vec<IrminDiff>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureCommit {
+    pub hash: String,
+    pub message: String,
+    pub author: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    #[serde(default)]
+    pub branch: String,
+    #[serde(default)]
+    pub changes: Vec<FixtureChange>,
+}
+
+/// Change made by a commit; `type` is matched case-insensitively
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureChange {
+    pub path: String,
+    #[serde(alias = "type")]
+    pub change_type: String,
+    #[serde(default)]
+    pub old_value: Option<String>,
+    #[serde(default)]
+    pub new_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureBranch {
+    pub name: String,
+    pub head_commit: String,
+}
+
+impl Scenario {
+    fn parse(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    fn merge(mut self, other: Scenario) -> Self {
+        if self.description.is_empty() {
+            self.description = other.description;
+        }
+        self.tree = self.tree.or(other.tree);
+        self.tree_commit = self.tree_commit.or(other.tree_commit);
+        self.commits.extend(other.commits);
+        self.branches.extend(other.branches);
+        self.diffs.extend(other.diffs);
+        self
+    }
+
+    /// Build one of the scenarios compiled into the binary
+    pub fn embedded(name: &str) -> Result<Self> {
+        let scenario = match name {
+            "demo" => Scenario::parse(DEMO_COMMITS)?
+                .merge(Scenario::parse(DEMO_BRANCHES)?)
+                .merge(Scenario::parse(DEMO_DIFFS)?),
+            "sample" => Scenario::parse(SAMPLE_HISTORY)?.merge(tree_scenario(SAMPLE_TREE)?),
+            "enterprise" => tree_scenario(ENTERPRISE_TREE)?,
+            _ => bail!("Unknown fixture scenario '{}' (built in: {})", name, EMBEDDED_SCENARIOS.join(", ")),
+        };
+        Ok(Scenario { name: name.to_string(), ..scenario })
+    }
+}
+
+fn tree_scenario(text: &str) -> Result<Scenario> {
+    let json: serde_json::Value = serde_json::from_str(text)?;
+    let description = json.get("description").and_then(|d| d.as_str()).unwrap_or_default().to_string();
+    Ok(Scenario {
+        description,
+        tree: Some(serde_json::from_value(json)?),
+        ..Scenario::default()
+    })
+}
+
+/// A file or link in the tree of one commit
+#[derive(Debug, Clone)]
+struct Entry {
+    node_type: NodeType,
+    value: Option<String>,
+    metadata: NodeMetadata,
+}
+
+type Files = BTreeMap<String, Entry>;
+
+/// Read-only store holding the tree of every commit of a scenario
+#[derive(Debug, Clone)]
+pub struct FixtureStore {
+    pub name: String,
+    pub description: String,
+    commits: Vec<IrminCommit>,
+    branches: Vec<IrminBranch>,
+    states: HashMap<String, Files>,
+}
+
+impl FixtureStore {
+    /// Load a store by spec, caching it for the lifetime of the process.
+    ///
+    /// The spec is the name of a built-in scenario, the path of a scenario
+    /// file, or a directory of `<name>.json` scenario files followed by
+    /// `#<name>` (which may be left out when the directory holds only one).
+    pub fn load(spec: &str) -> Result<Arc<FixtureStore>> {
+        static STORES: OnceLock<Mutex<HashMap<String, Arc<FixtureStore>>>> = OnceLock::new();
+        let stores = STORES.get_or_init(Default::default);
+        if let Some(store) = stores.lock().unwrap().get(spec) {
+            return Ok(store.clone());
+        }
+        let store = Arc::new(FixtureStore::build(load_scenario(spec)?)?);
+        stores.lock().unwrap().insert(spec.to_string(), store.clone());
+        Ok(store)
+    }
+
+    /// The default built-in scenario, served when a real store is unavailable
+    pub fn fallback() -> Arc<FixtureStore> {
+        FixtureStore::load(DEFAULT_SCENARIO).expect("built-in fixture scenario is valid")
+    }
+
+    pub fn build(scenario: Scenario) -> Result<Self> {
+        let mut scenario = scenario;
+        if scenario.commits.is_empty() {
+            let tree = scenario
+                .tree
+                .as_ref()
+                .ok_or_else(|| anyhow!("Scenario '{}' has neither a tree nor commits", scenario.name))?;
+            scenario.commits.push(snapshot_commit(&scenario.name, tree));
+        }
+
+        let index: HashMap<&str, &FixtureCommit> = scenario.commits.iter().map(|c| (c.hash.as_str(), c)).collect();
+        let hints = value_hints(&scenario);
+        let deltas: HashMap<&str, Vec<DiffChange>> = scenario
+            .commits
+            .iter()
+            .map(|c| Ok((c.hash.as_str(), commit_delta(c, &scenario.diffs, &hints)?)))
+            .collect::<Result<_>>()?;
+        let parent_of = |c: &FixtureCommit| c.parents.first().filter(|p| index.contains_key(p.as_str())).cloned();
+
+        let commits: Vec<IrminCommit> = scenario
+            .commits
+            .iter()
+            .map(|c| IrminCommit {
+                hash: c.hash.clone(),
+                message: c.message.clone(),
+                author: c.author.clone(),
+                timestamp: c.timestamp,
+                parents: c.parents.clone(),
+                branch: if c.branch.is_empty() { "main".to_string() } else { c.branch.clone() },
+            })
+            .collect();
+        let branches = derive_branches(&scenario, &commits, &index);
+
+        let mut states: HashMap<String, Files> = HashMap::new();
+        if let Some(tree) = &scenario.tree {
+            let anchor = scenario
+                .tree_commit
+                .clone()
+                .or_else(|| default_head(&branches, &commits))
+                .unwrap_or_default();
+            if !index.contains_key(anchor.as_str()) {
+                bail!("Tree commit '{}' is not a commit of the scenario", anchor);
+            }
+            states.insert(anchor, files_of(tree));
+        }
+
+        // Walk the graph outwards from what is known: children by applying
+        // their changes, parents by reverting them, and roots from scratch
+        // once nothing else can be reached.
+        loop {
+            let mut progressed = false;
+            for commit in &scenario.commits {
+                let parent = parent_of(commit);
+                match (states.contains_key(&commit.hash), &parent) {
+                    (false, Some(p)) if states.contains_key(p) => {
+                        let files = apply(&states[p], &deltas[commit.hash.as_str()], commit.timestamp);
+                        states.insert(commit.hash.clone(), files);
+                        progressed = true;
+                    }
+                    (true, Some(p)) if !states.contains_key(p) => {
+                        let timestamp = index[p.as_str()].timestamp;
+                        let files = revert(&states[&commit.hash], &deltas[commit.hash.as_str()], timestamp);
+                        states.insert(p.clone(), files);
+                        progressed = true;
+                    }
+                    _ => {}
+                }
+            }
+            if progressed {
+                continue;
+            }
+            let root = scenario
+                .commits
+                .iter()
+                .find(|c| !states.contains_key(&c.hash) && parent_of(c).is_none());
+            match root {
+                Some(root) => {
+                    let files = apply(&Files::new(), &deltas[root.hash.as_str()], root.timestamp);
+                    states.insert(root.hash.clone(), files);
+                }
+                None => break,
+            }
+        }
+
+        Ok(FixtureStore {
+            name: scenario.name,
+            description: scenario.description,
+            commits,
+            branches,
+            states,
+        })
+    }
+
+    /// Store serving the given trees as they are. Commits without a tree stay
+    /// in the log, but their tree and diffs cannot be read.
+    pub fn from_trees(
+        name: String,
+        description: String,
+        commits: Vec<IrminCommit>,
+        branches: Vec<IrminBranch>,
+        trees: &HashMap<String, IrminNode>,
+    ) -> Self {
+        FixtureStore {
+            name,
+            description,
+            commits,
+            branches,
+            states: trees.iter().map(|(commit, tree)| (commit.clone(), files_of(tree))).collect(),
+        }
+    }
+
+    /// Commits in the order the scenario lists them
+    pub fn commits(&self) -> Vec<IrminCommit> {
+        self.commits.clone()
+    }
+
+    pub fn branches(&self) -> Vec<IrminBranch> {
+        self.branches.clone()
+    }
+
+    /// Tree at the head of `main`, or of the first branch when there is none
+    pub fn tree(&self) -> Result<IrminNode> {
+        let head = default_head(&self.branches, &self.commits)
+            .ok_or_else(|| anyhow!("Scenario '{}' has no commits", self.name))?;
+        self.tree_at(&head)
+            .with_context(|| format!("Cannot read the head of scenario '{}'", self.name))
+    }
+
+    pub fn tree_at(&self, commit: &str) -> Result<IrminNode> {
+        let hash = self.resolve(commit)?;
+        let timestamp = self.commit(&hash).map(|c| c.timestamp).unwrap_or_else(Utc::now);
+        Ok(build_tree(self.files(&hash)?, timestamp))
+    }
+
+    /// Changes between the trees of two commits, ordered by path
+    pub fn diff(&self, from_commit: &str, to_commit: &str) -> Result<IrminDiff> {
+        let from = self.files(&self.resolve(from_commit)?)?;
+        let to = self.files(&self.resolve(to_commit)?)?;
+        let mut changes = Vec::new();
+        for (path, old) in from {
+            match to.get(path) {
+                None => changes.push(DiffChange {
+                    path: path.clone(),
+                    change_type: ChangeType::Deleted,
+                    old_value: old.value.clone(),
+                    new_value: None,
+                }),
+                Some(new) if new.value != old.value => changes.push(DiffChange {
+                    path: path.clone(),
+                    change_type: ChangeType::Modified,
+                    old_value: old.value.clone(),
+                    new_value: new.value.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (path, new) in to {
+            if !from.contains_key(path) {
+                changes.push(DiffChange {
+                    path: path.clone(),
+                    change_type: ChangeType::Added,
+                    old_value: None,
+                    new_value: new.value.clone(),
+                });
+            }
+        }
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(IrminDiff {
+            from_commit: from_commit.to_string(),
+            to_commit: to_commit.to_string(),
+            changes,
+        })
+    }
+
+    /// A copy of the store with `request` committed on top of its branch,
+    /// creating the branch if needed, and the hash of the new commit
+    pub fn committed(&self, request: &CommitRequest, timestamp: DateTime<Utc>) -> Result<(FixtureStore, String)> {
+        let parent = self.branches.iter().find(|b| b.name == request.branch);
+        let files = match parent {
+            Some(branch) => apply(self.files(&branch.head_commit)?, &request.changes, timestamp),
+            None => apply(&Files::new(), &request.changes, timestamp),
+        };
+        let mut hasher = Sha256::new();
+        for part in [
+            parent.map(|b| b.head_commit.as_str()).unwrap_or_default(),
+            &tree::content_hash(&build_tree(&files, timestamp)),
+            &request.author,
+            &request.message,
+            &timestamp.to_rfc3339(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update(b"\0");
+        }
+        let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut store = self.clone();
+        store.commits.insert(
+            0,
+            IrminCommit {
+                hash: hash.clone(),
+                message: request.message.clone(),
+                author: request.author.clone(),
+                timestamp,
+                parents: parent.map(|b| vec![b.head_commit.clone()]).unwrap_or_default(),
+                branch: request.branch.clone(),
+            },
+        );
+        let branch = IrminBranch {
+            name: request.branch.clone(),
+            head_commit: hash.clone(),
+            last_updated: timestamp,
+            commit_count: parent.map(|b| b.commit_count).unwrap_or_default() + 1,
+        };
+        match store.branches.iter_mut().find(|b| b.name == request.branch) {
+            Some(existing) => *existing = branch,
+            None => store.branches.push(branch),
+        }
+        store.states.insert(hash.clone(), files);
+        Ok((store, hash))
+    }
+
+    /// Fuzzy search over the paths of the head tree
+    pub fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        Ok(fuzzy::search_tree(&self.tree()?, query))
+    }
+
+    fn files(&self, hash: &str) -> Result<&Files> {
+        self.states
+            .get(hash)
+            .ok_or_else(|| anyhow!("The tree of commit {} is not part of '{}'", hash, self.name))
+    }
+
+    fn commit(&self, hash: &str) -> Option<&IrminCommit> {
+        self.commits.iter().find(|c| c.hash == hash)
+    }
+
+    /// Resolve a branch name, full hash or unique hash prefix
+    fn resolve(&self, revision: &str) -> Result<String> {
+        if let Some(branch) = self.branches.iter().find(|b| b.name == revision) {
+            return Ok(branch.head_commit.clone());
+        }
+        let matches: Vec<&IrminCommit> = self.commits.iter().filter(|c| c.hash.starts_with(revision)).collect();
+        match matches.as_slice() {
+            _ if revision.is_empty() => bail!("Empty revision"),
+            [commit] => Ok(commit.hash.clone()),
+            [] => bail!("Unknown commit '{}' in fixture scenario '{}'", revision, self.name),
+            _ => bail!("Ambiguous commit prefix '{}'", revision),
+        }
+    }
+}
+
+fn load_scenario(spec: &str) -> Result<Scenario> {
+    if EMBEDDED_SCENARIOS.contains(&spec) {
+        return Scenario::embedded(spec);
+    }
+    let (path, name) = match spec.rsplit_once('#') {
+        Some((path, name)) => (Path::new(path), Some(name)),
+        None => (Path::new(spec), None),
+    };
+    if !path.exists() {
+        bail!(
+            "Fixture scenario '{}' is neither built in ({}) nor an existing path",
+            spec,
+            EMBEDDED_SCENARIOS.join(", ")
+        );
+    }
+    let file = if path.is_dir() {
+        let mut names: Vec<String> = std::fs::read_dir(path)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().to_str()?.strip_suffix(".json").map(str::to_string))
+            .collect();
+        names.sort();
+        let chosen = match (name, names.as_slice()) {
+            (Some(name), _) if names.iter().any(|n| n == name) => name.to_string(),
+            (None, [only]) => only.clone(),
+            _ => bail!("Choose a scenario in {} with '#<name>' (available: {})", path.display(), names.join(", ")),
+        };
+        path.join(format!("{}.json", chosen))
+    } else {
+        path.to_path_buf()
+    };
+    let text = std::fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut scenario = Scenario::parse(&text).with_context(|| format!("Invalid scenario in {}", file.display()))?;
+    if scenario.name.is_empty() {
+        scenario.name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    }
+    Ok(scenario)
+}
+
+/// Single commit standing for a scenario that only has a tree
+fn snapshot_commit(name: &str, tree: &IrminNode) -> FixtureCommit {
+    let mut timestamp = tree.metadata.last_modified;
+    tree::walk(tree, &mut |_, _, node| timestamp = timestamp.max(node.metadata.last_modified));
+    FixtureCommit {
+        hash: tree::content_hash(tree),
+        message: format!("Load {} fixture", name),
+        author: "IrminView".to_string(),
+        timestamp,
+        parents: Vec::new(),
+        branch: "main".to_string(),
+        changes: Vec::new(),
+    }
+}
+
+/// Known earlier values of paths, used for changes that do not carry one
+fn value_hints(scenario: &Scenario) -> HashMap<String, String> {
+    let mut hints = HashMap::new();
+    let changes = scenario
+        .diffs
+        .iter()
+        .flat_map(|d| d.changes.iter().map(|c| (&c.path, &c.old_value)))
+        .chain(scenario.commits.iter().flat_map(|c| c.changes.iter().map(|c| (&c.path, &c.old_value))));
+    for (path, old_value) in changes {
+        if let Some(old) = old_value {
+            hints.entry(path.clone()).or_insert_with(|| old.clone());
+        }
+    }
+    hints
+}
+
+/// Changes a commit made to its first parent, preferring a full-value diff
+/// between the two over the commit's own change list
+fn commit_delta(commit: &FixtureCommit, diffs: &[IrminDiff], hints: &HashMap<String, String>) -> Result<Vec<DiffChange>> {
+    let parent = commit.parents.first().map(String::as_str).unwrap_or_default();
+    let mut changes: Vec<DiffChange> = diffs
+        .iter()
+        .filter(|d| d.to_commit == commit.hash && d.from_commit == parent)
+        .flat_map(|d| d.changes.iter().cloned())
+        .collect();
+    let short = &commit.hash[..commit.hash.len().min(8)];
+    for change in &commit.changes {
+        if changes.iter().any(|c| c.path == change.path) {
+            continue;
+        }
+        let change_type = match change.change_type.to_lowercase().as_str() {
+            "added" => ChangeType::Added,
+            "modified" => ChangeType::Modified,
+            "deleted" => ChangeType::Deleted,
+            other => bail!("Unknown change type '{}' for {} in commit {}", other, change.path, commit.hash),
+        };
+        let old_value = match change_type {
+            ChangeType::Added => None,
+            _ => Some(
+                change
+                    .old_value
+                    .clone()
+                    .unwrap_or_else(|| format!("{} before {}", change.path, short)),
+            ),
+        };
+        let new_value = match change_type {
+            ChangeType::Deleted => None,
+            _ => Some(
+                change
+                    .new_value
+                    .clone()
+                    .or_else(|| hints.get(&change.path).cloned())
+                    .unwrap_or_else(|| format!("{} at {}", change.path, short)),
+            ),
+        };
+        changes.push(DiffChange {
+            path: change.path.clone(),
+            change_type,
+            old_value,
+            new_value,
+        });
+    }
+    Ok(changes)
+}
+
+fn derive_branches(
+    scenario: &Scenario,
+    commits: &[IrminCommit],
+    index: &HashMap<&str, &FixtureCommit>,
+) -> Vec<IrminBranch> {
+    let mut heads: Vec<(String, String)> = scenario
+        .branches
+        .iter()
+        .filter(|b| index.contains_key(b.head_commit.as_str()))
+        .map(|b| (b.name.clone(), b.head_commit.clone()))
+        .collect();
+    if scenario.branches.is_empty() {
+        for commit in commits {
+            match heads.iter_mut().find(|(name, _)| *name == commit.branch) {
+                Some((_, head)) if index[head.as_str()].timestamp >= commit.timestamp => {}
+                Some((_, head)) => *head = commit.hash.clone(),
+                None => heads.push((commit.branch.clone(), commit.hash.clone())),
+            }
+        }
+    }
+
+    heads
+        .into_iter()
+        .map(|(name, head)| {
+            let mut seen = HashSet::new();
+            let mut queue = VecDeque::from([head.as_str()]);
+            while let Some(hash) = queue.pop_front() {
+                if let Some(commit) = index.get(hash) {
+                    if seen.insert(hash) {
+                        queue.extend(commit.parents.iter().map(String::as_str));
+                    }
+                }
+            }
+            IrminBranch {
+                last_updated: index[head.as_str()].timestamp,
+                commit_count: seen.len(),
+                head_commit: head,
+                name,
+            }
+        })
+        .collect()
+}
+
+fn default_head(branches: &[IrminBranch], commits: &[IrminCommit]) -> Option<String> {
+    branches
+        .iter()
+        .find(|b| b.name == "main")
+        .or_else(|| branches.first())
+        .map(|b| b.head_commit.clone())
+        .or_else(|| commits.first().map(|c| c.hash.clone()))
+}
+
+fn files_of(root: &IrminNode) -> Files {
+    let mut files = Files::new();
+    tree::walk(root, &mut |path, _, node| {
+        if node.node_type != NodeType::Directory {
+            files.insert(
+                path.to_string(),
+                Entry {
+                    node_type: node.node_type.clone(),
+                    value: node.value.clone(),
+                    metadata: node.metadata.clone(),
+                },
+            );
+        }
+    });
+    files
+}
+
+fn set_value(files: &mut Files, path: &str, value: Option<String>, timestamp: DateTime<Utc>) {
+    let path = tree::normalize_path(path);
+    let previous = files.remove(&path);
+    files.insert(
+        path,
+        Entry {
+            node_type: previous.as_ref().map(|e| e.node_type.clone()).unwrap_or(NodeType::File),
+            metadata: NodeMetadata {
+                last_modified: timestamp,
+                size: Some(value.as_ref().map(|v| v.len()).unwrap_or_default() as u64),
+                permissions: previous
+                    .and_then(|e| e.metadata.permissions)
+                    .or_else(|| Some("644".to_string())),
+            },
+            value,
+        },
+    );
+}
+
+fn apply(files: &Files, changes: &[DiffChange], timestamp: DateTime<Utc>) -> Files {
+    let mut files = files.clone();
+    for change in changes {
+        match change.change_type {
+            ChangeType::Added | ChangeType::Modified => {
+                set_value(&mut files, &change.path, change.new_value.clone(), timestamp)
+            }
+            ChangeType::Deleted => {
+                files.remove(&tree::normalize_path(&change.path));
+            }
+        }
+    }
+    files
+}
+
+/// Undo `changes`; restored values are stamped with the parent's timestamp
+fn revert(files: &Files, changes: &[DiffChange], timestamp: DateTime<Utc>) -> Files {
+    let mut files = files.clone();
+    for change in changes {
+        match change.change_type {
+            ChangeType::Added => {
+                files.remove(&tree::normalize_path(&change.path));
+            }
+            ChangeType::Modified | ChangeType::Deleted => {
+                set_value(&mut files, &change.path, change.old_value.clone(), timestamp)
+            }
+        }
+    }
+    files
+}
+
+fn build_tree(files: &Files, timestamp: DateTime<Utc>) -> IrminNode {
+    let mut root = directory("/", timestamp);
+    for (path, entry) in files {
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let (name, parents) = segments.split_last().expect("store paths are not empty");
+        let mut node = &mut root;
+        for segment in parents {
+            node = node
+                .children
+                .entry(segment.to_string())
+                .or_insert_with(|| directory(segment, entry.metadata.last_modified));
+        }
+        node.children.insert(
+            name.to_string(),
+            IrminNode {
+                key: name.to_string(),
+                value: entry.value.clone(),
+                node_type: entry.node_type.clone(),
+                children: HashMap::new(),
+                metadata: entry.metadata.clone(),
+                hash: None,
+            },
+        );
+    }
+    stamp_directories(&mut root);
+    root
+}
+
+fn directory(key: &str, last_modified: DateTime<Utc>) -> IrminNode {
+    IrminNode {
+        key: key.to_string(),
+        value: None,
+        node_type: NodeType::Directory,
+        children: HashMap::new(),
+        metadata: NodeMetadata {
+            last_modified,
+            size: None,
+            permissions: Some("755".to_string()),
+        },
+        hash: None,
+    }
+}
+
+/// Date each directory by its most recently modified descendant
+fn stamp_directories(node: &mut IrminNode) -> Option<DateTime<Utc>> {
+    if node.node_type != NodeType::Directory {
+        return Some(node.metadata.last_modified);
+    }
+    let newest = node.children.values_mut().filter_map(stamp_directories).max();
+    if let Some(newest) = newest {
+        node.metadata.last_modified = newest;
+    }
+    newest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_scenario_is_consistent() {
+        let store = FixtureStore::build(Scenario::embedded("sample").unwrap()).unwrap();
+        let anchor: IrminNode = serde_json::from_str(SAMPLE_TREE).unwrap();
+        assert_eq!(tree::file_values(&store.tree().unwrap()), tree::file_values(&anchor));
+
+        for commit in store.commits() {
+            let tree = store.tree_at(&commit.hash).unwrap();
+            let Some(parent) = commit.parents.first() else {
+                continue;
+            };
+            let diff = store.diff(parent, &commit.hash).unwrap();
+            let mut files = tree::file_values(&store.tree_at(parent).unwrap());
+            for change in &diff.changes {
+                match &change.new_value {
+                    Some(value) => files.insert(change.path.clone(), value.clone()),
+                    None => files.remove(&change.path),
+                };
+            }
+            assert_eq!(files, tree::file_values(&tree), "commit {}", commit.hash);
+        }
+
+        let initial = store.tree_at("b2a1").unwrap();
+        assert!(initial.children.contains_key("README.md"));
+        assert!(!initial.children.contains_key("users"));
+        let feature = store.tree_at("feature/logging").unwrap();
+        assert_eq!(feature.children["logs"].children.len(), 1);
+
+        let branches = store.branches();
+        let count = |name: &str| branches.iter().find(|b| b.name == name).unwrap().commit_count;
+        assert_eq!((count("main"), count("feature/logging")), (3, 3));
+    }
+
+    #[test]
+    fn test_demo_scenario_uses_full_value_diffs() {
+        let store = FixtureStore::build(Scenario::embedded("demo").unwrap()).unwrap();
+        let branches = store.branches();
+        assert!(branches.iter().all(|b| b.name != "develop"));
+        assert_eq!(branches.iter().find(|b| b.name == "main").unwrap().commit_count, 3);
+
+        let diff = store
+            .diff("f6e5d4c3b2a1987654321098765432109876dcba", "a1b2c3d4e5f6789012345678901234567890abcd")
+            .unwrap();
+        let paths: Vec<&str> = diff.changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["/auth/users.json", "/config/app.json", "/config/auth.json"]);
+        let app = diff.changes.iter().find(|c| c.path == "/config/app.json").unwrap();
+        assert!(app.old_value.as_deref().unwrap().contains("\"version\""));
+
+        for name in EMBEDDED_SCENARIOS {
+            let store = FixtureStore::build(Scenario::embedded(name).unwrap()).unwrap();
+            assert!(!store.commits().is_empty());
+            assert!(!store.tree().unwrap().children.is_empty(), "scenario {}", name);
+        }
+    }
+
+    #[test]
+    fn test_load_scenario_directory() {
+        let dir = std::env::temp_dir().join(format!("irmin-fixtures-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let scenario = serde_json::json!({
+            "commits": [
+                {"hash": "c2", "message": "Remove b", "author": "A", "timestamp": "2024-02-02T00:00:00Z",
+                 "parents": ["c1"], "branch": "main", "changes": [{"path": "/b", "type": "Deleted"}]},
+                {"hash": "c1", "message": "Add a and b", "author": "A", "timestamp": "2024-02-01T00:00:00Z",
+                 "branch": "main", "changes": [{"path": "/a", "type": "added", "new_value": "1"},
+                                               {"path": "/b", "type": "added", "new_value": "2"}]}
+            ]
+        });
+        std::fs::write(dir.join("small.json"), scenario.to_string()).unwrap();
+        std::fs::write(dir.join("other.json"), "{}").unwrap();
+
+        let spec = format!("{}#small", dir.display());
+        let store = FixtureStore::load(&spec).unwrap();
+        assert_eq!(store.name, "small");
+        assert_eq!(store.branches()[0].head_commit, "c2");
+        assert_eq!(tree::file_values(&store.tree().unwrap()).into_keys().collect::<Vec<_>>(), vec!["/a"]);
+        assert_eq!(store.diff("c1", "c2").unwrap().changes[0].old_value.as_deref(), Some("2"));
+        assert!(FixtureStore::load(&dir.display().to_string()).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
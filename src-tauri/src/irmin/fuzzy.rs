@@ -0,0 +1,289 @@
+use super::{tree, types::{IrminNode, SearchResult}};
+use std::cmp::Ordering;
+
+// Scoring constants, modelled on fzf's algorithm
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+/// Match right after a path separator or at the start of the path
+const BONUS_SEGMENT: i64 = 10;
+/// Match right after a word separator such as `_`, `-` or `.`
+const BONUS_BOUNDARY: i64 = 8;
+/// Match on an upper case letter following a lower case one
+const BONUS_CAMEL: i64 = 7;
+/// Extra score for each character continuing a contiguous run
+const BONUS_CONSECUTIVE: i64 = 4;
+/// Extra score for each character matched inside the basename
+const BONUS_BASENAME: i64 = 3;
+/// The bonus of the first pattern character is multiplied by this
+const FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// Result of matching a pattern against a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Character (not byte) positions of the matched pattern characters
+    pub indices: Vec<usize>,
+}
+
+impl FuzzyMatch {
+    /// Score normalized to `0.0..=1.0` against a perfect match of the same pattern
+    pub fn relevance(&self, pattern_len: usize) -> f32 {
+        let perfect = perfect_score(pattern_len);
+        if perfect <= 0 {
+            return 0.0;
+        }
+        (self.score as f32 / perfect as f32).clamp(0.0, 1.0)
+    }
+}
+
+fn perfect_score(pattern_len: usize) -> i64 {
+    let n = pattern_len as i64;
+    if n == 0 {
+        return 0;
+    }
+    n * (SCORE_MATCH + BONUS_SEGMENT + BONUS_BASENAME)
+        + BONUS_SEGMENT * (FIRST_CHAR_MULTIPLIER - 1)
+        + (n - 1) * BONUS_CONSECUTIVE
+}
+
+/// Bonus for matching the character at `idx`, based on the character before it
+fn char_bonus(chars: &[char], idx: usize) -> i64 {
+    let Some(&prev) = idx.checked_sub(1).and_then(|i| chars.get(i)) else {
+        return BONUS_SEGMENT;
+    };
+    let current = chars[idx];
+    if prev == '/' {
+        BONUS_SEGMENT
+    } else if matches!(prev, '_' | '-' | '.' | ' ' | ':') {
+        BONUS_BOUNDARY
+    } else if prev.is_lowercase() && current.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// Match `pattern` against `text` as a case-insensitive subsequence.
+///
+/// Among all possible alignments the best scoring one is returned: matches
+/// on segment and word boundaries, contiguous runs and matches inside the
+/// basename score higher, gaps between matched characters cost points.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let original: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = original
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let (n, m) = (pattern.len(), lowered.len());
+    if n > m {
+        return None;
+    }
+
+    // Cheap rejection before running the full alignment
+    let mut pi = 0;
+    for &c in &lowered {
+        if c == pattern[pi] {
+            pi += 1;
+            if pi == n {
+                break;
+            }
+        }
+    }
+    if pi < n {
+        return None;
+    }
+
+    let basename_start = original.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let bonus: Vec<i64> = (0..m)
+        .map(|j| char_bonus(&original, j) + if j >= basename_start { BONUS_BASENAME } else { 0 })
+        .collect();
+
+    // score[i][j]: best score with pattern[i] matched at text[j]
+    // prev[i][j]: position of pattern[i - 1] in that alignment
+    let mut score = vec![vec![None::<i64>; m]; n];
+    let mut prev = vec![vec![0usize; m]; n];
+
+    for j in 0..m {
+        if lowered[j] == pattern[0] {
+            score[0][j] = Some(SCORE_MATCH + bonus[j] * FIRST_CHAR_MULTIPLIER);
+        }
+    }
+
+    for i in 1..n {
+        // Best predecessor ending at least two characters back, with the gap
+        // penalty already applied for a gap ending just before `j`
+        let mut gap_best: Option<(i64, usize)> = None;
+        for j in 1..m {
+            if j >= 2 {
+                gap_best = gap_best.map(|(s, k)| (s + SCORE_GAP_EXTENSION, k));
+                if let Some(s) = score[i - 1][j - 2] {
+                    let candidate = s + SCORE_GAP_START;
+                    if gap_best.is_none_or(|(best, _)| candidate >= best) {
+                        gap_best = Some((candidate, j - 2));
+                    }
+                }
+            }
+            if lowered[j] != pattern[i] {
+                continue;
+            }
+
+            let consecutive = score[i - 1][j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+            let best = match (consecutive, gap_best) {
+                (Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+                (c, g) => c.or(g),
+            };
+            if let Some((s, k)) = best {
+                score[i][j] = Some(s + SCORE_MATCH + bonus[j]);
+                prev[i][j] = k;
+            }
+        }
+    }
+
+    // Pick the best end position, preferring the earliest on ties
+    let (mut j, best) = (0..m)
+        .filter_map(|j| score[n - 1][j].map(|s| (j, s)))
+        .fold(None, |acc: Option<(usize, i64)>, (j, s)| match acc {
+            Some((_, best)) if best >= s => acc,
+            _ => Some((j, s)),
+        })?;
+
+    let mut indices = vec![0; n];
+    for i in (0..n).rev() {
+        indices[i] = j;
+        if i > 0 {
+            j = prev[i][j];
+        }
+    }
+
+    Some(FuzzyMatch { score: best, indices })
+}
+
+/// Order search results by relevance, breaking ties by shorter then
+/// lexicographically smaller path so the ordering is deterministic
+pub fn compare_results(a: &SearchResult, b: &SearchResult) -> Ordering {
+    b.relevance_score
+        .total_cmp(&a.relevance_score)
+        .then_with(|| a.path.len().cmp(&b.path.len()))
+        .then_with(|| a.path.cmp(&b.path))
+}
+
+/// Search through the tree, fuzzy matching the query against full paths
+pub fn search_tree(node: &IrminNode, query: &str) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    let pattern_len = query.chars().count();
+
+    tree::walk(node, &mut |path, _depth, child| {
+        if let Some(m) = fuzzy_match(query, path) {
+            results.push(SearchResult {
+                path: path.to_string(),
+                node: child.clone(),
+                relevance_score: m.relevance(pattern_len),
+                matched_indices: m.indices,
+            });
+        }
+    });
+
+    results.sort_by(compare_results);
+    results
+}
+
+/// Score results found by a backend the way `search_tree` does, so ranking
+/// and highlighting do not depend on the backend. Results whose path does
+/// not match (e.g. matched on their value) keep no indices and rank last.
+pub fn rerank(query: &str, mut results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let pattern_len = query.chars().count();
+    for result in &mut results {
+        match fuzzy_match(query, &result.path) {
+            Some(m) => {
+                result.relevance_score = m.relevance(pattern_len);
+                result.matched_indices = m.indices;
+            }
+            None => {
+                result.relevance_score = 0.0;
+                result.matched_indices.clear();
+            }
+        }
+    }
+    results.sort_by(compare_results);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence_required() {
+        assert!(fuzzy_match("abc", "/a/b/c").is_some());
+        assert!(fuzzy_match("acb", "/a/b/c").is_none());
+        assert!(fuzzy_match("", "/a").is_none());
+        assert!(fuzzy_match("abcd", "abc").is_none());
+    }
+
+    #[test]
+    fn test_indices_point_at_matched_chars() {
+        let m = fuzzy_match("alice", "/users/alice.json").unwrap();
+        assert_eq!(m.indices, vec![7, 8, 9, 10, 11]);
+
+        let m = fuzzy_match("ALJ", "/users/alice.json").unwrap();
+        let chars: Vec<char> = "/users/alice.json".chars().collect();
+        let matched: String = m.indices.iter().map(|&i| chars[i]).collect();
+        assert_eq!(matched.to_lowercase(), "alj");
+    }
+
+    #[test]
+    fn test_contiguous_and_boundary_matches_rank_higher() {
+        let contiguous = fuzzy_match("conf", "/config.json").unwrap();
+        let scattered = fuzzy_match("conf", "/cache/old/notes/form.txt").unwrap();
+        assert!(contiguous.score > scattered.score);
+
+        let boundary = fuzzy_match("db", "/data/db.json").unwrap();
+        let inner = fuzzy_match("db", "/data/adbc.json").unwrap();
+        assert!(boundary.score > inner.score);
+    }
+
+    #[test]
+    fn test_basename_match_ranks_higher() {
+        let basename = fuzzy_match("users", "/data/users.json").unwrap();
+        let directory = fuzzy_match("users", "/users/data.json").unwrap();
+        assert!(basename.score > directory.score);
+    }
+
+    #[test]
+    fn test_relevance_is_normalized() {
+        let m = fuzzy_match("app", "/logs/app.log").unwrap();
+        let relevance = m.relevance(3);
+        assert!(relevance > 0.0 && relevance <= 1.0);
+        assert!(!relevance.is_nan());
+    }
+
+    #[test]
+    fn test_rerank_scores_backend_results_like_the_tree_search() {
+        let tree = crate::irmin::mock_data::generate_mock_tree();
+        let local = search_tree(&tree, "alice");
+        let mut backend: Vec<SearchResult> = local
+            .iter()
+            .rev()
+            .cloned()
+            .map(|r| SearchResult { relevance_score: 1.0, matched_indices: Vec::new(), ..r })
+            .collect();
+        backend.push(SearchResult {
+            path: "/notes/value-match".to_string(),
+            node: tree.clone(),
+            relevance_score: 1.0,
+            matched_indices: Vec::new(),
+        });
+
+        let summary = |results: &[SearchResult]| -> Vec<(String, f32, Vec<usize>)> {
+            results.iter().map(|r| (r.path.clone(), r.relevance_score, r.matched_indices.clone())).collect()
+        };
+        let reranked = rerank("alice", backend);
+        assert_eq!(summary(&reranked[..local.len()]), summary(&local));
+        assert_eq!(reranked.last().unwrap().relevance_score, 0.0);
+    }
+}
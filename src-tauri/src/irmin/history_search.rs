@@ -0,0 +1,311 @@
+use super::{tree, types::*};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::BTreeMap;
+
+/// A key or value match found in one subtree
+#[derive(Debug, Clone)]
+struct Hit {
+    path: String,
+    matched_key: bool,
+    matched_value: bool,
+}
+
+/// Where and when one key matched across the scanned commits
+#[derive(Debug, Clone)]
+struct Presence {
+    matched_key: bool,
+    matched_value: bool,
+    first: (String, DateTime<Utc>),
+    last: (String, DateTime<Utc>),
+    commit_count: usize,
+}
+
+/// What the previous commit had at one path
+struct Remembered {
+    hash: String,
+    hits: Vec<Hit>,
+}
+
+/// Case-insensitive substring search over the keys and values of many commits.
+///
+/// Subtrees of the previous commit that have hits, and the largest ones
+/// without any, are remembered by path and the hash the backend reported for
+/// them. A subtree whose hash did not change since the previous commit is not
+/// scanned again. Nodes without a backend hash are always scanned.
+pub struct HistorySearch {
+    query: String,
+    previous: BTreeMap<String, Remembered>,
+    presence: BTreeMap<String, Presence>,
+    last_commit: Option<String>,
+    commits_scanned: usize,
+    subtrees_reused: usize,
+}
+
+impl HistorySearch {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_lowercase(),
+            previous: BTreeMap::new(),
+            presence: BTreeMap::new(),
+            last_commit: None,
+            commits_scanned: 0,
+            subtrees_reused: 0,
+        }
+    }
+
+    /// Scan the tree of one commit; commits must be scanned oldest first
+    pub fn scan_commit(&mut self, commit: &IrminCommit, root: &IrminNode) {
+        let mut remembered = BTreeMap::new();
+        let hits = self.scan_node(root, "/", &mut remembered);
+        if let (true, Some(hash)) = (hits.is_empty(), &root.hash) {
+            remembered.insert("/".to_string(), Remembered { hash: hash.clone(), hits: Vec::new() });
+        }
+        self.previous = remembered;
+        for hit in hits {
+            let entry = self.presence.entry(hit.path).or_insert_with(|| Presence {
+                matched_key: false,
+                matched_value: false,
+                first: (commit.hash.clone(), commit.timestamp),
+                last: (commit.hash.clone(), commit.timestamp),
+                commit_count: 0,
+            });
+            entry.matched_key |= hit.matched_key;
+            entry.matched_value |= hit.matched_value;
+            entry.last = (commit.hash.clone(), commit.timestamp);
+            entry.commit_count += 1;
+        }
+        self.last_commit = Some(commit.hash.clone());
+        self.commits_scanned += 1;
+    }
+
+    /// Hits of the subtree at `path`; subtrees with hits are added to
+    /// `remembered`, and so are the children without hits of those
+    fn scan_node(&mut self, node: &IrminNode, path: &str, remembered: &mut BTreeMap<String, Remembered>) -> Vec<Hit> {
+        if let Some(hash) = &node.hash {
+            if let Some(previous) = self.previous.remove(path).filter(|p| &p.hash == hash) {
+                self.subtrees_reused += 1;
+                let hits = previous.hits.clone();
+                if !hits.is_empty() {
+                    remembered.insert(path.to_string(), previous);
+                    // Keep what is remembered below it, for when it changes
+                    let prefix = if path == "/" { "/".to_string() } else { format!("{}/", path) };
+                    let below: Vec<String> = self
+                        .previous
+                        .range(prefix.clone()..)
+                        .map(|(p, _)| p)
+                        .take_while(|p| p.starts_with(&prefix))
+                        .cloned()
+                        .collect();
+                    for p in below {
+                        if let Some(entry) = self.previous.remove(&p) {
+                            remembered.insert(p, entry);
+                        }
+                    }
+                }
+                return hits;
+            }
+        }
+
+        let mut hits = Vec::new();
+        if path != "/" {
+            let matched_key = path.to_lowercase().contains(&self.query);
+            let matched_value = node
+                .value
+                .as_ref()
+                .is_some_and(|v| v.to_lowercase().contains(&self.query));
+            if matched_key || matched_value {
+                hits.push(Hit { path: path.to_string(), matched_key, matched_value });
+            }
+        }
+
+        let mut keys: Vec<&String> = node.children.keys().collect();
+        keys.sort();
+        let mut without_hits = Vec::new();
+        for key in keys {
+            let child = &node.children[key];
+            let child_path = tree::join_path(path, key);
+            let child_hits = self.scan_node(child, &child_path, remembered);
+            if child_hits.is_empty() {
+                without_hits.push((child_path, child));
+            }
+            hits.extend(child_hits);
+        }
+
+        if !hits.is_empty() {
+            for (child_path, child) in without_hits {
+                if let Some(hash) = &child.hash {
+                    remembered.insert(child_path, Remembered { hash: hash.clone(), hits: Vec::new() });
+                }
+            }
+            if let Some(hash) = &node.hash {
+                remembered.insert(path.to_string(), Remembered { hash: hash.clone(), hits: hits.clone() });
+            }
+        }
+        hits
+    }
+
+    pub fn commits_scanned(&self) -> usize {
+        self.commits_scanned
+    }
+
+    pub fn subtrees_reused(&self) -> usize {
+        self.subtrees_reused
+    }
+
+    /// Matches ordered by path, each with its first and last matching commit
+    pub fn finish(self) -> Vec<HistoryMatch> {
+        let last_commit = self.last_commit;
+        self.presence
+            .into_iter()
+            .map(|(path, p)| HistoryMatch {
+                present_at_end: last_commit.as_deref() == Some(p.last.0.as_str()),
+                path,
+                matched_key: p.matched_key,
+                matched_value: p.matched_value,
+                first_commit: p.first.0,
+                first_seen: p.first.1,
+                last_commit: p.last.0,
+                last_seen: p.last.1,
+                commit_count: p.commit_count,
+            })
+            .collect()
+    }
+}
+
+/// Commits of `branch` (all branches when `None`) between `since` and
+/// `until` inclusive, oldest first
+pub fn commits_in_range(
+    commits: &[IrminCommit],
+    branch: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Vec<IrminCommit> {
+    let mut selected: Vec<IrminCommit> = commits
+        .iter()
+        .filter(|c| branch.is_none_or(|b| c.branch == b))
+        .filter(|c| since.is_none_or(|s| c.timestamp >= s))
+        .filter(|c| until.is_none_or(|u| c.timestamp <= u))
+        .cloned()
+        .collect();
+    selected.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.hash.cmp(&b.hash)));
+    selected
+}
+
+/// Parse a range bound given as RFC 3339 or as a `YYYY-MM-DD` date
+pub fn parse_time_bound(input: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+        .ok_or_else(|| format!("Invalid time '{}', expected YYYY-MM-DD or RFC 3339", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::mock_data;
+
+    fn commit(hash: &str, hours_ago: i64) -> IrminCommit {
+        IrminCommit {
+            hash: hash.to_string(),
+            message: String::new(),
+            author: String::new(),
+            timestamp: Utc::now() - chrono::Duration::hours(hours_ago),
+            parents: Vec::new(),
+            branch: "main".to_string(),
+        }
+    }
+
+    /// `tree` with content hashes standing in for the backend's
+    fn hashed(mut tree: IrminNode) -> IrminNode {
+        fn set(node: &mut IrminNode, path: &str, hashes: &std::collections::HashMap<String, String>) {
+            node.hash = Some(hashes[path].clone());
+            for (key, child) in node.children.iter_mut() {
+                set(child, &tree::join_path(path, key), hashes);
+            }
+        }
+        let hashes = tree::content_hashes(&tree);
+        set(&mut tree, "/", &hashes);
+        tree
+    }
+
+    #[test]
+    fn test_reports_first_and_last_commit_of_deleted_value() {
+        let first = hashed(mock_data::generate_mock_tree());
+        let mut second = mock_data::generate_mock_tree();
+        second.children.get_mut("users").unwrap().children.remove("bob.json");
+        let second = hashed(second);
+        let third = second.clone();
+
+        let mut search = HistorySearch::new("BOB@example");
+        search.scan_commit(&commit("c1", 3), &first);
+        search.scan_commit(&commit("c2", 2), &first);
+        search.scan_commit(&commit("c3", 1), &second);
+        search.scan_commit(&commit("c4", 0), &third);
+
+        let matches = search.finish();
+        assert_eq!(matches.len(), 1);
+        let bob = &matches[0];
+        assert_eq!(bob.path, "/users/bob.json");
+        assert!(bob.matched_value && !bob.matched_key);
+        assert_eq!((bob.first_commit.as_str(), bob.last_commit.as_str()), ("c1", "c2"));
+        assert_eq!(bob.commit_count, 2);
+        assert!(!bob.present_at_end);
+    }
+
+    #[test]
+    fn test_unchanged_subtrees_are_reused() {
+        let tree = hashed(mock_data::generate_mock_tree());
+        let mut search = HistorySearch::new("alice");
+        search.scan_commit(&commit("c1", 1), &tree);
+        assert_eq!(search.subtrees_reused(), 0);
+        search.scan_commit(&commit("c2", 0), &tree);
+        assert_eq!(search.subtrees_reused(), 1, "identical root is matched once");
+        assert_eq!(search.commits_scanned(), 2);
+
+        let matches = search.finish();
+        assert!(matches.iter().all(|m| m.commit_count == 2 && m.present_at_end));
+    }
+
+    #[test]
+    fn test_only_subtrees_with_hits_and_their_siblings_are_remembered() {
+        let mut search = HistorySearch::new("alice");
+        search.scan_commit(&commit("c1", 2), &hashed(mock_data::generate_mock_tree()));
+        let remembered: Vec<&str> = search.previous.keys().map(String::as_str).collect();
+        assert_eq!(
+            remembered,
+            ["/", "/README.md", "/config.json", "/logs", "/users", "/users/alice.json", "/users/bob.json"]
+        );
+        assert!(search.previous["/logs"].hits.is_empty());
+
+        let mut changed = mock_data::generate_mock_tree();
+        changed.children.get_mut("users").unwrap().children.remove("bob.json");
+        search.scan_commit(&commit("c2", 1), &hashed(changed));
+        assert_eq!(search.subtrees_reused(), 4, "everything but the changed path to bob.json");
+        assert!(!search.previous.contains_key("/users/bob.json"));
+
+        // Without backend hashes every commit is scanned in full
+        search.scan_commit(&commit("c3", 0), &mock_data::generate_mock_tree());
+        assert_eq!(search.subtrees_reused(), 4);
+        assert!(search.previous.is_empty());
+        assert_eq!(search.finish()[0].commit_count, 3);
+    }
+
+    #[test]
+    fn test_commits_in_range() {
+        let commits = mock_data::generate_mock_commits();
+        let main = commits_in_range(&commits, Some("main"), None, None);
+        assert_eq!(main.len(), 3);
+        assert!(main.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let recent = commits_in_range(&commits, None, Some(Utc::now() - chrono::Duration::hours(3)), None);
+        assert_eq!(recent.len(), 2);
+
+        assert!(parse_time_bound("2024-01-15").is_ok());
+        assert!(parse_time_bound("2024-01-15T10:00:00Z").is_ok());
+        assert!(parse_time_bound("last week").is_err());
+    }
+}
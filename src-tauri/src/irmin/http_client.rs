@@ -1,8 +1,13 @@
+use super::progress;
 use super::types::*;
+use super::wire;
 use anyhow::Result;
 use reqwest::Client;
 use std::time::Duration;
 
+/// Upper bound on a whole streamed transfer; chunks use the configured timeout
+const STREAM_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
 /// Configuration for Irmin HTTP server connection
 #[derive(Clone)]
 pub struct IrminHttpConfig {
@@ -58,7 +63,7 @@ impl IrminHttpClient {
         let response = self.client.get(&url).send().await?;
         
         if response.status().is_success() {
-            let node: IrminNode = response.json().await?;
+            let node: IrminNode = wire::decode(&read_body(response, "Downloading tree").await?)?;
             Ok(node)
         } else {
             let error_text = response.text().await?;
@@ -66,13 +71,76 @@ impl IrminHttpClient {
         }
     }
 
+    /// Get tree structure at a specific commit from Irmin server
+    pub async fn get_tree_at(&self, commit: &str) -> Result<IrminNode> {
+        let url = format!("{}/api/tree?commit={}", self.config.server_url, urlencoding::encode(commit));
+        let response = self.client.get(&url).send().await?;
+        
+        if response.status().is_success() {
+            let node: IrminNode = wire::decode(&read_body(response, "Downloading tree").await?)?;
+            Ok(node)
+        } else {
+            let error_text = response.text().await?;
+            Err(anyhow::anyhow!("Server error: {}", error_text))
+        }
+    }
+
+    /// Stream the tree at `commit` (head of main when `None`) as NDJSON
+    /// entries, handing them to `on_entries` as chunks arrive. The timeout
+    /// applies to each chunk rather than to the whole transfer.
+    pub async fn stream_tree<F>(&self, commit: Option<&str>, mut on_entries: F) -> Result<usize>
+    where
+        F: FnMut(Vec<TreeEntry>) -> Result<()>,
+    {
+        let mut url = format!("{}/api/tree/stream", self.config.server_url);
+        if let Some(commit) = commit {
+            url = format!("{}?commit={}", url, urlencoding::encode(commit));
+        }
+        let idle = self.config.timeout;
+        let mut response = tokio::time::timeout(idle, self.client.get(&url).timeout(STREAM_TIMEOUT).send())
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out waiting for {}", url))??;
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(anyhow::anyhow!("Server error: {}", error_text));
+        }
+
+        let progress = progress::current();
+        progress.phase("Streaming tree", None);
+        let mut decoder = wire::NdjsonDecoder::new();
+        let mut count = 0;
+        loop {
+            let chunk = tokio::time::timeout(idle, response.chunk())
+                .await
+                .map_err(|_| anyhow::anyhow!("Tree stream stalled after {} entries", count))??;
+            let entries = match chunk {
+                Some(chunk) => {
+                    let entries = decoder.push(&chunk)?;
+                    progress.advance(entries.len() as u64, chunk.len() as u64);
+                    entries
+                }
+                None => break,
+            };
+            if !entries.is_empty() {
+                count += entries.len();
+                on_entries(entries)?;
+            }
+        }
+        let entries = decoder.finish()?;
+        if !entries.is_empty() {
+            count += entries.len();
+            on_entries(entries)?;
+        }
+        Ok(count)
+    }
+
     /// Get commits from Irmin server
     pub async fn get_commits(&self) -> Result<Vec<IrminCommit>> {
         let url = format!("{}/api/commits", self.config.server_url);
         let response = self.client.get(&url).send().await?;
         
         if response.status().is_success() {
-            let commits: Vec<IrminCommit> = response.json().await?;
+            let commits: Vec<IrminCommit> = wire::decode(&read_body(response, "Downloading commits").await?)?;
             Ok(commits)
         } else {
             let error_text = response.text().await?;
@@ -86,7 +154,7 @@ impl IrminHttpClient {
         let response = self.client.get(&url).send().await?;
         
         if response.status().is_success() {
-            let branches: Vec<IrminBranch> = response.json().await?;
+            let branches: Vec<IrminBranch> = wire::decode(&read_body(response, "Downloading branches").await?)?;
             Ok(branches)
         } else {
             let error_text = response.text().await?;
@@ -100,7 +168,7 @@ impl IrminHttpClient {
         let response = self.client.get(&url).send().await?;
         
         if response.status().is_success() {
-            let results: Vec<SearchResult> = response.json().await?;
+            let results: Vec<SearchResult> = wire::decode(&read_body(response, "Downloading search results").await?)?;
             Ok(results)
         } else {
             let error_text = response.text().await?;
@@ -119,18 +187,46 @@ impl IrminHttpClient {
         let response = self.client.get(&url).send().await?;
         
         if response.status().is_success() {
-            let diff: IrminDiff = response.json().await?;
+            let diff: IrminDiff = wire::decode(&read_body(response, "Downloading diff").await?)?;
             Ok(diff)
         } else {
             let error_text = response.text().await?;
             Err(anyhow::anyhow!("Server error: {}", error_text))
         }
     }
+
+    /// Apply changes to a branch as a single commit on the Irmin server
+    pub async fn commit_changes(&self, request: &CommitRequest) -> Result<CommitResult> {
+        let url = format!("{}/api/commit", self.config.server_url);
+        let response = self.client.post(&url).json(request).send().await?;
+        
+        if response.status().is_success() {
+            let result: CommitResult = wire::decode(&response.text().await?)?;
+            Ok(result)
+        } else {
+            let error_text = response.text().await?;
+            Err(anyhow::anyhow!("Server error: {}", error_text))
+        }
+    }
+}
+
+/// Read a response body as the `phase` of the current operation, reporting
+/// the bytes received as they arrive
+async fn read_body(mut response: reqwest::Response, phase: &str) -> Result<String> {
+    let progress = progress::current();
+    progress.phase(phase, None);
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        progress.advance(0, chunk.len() as u64);
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8(body)?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::irmin::reference_server::{Fault, Malformed, ReferenceServer, RunningServer};
 
     #[tokio::test]
     async fn test_http_config_creation() {
@@ -152,6 +248,85 @@ mod tests {
         // If this doesn't panic, the client was created successfully
     }
 
-    // Integration tests would require a running Irmin server
-    // These would be run separately in CI/CD pipeline
+    async fn reference_client() -> (RunningServer, IrminHttpClient) {
+        let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+        let client = IrminHttpClient::new(
+            IrminHttpConfig::new()
+                .with_url(running.url.clone())
+                .with_timeout(Duration::from_millis(500)),
+        );
+        (running, client)
+    }
+
+    #[tokio::test]
+    async fn test_client_against_reference_server() {
+        let (running, client) = reference_client().await;
+        assert!(client.health_check().await.unwrap());
+
+        let commits = client.get_commits().await.unwrap();
+        let branches = client.get_branches().await.unwrap();
+        assert!(!commits.is_empty() && !branches.is_empty());
+        let tree = client.get_tree_at(&commits[0].hash).await.unwrap();
+        assert_eq!(tree.key, "root");
+        assert!(tree.hash.is_some() && tree.children.values().all(|child| child.hash.is_some()));
+
+        let result = client
+            .commit_changes(&CommitRequest {
+                branch: "main".to_string(),
+                message: "Add notes".to_string(),
+                author: "Tester".to_string(),
+                changes: vec![DiffChange {
+                    path: "/notes.txt".to_string(),
+                    change_type: ChangeType::Added,
+                    old_value: None,
+                    new_value: Some("hello".to_string()),
+                }],
+            })
+            .await
+            .unwrap();
+        let diff = client.get_diff(&branches[0].head_commit, &result.hash).await.unwrap();
+        assert!(diff.changes.iter().any(|c| c.path == "/notes.txt"));
+        assert!(running.server.requests().contains(&"POST /api/commit".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_stream_tree_incrementally() {
+        let (running, client) = reference_client().await;
+        let expected = crate::irmin::tree::entries(&running.server.store().tree().unwrap());
+
+        let mut streamed = Vec::new();
+        let count = client
+            .stream_tree(None, |entries| {
+                streamed.extend(entries);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, expected.len());
+        let paths: Vec<&str> = streamed.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, expected.iter().map(|e| e.path.as_str()).collect::<Vec<_>>());
+
+        running.server.inject_once("/api/tree/stream", Fault::Malformed(Malformed::Html));
+        assert!(client.stream_tree(None, |_| Ok(())).await.is_err());
+        let error = client.stream_tree(Some("nope"), |_| Ok(())).await.unwrap_err();
+        assert!(error.to_string().contains("Unknown commit"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_client_reports_faults() {
+        let (running, client) = reference_client().await;
+
+        running.server.inject_once("/api/commits", Fault::Error(503, "store locked".to_string()));
+        let error = client.get_commits().await.unwrap_err().to_string();
+        assert!(error.contains("store locked"), "{}", error);
+
+        for kind in [Malformed::Truncated, Malformed::WrongShape, Malformed::Html, Malformed::Empty] {
+            running.server.inject_once("/api/tree", Fault::Malformed(kind));
+            assert!(client.get_tree().await.is_err(), "{:?} was accepted", kind);
+        }
+
+        running.server.inject_once("*", Fault::Latency(Duration::from_secs(2)));
+        assert!(client.get_branches().await.is_err());
+        assert!(client.get_branches().await.is_ok());
+    }
 }
\ No newline at end of file
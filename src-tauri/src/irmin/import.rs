@@ -0,0 +1,321 @@
+use super::{export, tree, types::*};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path};
+
+/// Directories that are never imported from a local directory
+const SKIPPED_DIRS: &[&str] = &[".git"];
+
+/// Kinds of sources an import can read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    Directory,
+    Tar,
+    TarGz,
+    Zip,
+    Json,
+}
+
+impl ImportSource {
+    /// Guess the source kind from the file system entry and its extension
+    pub fn detect(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(ImportSource::Directory);
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(ImportSource::TarGz)
+        } else if name.ends_with(".tar") {
+            Ok(ImportSource::Tar)
+        } else if name.ends_with(".zip") {
+            Ok(ImportSource::Zip)
+        } else if name.ends_with(".json") {
+            Ok(ImportSource::Json)
+        } else {
+            bail!("Cannot import {}: expected a directory, .tar, .tar.gz, .zip or .json", path.display())
+        }
+    }
+}
+
+/// Read every file of a source as store path → contents.
+///
+/// Archive and directory paths map onto store paths one to one; a manifest
+/// written by `export_tree` at the top level is skipped so exports can be
+/// imported again.
+pub fn read_source(path: &Path) -> Result<BTreeMap<String, String>> {
+    let mut files = match ImportSource::detect(path)? {
+        ImportSource::Directory => {
+            let mut files = BTreeMap::new();
+            read_directory(path, "/", &mut files)?;
+            files
+        }
+        ImportSource::Tar => read_tar(File::open(path)?)?,
+        ImportSource::TarGz => read_tar(flate2::read::GzDecoder::new(File::open(path)?))?,
+        ImportSource::Zip => read_zip(File::open(path)?)?,
+        ImportSource::Json => {
+            let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            let json: Value = serde_json::from_str(&text).with_context(|| format!("Invalid JSON in {}", path.display()))?;
+            read_json(&json)?
+        }
+    };
+    files.remove(&format!("/{}", export::MANIFEST_FILE));
+    Ok(files)
+}
+
+fn read_directory(dir: &Path, prefix: &str, files: &mut BTreeMap<String, String>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let store_path = tree::join_path(prefix, &name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                read_directory(&entry.path(), &store_path, files)?;
+            }
+        } else if file_type.is_file() {
+            let bytes = fs::read(entry.path())?;
+            files.insert(store_path.clone(), utf8(&store_path, bytes)?);
+        }
+    }
+    Ok(())
+}
+
+fn read_tar<R: Read>(reader: R) -> Result<BTreeMap<String, String>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let store_path = archive_path(&entry.path()?)?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        files.insert(store_path.clone(), utf8(&store_path, bytes)?);
+    }
+    Ok(files)
+}
+
+fn read_zip(file: File) -> Result<BTreeMap<String, String>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() || entry.is_symlink() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name() else {
+            bail!("Archive entry '{}' escapes the import root", entry.name());
+        };
+        let store_path = archive_path(&name)?;
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        files.insert(store_path.clone(), utf8(&store_path, bytes)?);
+    }
+    Ok(files)
+}
+
+/// Store path for an archive member, rejecting members outside the archive root
+fn archive_path(path: &Path) -> Result<String> {
+    let mut segments = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(segment) => segments.push(segment.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => bail!("Archive entry '{}' escapes the import root", path.display()),
+        }
+    }
+    Ok(format!("/{}", segments.join("/")))
+}
+
+fn utf8(path: &str, bytes: Vec<u8>) -> Result<String> {
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("{} is not valid UTF-8 and cannot be stored as a value", path))
+}
+
+/// Map a nested JSON object onto store paths.
+///
+/// Objects shaped like an `IrminNode` (`node_type` and `children`, as in
+/// `assets/demo-data/enterprise-tree.json`) are read node by node, ignoring
+/// the root key. Any other object is read as plain nesting: objects become
+/// directories, strings become values and other JSON values are stored as
+/// their JSON text.
+pub fn read_json(json: &Value) -> Result<BTreeMap<String, String>> {
+    let Some(object) = json.as_object() else {
+        bail!("Expected a JSON object at the top level");
+    };
+    let mut files = BTreeMap::new();
+    if object.contains_key("node_type") && object.contains_key("children") {
+        read_node_json(json, "/", &mut files)?;
+    } else {
+        read_plain_json(object, "/", &mut files);
+    }
+    Ok(files)
+}
+
+fn read_node_json(node: &Value, path: &str, files: &mut BTreeMap<String, String>) -> Result<()> {
+    match node.get("node_type").and_then(Value::as_str) {
+        Some("Directory") => {
+            let Some(children) = node.get("children").and_then(Value::as_object) else {
+                return Ok(());
+            };
+            for (key, child) in children {
+                read_node_json(child, &tree::join_path(path, key), files)?;
+            }
+        }
+        Some("File") => {
+            let value = node.get("value").and_then(Value::as_str).unwrap_or_default();
+            files.insert(path.to_string(), value.to_string());
+        }
+        Some(other) => eprintln!("Skipping {}: unsupported node type {}", path, other),
+        None => bail!("Node at {} has no node_type", path),
+    }
+    Ok(())
+}
+
+fn read_plain_json(object: &serde_json::Map<String, Value>, path: &str, files: &mut BTreeMap<String, String>) {
+    for (key, value) in object {
+        let child_path = tree::join_path(path, key);
+        match value {
+            Value::Object(children) => read_plain_json(children, &child_path, files),
+            Value::String(text) => {
+                files.insert(child_path, text.clone());
+            }
+            other => {
+                files.insert(child_path, other.to_string());
+            }
+        }
+    }
+}
+
+/// Changes that bring the files below `prefix` in `current` in line with
+/// `files`, ordered by path.
+///
+/// Files are mounted under `prefix`. With `replace`, existing files below the
+/// prefix that the source does not contain are deleted; otherwise they are
+/// left alone. Unchanged files produce no change.
+pub fn plan_import(
+    current: &IrminNode,
+    files: &BTreeMap<String, String>,
+    prefix: &str,
+    replace: bool,
+) -> Vec<DiffChange> {
+    let prefix = tree::normalize_path(prefix);
+    let mut existing = BTreeMap::new();
+    let base = tree::find_node(current, &prefix);
+    if let Some(base) = base.filter(|n| n.node_type == NodeType::Directory) {
+        tree::walk(base, &mut |path, _, node| {
+            if node.node_type != NodeType::Directory {
+                existing.insert(tree::join_path(&prefix, path.trim_start_matches('/')), node.value.clone());
+            }
+        });
+    }
+
+    let mut changes = Vec::new();
+    let mut imported = BTreeSet::new();
+    for (path, value) in files {
+        let target = tree::join_path(&prefix, path.trim_start_matches('/'));
+        match existing.get(&target) {
+            Some(Some(old)) if old == value => {}
+            Some(old) => changes.push(DiffChange {
+                path: target.clone(),
+                change_type: ChangeType::Modified,
+                old_value: old.clone(),
+                new_value: Some(value.clone()),
+            }),
+            None => changes.push(DiffChange {
+                path: target.clone(),
+                change_type: ChangeType::Added,
+                old_value: None,
+                new_value: Some(value.clone()),
+            }),
+        }
+        imported.insert(target);
+    }
+
+    if replace {
+        for (path, old) in &existing {
+            if !imported.contains(path) {
+                changes.push(DiffChange {
+                    path: path.clone(),
+                    change_type: ChangeType::Deleted,
+                    old_value: old.clone(),
+                    new_value: None,
+                });
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::mock_data;
+
+    #[test]
+    fn test_read_enterprise_tree_json() {
+        let json: Value = serde_json::from_str(include_str!("../../../assets/demo-data/enterprise-tree.json")).unwrap();
+        let files = read_json(&json).unwrap();
+        assert!(files["/config/database.json"].contains("db-primary.company.com"));
+        assert!(files.keys().all(|p| !p.starts_with("/enterprise-app")));
+
+        let plain = serde_json::json!({"users": {"alice.json": "{}", "count": 2}});
+        let files = read_json(&plain).unwrap();
+        assert_eq!(files["/users/alice.json"], "{}");
+        assert_eq!(files["/users/count"], "2");
+    }
+
+    #[test]
+    fn test_plan_import_under_prefix() {
+        let current = mock_data::generate_mock_tree();
+        let alice = current.children["users"].children["alice.json"].value.clone().unwrap();
+        let files = BTreeMap::from([
+            ("/alice.json".to_string(), alice),
+            ("/bob.json".to_string(), "{}".to_string()),
+            ("/dave.json".to_string(), "{}".to_string()),
+        ]);
+
+        let changes = plan_import(&current, &files, "users", false);
+        let summary: Vec<(&str, ChangeType)> = changes.iter().map(|c| (c.path.as_str(), c.change_type.clone())).collect();
+        assert_eq!(
+            summary,
+            vec![("/users/bob.json", ChangeType::Modified), ("/users/dave.json", ChangeType::Added)]
+        );
+
+        let only_dave = BTreeMap::from([("/dave.json".to_string(), "{}".to_string())]);
+        let replaced = plan_import(&current, &only_dave, "/users/", true);
+        let deleted: Vec<&str> = replaced
+            .iter()
+            .filter(|c| c.change_type == ChangeType::Deleted)
+            .map(|c| c.path.as_str())
+            .collect();
+        assert_eq!(deleted, vec!["/users/alice.json", "/users/bob.json"]);
+    }
+
+    #[test]
+    fn test_export_then_import_round_trips() {
+        let root = mock_data::generate_mock_tree();
+        let archive = std::env::temp_dir().join(format!("irmin-import-{}.zip", uuid::Uuid::new_v4()));
+        let source = export::ExportSource {
+            commit: "abc123".to_string(),
+            revision: "main".to_string(),
+            path: "/".to_string(),
+        };
+        export::export_tree(&root, &source, export::ExportFormat::Zip, &archive, &mut |_| Ok(())).unwrap();
+
+        let files = read_source(&archive).unwrap();
+        assert!(!files.contains_key(&format!("/{}", export::MANIFEST_FILE)));
+        assert!(plan_import(&root, &files, "/", true).is_empty());
+        fs::remove_file(archive).unwrap();
+    }
+}
@@ -1,9 +1,15 @@
-use std::process::Command;
-use tokio::task;
-use super::{types::*, http_client::*};
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use super::{types::*, cache::{self, Lifetime}, fixtures::FixtureStore, fuzzy, http_client::*, progress, snapshot, tree, validation::SchemaRules, wire};
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
 use anyhow::Result;
 use std::env;
 
+/// Author recorded on commits made by IrminView
+pub const DEFAULT_AUTHOR: &str = "IrminView";
+
 /// Configuration for Irmin store connection
 #[derive(Clone)]
 pub struct IrminConfig {
@@ -11,18 +17,32 @@ pub struct IrminConfig {
     pub bridge_executable: String,
     pub server_url: Option<String>,
     pub use_http: bool,
+    /// Fixture scenario served instead of a real store (see `fixtures`)
+    pub fixtures: Option<String>,
+    /// Snapshot file served read-only instead of a store (see `snapshot`)
+    pub snapshot: Option<String>,
+    /// Serve repeated reads from the shared response cache (see `cache`)
+    pub cache: bool,
 }
 
 impl IrminConfig {
     pub fn new() -> Self {
         let use_http = env::var("IRMIN_USE_HTTP").unwrap_or_default() == "true";
         let server_url = env::var("IRMIN_SERVER_URL").ok();
+        let fixtures = env::var("IRMIN_FIXTURES").ok().filter(|f| !f.is_empty());
+        let snapshot = env::var("IRMIN_SNAPSHOT")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .or_else(snapshot::active);
         
         Self {
             store_path: "./irmin_store".to_string(),
             bridge_executable: "irmin-bridge-cli".to_string(),
             server_url,
             use_http,
+            fixtures,
+            snapshot,
+            cache: cache::enabled_by_env(),
         }
     }
 
@@ -36,127 +56,421 @@ impl IrminConfig {
         self.use_http = true;
         self
     }
+
+    pub fn with_fixtures(mut self, scenario: String) -> Self {
+        self.fixtures = Some(scenario);
+        self
+    }
+
+    pub fn with_snapshot(mut self, path: String) -> Self {
+        self.snapshot = Some(path);
+        self
+    }
+
+    pub fn without_cache(mut self) -> Self {
+        self.cache = false;
+        self
+    }
+
+    /// Identifies the store in cache keys
+    fn cache_scope(&self) -> String {
+        match (self.use_http, &self.server_url) {
+            (true, Some(url)) => format!("http:{}", url),
+            (true, None) => format!("http:{}", IrminHttpConfig::new().server_url),
+            (false, _) => format!("bridge:{}:{}", self.bridge_executable, self.store_path),
+        }
+    }
+
+    /// The read-only store to serve, when a snapshot or fixtures are configured
+    fn fixture_store(&self) -> Result<Option<std::sync::Arc<FixtureStore>>> {
+        if let Some(path) = &self.snapshot {
+            return snapshot::open(path).map(Some);
+        }
+        self.fixtures.as_deref().map(FixtureStore::load).transpose()
+    }
 }
 
 /// Execute OCaml bridge command and parse JSON response
 async fn execute_bridge_command(config: &IrminConfig, args: &[&str]) -> Result<String> {
-    let config_clone = config.clone();
-    let args_vec: Vec<String> = args.iter().map(|s| s.to_string()).collect();
-    
-    task::spawn_blocking(move || {
-        let mut cmd = Command::new(&config_clone.bridge_executable);
-        cmd.args(&args_vec);
-        cmd.arg("--path").arg(&config_clone.store_path);
-        
-        let output = cmd.output()?;
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Bridge command failed: {}", stderr));
+    execute_bridge_command_with_input(config, args, None).await
+}
+
+/// Execute OCaml bridge command, writing `input` to its stdin.
+///
+/// The child is killed if the returned future is dropped, so cancelling an
+/// operation stops the bridge process too. Input is written on its own task
+/// while the output is read, so neither side can fill a pipe and block the
+/// other.
+async fn execute_bridge_command_with_input(config: &IrminConfig, args: &[&str], input: Option<String>) -> Result<String> {
+    let progress = progress::current();
+    progress.phase(&format!("Running irmin-bridge-cli {}", args.first().unwrap_or(&"")), None);
+    let mut cmd = Command::new(&config.bridge_executable);
+    cmd.args(args)
+        .arg("--path")
+        .arg(&config.store_path)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => Some(tokio::spawn(async move { stdin.write_all(input.as_bytes()).await })),
+        _ => None,
+    };
+    let output = child.wait_with_output().await?;
+    if let Some(writer) = writer {
+        // A bridge that exits without reading all its input reports why itself
+        if let Err(e) = writer.await? {
+            if output.status.success() {
+                return Err(e.into());
+            }
         }
-        
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Ok(stdout.trim().to_string())
-    }).await?
+    }
+    progress.advance(0, output.stdout.len() as u64);
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("Bridge command failed: {}", stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.trim().to_string())
+}
+
+/// Run `load` unless the response to `request` is cached
+async fn cached<T, F>(config: &IrminConfig, request: String, lifetime: Lifetime, load: F) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+    F: Future<Output = Result<T>>,
+{
+    if !config.cache {
+        return load.await;
+    }
+    let key = cache::key(&config.cache_scope(), &request);
+    if let Some(value) = cache::shared().lock().ok().and_then(|mut c| c.get(&key)) {
+        return Ok(value);
+    }
+    let value = load.await?;
+    if let Ok(mut c) = cache::shared().lock() {
+        c.put(&key, &value, lifetime);
+    }
+    Ok(value)
+}
+
+/// How long responses about `revision` stay valid: a branch name moves with
+/// its head, while any other revision the backend accepts names a commit.
+/// Without the branch list the revision is treated as moving.
+async fn revision_lifetime(config: &IrminConfig, revision: &str) -> Lifetime {
+    if !config.cache {
+        return Lifetime::Head;
+    }
+    match get_irmin_branches(config).await {
+        Ok(branches) if !branches.iter().any(|b| b.name == revision) => Lifetime::Immutable,
+        _ => Lifetime::Head,
+    }
 }
 
 /// Get tree structure from real Irmin store
 pub async fn get_irmin_tree(config: &IrminConfig) -> Result<IrminNode> {
-    if config.use_http {
-        if let Some(server_url) = &config.server_url {
-            let http_config = IrminHttpConfig::new().with_url(server_url.clone());
-            let client = IrminHttpClient::new(http_config);
-            client.get_tree().await
+    if let Some(store) = config.fixture_store()? {
+        return store.tree();
+    }
+    cached(config, "tree".to_string(), Lifetime::Head, async {
+        if config.use_http {
+            http_client(config).get_tree().await
         } else {
-            let http_config = IrminHttpConfig::new();
-            let client = IrminHttpClient::new(http_config);
-            client.get_tree().await
+            let json_str = execute_bridge_command(config, &["tree"]).await?;
+            let node: IrminNode = wire::decode(&json_str)?;
+            Ok(node)
+        }
+    })
+    .await
+}
+
+/// Build an HTTP client for the configured server
+fn http_client(config: &IrminConfig) -> IrminHttpClient {
+    let http_config = match &config.server_url {
+        Some(server_url) => IrminHttpConfig::new().with_url(server_url.clone()),
+        None => IrminHttpConfig::new(),
+    };
+    IrminHttpClient::new(http_config)
+}
+
+/// Get tree structure at a specific commit from real Irmin store
+pub async fn get_irmin_tree_at(config: &IrminConfig, commit: &str) -> Result<IrminNode> {
+    if let Some(store) = config.fixture_store()? {
+        return store.tree_at(commit);
+    }
+    if revision_lifetime(config, commit).await == Lifetime::Head {
+        return cached(config, format!("tree-at:{}", commit), Lifetime::Head, fetch_tree_at(config, commit)).await;
+    }
+
+    // Trees are cached by content hash, so commits with equal trees share one entry
+    let scope = config.cache_scope();
+    let commit_key = cache::key(&scope, &format!("commit-tree:{}", commit));
+    if let Ok(mut c) = cache::shared().lock() {
+        if let Some(hash) = c.get::<String>(&commit_key) {
+            if let Some(node) = c.get(&cache::key(&scope, &format!("tree:{}", hash))) {
+                return Ok(node);
+            }
         }
+    }
+    let node = fetch_tree_at(config, commit).await?;
+    let hash = tree::content_hash(&node);
+    if let Ok(mut c) = cache::shared().lock() {
+        c.put(&cache::key(&scope, &format!("tree:{}", hash)), &node, Lifetime::Immutable);
+        c.put(&commit_key, &hash, Lifetime::Immutable);
+    }
+    Ok(node)
+}
+
+async fn fetch_tree_at(config: &IrminConfig, commit: &str) -> Result<IrminNode> {
+    if config.use_http {
+        http_client(config).get_tree_at(commit).await
     } else {
-        let json_str = execute_bridge_command(config, &["tree"]).await?;
-        let node: IrminNode = serde_json::from_str(&json_str)?;
+        let json_str = execute_bridge_command(config, &["tree", "--commit", commit]).await?;
+        let node: IrminNode = wire::decode(&json_str)?;
         Ok(node)
     }
 }
 
+/// Entries handed over at once when a backend cannot stream
+const ENTRY_BATCH: usize = 1000;
+
+/// Stream the tree at `commit` (head of main when `None`) as flat entries.
+///
+/// The HTTP server and the bridge CLI (`tree-stream`) send NDJSON that is
+/// decoded as it arrives. Snapshots and fixture scenarios are already in
+/// memory and are handed over in batches. Returns the number of entries.
+pub async fn stream_irmin_tree<F>(config: &IrminConfig, commit: Option<&str>, mut on_entries: F) -> Result<usize>
+where
+    F: FnMut(Vec<TreeEntry>) -> Result<()>,
+{
+    if config.fixture_store()?.is_none() {
+        if config.use_http {
+            return http_client(config).stream_tree(commit, on_entries).await;
+        }
+        return stream_bridge_tree(config, commit, on_entries).await;
+    }
+    let root = match commit {
+        Some(commit) => get_irmin_tree_at(config, commit).await?,
+        None => get_irmin_tree(config).await?,
+    };
+    let entries = tree::entries(&root);
+    let progress = progress::current();
+    progress.phase("Streaming tree", Some(entries.len() as u64));
+    for batch in entries.chunks(ENTRY_BATCH) {
+        on_entries(batch.to_vec())?;
+        progress.advance(batch.len() as u64, 0);
+    }
+    Ok(entries.len())
+}
+
+/// Run the bridge CLI's `tree-stream` and decode its output as it is written.
+///
+/// Like other bridge commands, the child is killed if the returned future is
+/// dropped. Its stderr is collected on its own task, so a bridge logging a
+/// lot cannot block on a full pipe while stdout is read.
+async fn stream_bridge_tree<F>(config: &IrminConfig, commit: Option<&str>, mut on_entries: F) -> Result<usize>
+where
+    F: FnMut(Vec<TreeEntry>) -> Result<()>,
+{
+    let progress = progress::current();
+    progress.phase("Streaming tree", None);
+    let mut cmd = Command::new(&config.bridge_executable);
+    cmd.arg("tree-stream").arg("--path").arg(&config.store_path);
+    if let Some(commit) = commit {
+        cmd.arg("--commit").arg(commit);
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+    let mut stdout = child.stdout.take().expect("bridge stdout is piped");
+    let mut stderr = child.stderr.take().expect("bridge stderr is piped");
+    let errors = tokio::spawn(async move {
+        let mut errors = Vec::new();
+        stderr.read_to_end(&mut errors).await.map(|_| errors)
+    });
+    let mut decoder = wire::NdjsonDecoder::new();
+    let mut chunk = vec![0; 64 * 1024];
+    let mut count = 0;
+    loop {
+        let read = stdout.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        let entries = decoder.push(&chunk[..read])?;
+        progress.advance(entries.len() as u64, read as u64);
+        if !entries.is_empty() {
+            count += entries.len();
+            on_entries(entries)?;
+        }
+    }
+
+    let status = child.wait().await?;
+    let errors = errors.await??;
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&errors);
+        return Err(anyhow::anyhow!("Bridge command failed: {}", stderr));
+    }
+    let entries = decoder.finish()?;
+    if !entries.is_empty() {
+        count += entries.len();
+        on_entries(entries)?;
+    }
+    Ok(count)
+}
+
+/// Drop the cached responses that depend on branch heads, after the store
+/// was changed outside IrminView
+pub fn invalidate_cached_heads(config: &IrminConfig) {
+    if let Ok(mut c) = cache::shared().lock() {
+        c.invalidate_heads(&config.cache_scope());
+    }
+}
+
 /// Get commits from real Irmin store
 pub async fn get_irmin_commits(config: &IrminConfig) -> Result<Vec<IrminCommit>> {
-    if config.use_http {
-        if let Some(server_url) = &config.server_url {
-            let http_config = IrminHttpConfig::new().with_url(server_url.clone());
-            let client = IrminHttpClient::new(http_config);
-            client.get_commits().await
+    if let Some(store) = config.fixture_store()? {
+        return Ok(store.commits());
+    }
+    cached(config, "commits".to_string(), Lifetime::Head, async {
+        if config.use_http {
+            http_client(config).get_commits().await
         } else {
-            let http_config = IrminHttpConfig::new();
-            let client = IrminHttpClient::new(http_config);
-            client.get_commits().await
+            let json_str = execute_bridge_command(config, &["commits"]).await?;
+            let commits: Vec<IrminCommit> = wire::decode(&json_str)?;
+            Ok(commits)
         }
-    } else {
-        let json_str = execute_bridge_command(config, &["commits"]).await?;
-        let commits: Vec<IrminCommit> = serde_json::from_str(&json_str)?;
-        Ok(commits)
-    }
+    })
+    .await
 }
 
 /// Get branches from real Irmin store
 pub async fn get_irmin_branches(config: &IrminConfig) -> Result<Vec<IrminBranch>> {
-    if config.use_http {
-        if let Some(server_url) = &config.server_url {
-            let http_config = IrminHttpConfig::new().with_url(server_url.clone());
-            let client = IrminHttpClient::new(http_config);
-            client.get_branches().await
+    if let Some(store) = config.fixture_store()? {
+        return Ok(store.branches());
+    }
+    cached(config, "branches".to_string(), Lifetime::Head, async {
+        if config.use_http {
+            http_client(config).get_branches().await
         } else {
-            let http_config = IrminHttpConfig::new();
-            let client = IrminHttpClient::new(http_config);
-            client.get_branches().await
+            let json_str = execute_bridge_command(config, &["branches"]).await?;
+            let branches: Vec<IrminBranch> = wire::decode(&json_str)?;
+            Ok(branches)
         }
-    } else {
-        let json_str = execute_bridge_command(config, &["branches"]).await?;
-        let branches: Vec<IrminBranch> = serde_json::from_str(&json_str)?;
-        Ok(branches)
-    }
+    })
+    .await
 }
 
-/// Search keys in real Irmin store
+/// Search keys in real Irmin store, ranked and highlighted by `fuzzy` like
+/// searches of fixtures and indexes
 pub async fn search_irmin_keys(config: &IrminConfig, query: &str) -> Result<Vec<SearchResult>> {
-    if config.use_http {
-        if let Some(server_url) = &config.server_url {
-            let http_config = IrminHttpConfig::new().with_url(server_url.clone());
-            let client = IrminHttpClient::new(http_config);
-            client.search_keys(query).await
+    if let Some(store) = config.fixture_store()? {
+        return store.search(query);
+    }
+    let results = cached(config, format!("search:{}", query), Lifetime::Head, async {
+        if config.use_http {
+            http_client(config).search_keys(query).await
         } else {
-            let http_config = IrminHttpConfig::new();
-            let client = IrminHttpClient::new(http_config);
-            client.search_keys(query).await
+            let json_str = execute_bridge_command(config, &["search", query]).await?;
+            let results: Vec<SearchResult> = wire::decode(&json_str)?;
+            Ok(results)
         }
-    } else {
-        let json_str = execute_bridge_command(config, &["search", query]).await?;
-        let results: Vec<SearchResult> = serde_json::from_str(&json_str)?;
-        Ok(results)
-    }
+    })
+    .await?;
+    Ok(fuzzy::rerank(query, results))
 }
 
 /// Get diff between commits in real Irmin store
 pub async fn get_irmin_diff(config: &IrminConfig, from_commit: &str, to_commit: &str) -> Result<IrminDiff> {
-    if config.use_http {
-        if let Some(server_url) = &config.server_url {
-            let http_config = IrminHttpConfig::new().with_url(server_url.clone());
-            let client = IrminHttpClient::new(http_config);
-            client.get_diff(from_commit, to_commit).await
+    if let Some(store) = config.fixture_store()? {
+        return store.diff(from_commit, to_commit);
+    }
+    let lifetime = match (revision_lifetime(config, from_commit).await, revision_lifetime(config, to_commit).await) {
+        (Lifetime::Immutable, Lifetime::Immutable) => Lifetime::Immutable,
+        _ => Lifetime::Head,
+    };
+    cached(config, format!("diff:{}..{}", from_commit, to_commit), lifetime, async {
+        if config.use_http {
+            http_client(config).get_diff(from_commit, to_commit).await
         } else {
-            let http_config = IrminHttpConfig::new();
-            let client = IrminHttpClient::new(http_config);
-            client.get_diff(from_commit, to_commit).await
+            let json_str = execute_bridge_command(config, &["diff", from_commit, to_commit]).await?;
+            let diff: IrminDiff = wire::decode(&json_str)?;
+            Ok(diff)
         }
+    })
+    .await
+}
+
+/// Get the tree at the head of a branch, or `None` when the branch does not exist yet
+pub async fn get_irmin_branch_tree(config: &IrminConfig, branch: &str) -> Result<Option<IrminNode>> {
+    let branches = get_irmin_branches(config).await?;
+    match branches.iter().find(|b| b.name == branch) {
+        Some(b) => Ok(Some(get_irmin_tree_at(config, &b.head_commit).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Resolve a revision (branch name or commit hash) to a commit hash
+pub async fn resolve_irmin_revision(config: &IrminConfig, revision: &str) -> Result<String> {
+    let branches = get_irmin_branches(config).await?;
+    Ok(branches
+        .into_iter()
+        .find(|b| b.name == revision)
+        .map(|b| b.head_commit)
+        .unwrap_or_else(|| revision.to_string()))
+}
+
+/// Apply changes to a branch of the real Irmin store as a single commit.
+///
+/// Commits whose values violate the project's schema rules are rejected.
+pub async fn commit_irmin_changes(config: &IrminConfig, request: &CommitRequest) -> Result<CommitResult> {
+    let violations = SchemaRules::load()?.validate_changes(&request.changes);
+    if !violations.is_empty() {
+        let details: Vec<String> = violations
+            .iter()
+            .map(|v| format!("{}{}: {} ({})", v.path, v.pointer, v.message, v.schema))
+            .collect();
+        anyhow::bail!("Commit rejected by schema rules:\n{}", details.join("\n"));
+    }
+    if let Some(path) = &config.snapshot {
+        anyhow::bail!("Snapshot {} is read-only", path);
+    }
+    if let Some(fixtures) = &config.fixtures {
+        anyhow::bail!("Fixture scenario '{}' is read-only", fixtures);
+    }
+    let result = if config.use_http {
+        http_client(config).commit_changes(request).await
     } else {
-        let json_str = execute_bridge_command(config, &["diff", from_commit, to_commit]).await?;
-        let diff: IrminDiff = serde_json::from_str(&json_str)?;
-        Ok(diff)
+        let changes = serde_json::to_string(&request.changes)?;
+        let args = [
+            "apply",
+            "--branch",
+            &request.branch,
+            "--message",
+            &request.message,
+            "--author",
+            &request.author,
+        ];
+        let json_str = execute_bridge_command_with_input(config, &args, Some(changes)).await?;
+        Ok(wire::decode::<CommitResult>(&json_str)?)
+    };
+    if result.is_ok() {
+        invalidate_cached_heads(config);
     }
+    result
 }
 
 /// Initialize or check Irmin store
 pub async fn initialize_irmin_store(config: &IrminConfig) -> Result<()> {
+    if config.fixture_store()?.is_some() {
+        return Ok(());
+    }
     if config.use_http {
         // For HTTP mode, just check if the server is healthy
         if let Some(server_url) = &config.server_url {
@@ -206,6 +520,128 @@ mod tests {
         assert_eq!(config.store_path, "/custom/path");
     }
 
+    #[tokio::test]
+    async fn test_fixture_config_serves_scenario() {
+        let config = IrminConfig::new().with_fixtures("sample".to_string());
+        let branches = get_irmin_branches(&config).await.unwrap();
+        let feature = branches.iter().find(|b| b.name == "feature/logging").unwrap();
+        let tree = get_irmin_tree_at(&config, &feature.head_commit).await.unwrap();
+        assert!(tree.children.contains_key("logs"));
+        assert!(commit_irmin_changes(&config, &CommitRequest {
+            branch: "main".to_string(),
+            message: "Change".to_string(),
+            author: DEFAULT_AUTHOR.to_string(),
+            changes: Vec::new(),
+        })
+        .await
+        .is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_only_revisions_resolving_to_commits_are_immutable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("irmin-lifetime-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let branch = IrminBranch {
+            name: "deadbeef1".to_string(),
+            head_commit: "a1b2c3d4e5f6".to_string(),
+            last_updated: chrono::Utc::now(),
+            commit_count: 1,
+        };
+        let bridge = dir.join("bridge.sh");
+        let script = format!("#!/bin/sh\necho '{}'\n", serde_json::to_string(&[branch]).unwrap());
+        std::fs::write(&bridge, script).unwrap();
+        std::fs::set_permissions(&bridge, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = IrminConfig::new().with_path(dir.display().to_string());
+        config.use_http = false;
+        config.fixtures = None;
+        config.snapshot = None;
+        config.cache = true;
+        config.bridge_executable = bridge.display().to_string();
+        assert_eq!(revision_lifetime(&config, "deadbeef1").await, Lifetime::Head);
+        assert_eq!(revision_lifetime(&config, "a1b2c3d4e5f6").await, Lifetime::Immutable);
+        assert_eq!(revision_lifetime(&config.clone().without_cache(), "a1b2c3d4e5f6").await, Lifetime::Head);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bridge_tree_is_streamed_as_written() {
+        // A bridge that writes one entry, then waits for the first to be read
+        let received = std::env::temp_dir().join(format!("irmin-received-{}", uuid::Uuid::new_v4()));
+        let script = format!(
+            "#!/bin/sh\n[ \"$1 $5\" = \"tree-stream abc\" ] || exit 1\n\
+             echo '{{\"path\":\"/a\",\"type\":\"Directory\",\"size\":null,\"hash\":\"h1\"}}'\n\
+             while [ ! -e {0} ]; do sleep 0.05; done\n\
+             echo '{{\"path\":\"/a/b\",\"type\":\"File\",\"size\":3,\"hash\":\"h2\"}}'\n",
+            received.display()
+        );
+        let (dir, config) = script_bridge("stream", &script);
+        let mut paths = Vec::new();
+        let stream = stream_irmin_tree(&config, Some("abc"), |entries| {
+            std::fs::write(&received, "").unwrap();
+            paths.extend(entries.into_iter().map(|e| e.path));
+            Ok(())
+        });
+        let count = tokio::time::timeout(std::time::Duration::from_secs(10), stream).await.unwrap().unwrap();
+        assert_eq!((count, paths), (2, vec!["/a".to_string(), "/a/b".to_string()]));
+        std::fs::remove_file(received).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Config running `script` as the bridge CLI in a fresh directory
+    #[cfg(unix)]
+    fn script_bridge(name: &str, script: &str) -> (std::path::PathBuf, IrminConfig) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("irmin-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bridge = dir.join("bridge.sh");
+        std::fs::write(&bridge, script).unwrap();
+        std::fs::set_permissions(&bridge, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = IrminConfig::new().with_path(dir.display().to_string());
+        config.use_http = false;
+        config.fixtures = None;
+        config.snapshot = None;
+        config.cache = false;
+        config.bridge_executable = bridge.display().to_string();
+        (dir, config)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bridges_logging_more_than_a_pipe_holds_do_not_block() {
+        // 256 KiB on stderr before any output, and before reading stdin
+        let script = "#!/bin/sh\nhead -c 262144 /dev/zero >&2\n\
+            case \"$1\" in\n\
+              tree-stream) echo '{\"path\":\"/a\",\"type\":\"File\",\"size\":1,\"hash\":\"h\"}' ;;\n\
+              apply) cat >/dev/null; echo '{\"hash\":\"c1\"}' ;;\n\
+            esac\n";
+        let (dir, config) = script_bridge("stderr", script);
+        let timeout = std::time::Duration::from_secs(10);
+
+        let stream = stream_irmin_tree(&config, None, |_| Ok(()));
+        assert_eq!(tokio::time::timeout(timeout, stream).await.unwrap().unwrap(), 1);
+
+        let request = CommitRequest {
+            branch: "main".to_string(),
+            message: "Large value".to_string(),
+            author: "Tester".to_string(),
+            changes: vec![DiffChange {
+                path: "/large.txt".to_string(),
+                change_type: ChangeType::Added,
+                old_value: None,
+                new_value: Some("x".repeat(1 << 20)),
+            }],
+        };
+        let commit = commit_irmin_changes(&config, &request);
+        assert_eq!(tokio::time::timeout(timeout, commit).await.unwrap().unwrap().hash, "c1");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     // Integration tests would require the OCaml bridge to be built and installed
     // These would be run separately in CI/CD pipeline after building the OCaml components
 }
\ No newline at end of file
@@ -19,6 +19,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: Some(45),
             permissions: Some("644".to_string()),
         },
+        hash: None,
     });
     
     root_children.insert("README.md".to_string(), IrminNode {
@@ -31,6 +32,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: Some(58),
             permissions: Some("644".to_string()),
         },
+        hash: None,
     });
     
     // Create a directory with nested content
@@ -45,6 +47,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: Some(65),
             permissions: Some("644".to_string()),
         },
+        hash: None,
     });
     
     users_children.insert("bob.json".to_string(), IrminNode {
@@ -57,6 +60,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: Some(62),
             permissions: Some("644".to_string()),
         },
+        hash: None,
     });
     
     root_children.insert("users".to_string(), IrminNode {
@@ -69,6 +73,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: None,
             permissions: Some("755".to_string()),
         },
+        hash: None,
     });
     
     // Create another directory
@@ -83,6 +88,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: Some(85),
             permissions: Some("644".to_string()),
         },
+        hash: None,
     });
     
     root_children.insert("logs".to_string(), IrminNode {
@@ -95,6 +101,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: None,
             permissions: Some("755".to_string()),
         },
+        hash: None,
     });
     
     IrminNode {
@@ -107,6 +114,7 @@ pub fn generate_mock_tree() -> IrminNode {
             size: None,
             permissions: Some("755".to_string()),
         },
+        hash: None,
     }
 }
 
@@ -2,5 +2,26 @@ pub mod types;
 pub mod commands;
 pub mod mock_data;
 pub mod integration;
-pub mod demo;
-pub mod http_client;
\ No newline at end of file
+pub mod http_client;
+pub mod tree;
+pub mod search_index;
+pub mod fuzzy;
+pub mod filter;
+pub mod query;
+pub mod history_search;
+pub mod export;
+pub mod import;
+pub mod commit_export;
+pub mod patch;
+pub mod fixtures;
+pub mod stats;
+pub mod render;
+pub mod project;
+pub mod validation;
+pub mod cache;
+pub mod snapshot;
+pub mod reference_server;
+pub mod wire;
+pub mod operations;
+pub mod progress;
+pub mod watcher;
\ No newline at end of file
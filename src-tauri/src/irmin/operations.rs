@@ -0,0 +1,200 @@
+//! Long-running operations (tree loads, searches, diffs, exports) that the
+//! UI can cancel.
+//!
+//! A command registers its work under an operation ID chosen by the caller,
+//! so the UI knows the ID before the command returns, and runs it with
+//! [`Operation::run`]. [`cancel`] makes `run` return [`Cancelled`] at once
+//! and drops the work: in-flight HTTP requests are aborted and bridge
+//! processes are killed, as both are tied to their futures. Work on blocking
+//! threads checks a [`CancelHandle`] between steps.
+//!
+//! Starting an operation under an ID that is still running cancels the
+//! earlier one, so a UI can use one ID per input, e.g. `"search"`, and only
+//! the latest search runs.
+//!
+//! Work run by an operation reports its progress under the operation's ID
+//! (see `progress`).
+
+use super::progress::{self, Progress};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio_util::sync::CancellationToken;
+
+/// Error of an operation stopped by [`cancel`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cancelled(pub String);
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation {} was cancelled", self.0)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+struct Registered {
+    generation: u64,
+    token: CancellationToken,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Registered>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Registered>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A registered operation; unregistered, and its progress finished, when
+/// dropped
+pub struct Operation {
+    handle: CancelHandle,
+    generation: u64,
+    progress: Progress,
+}
+
+/// Cancellation state of an operation, for checking from blocking work
+#[derive(Clone)]
+pub struct CancelHandle {
+    id: String,
+    token: CancellationToken,
+}
+
+impl CancelHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// `Err(Cancelled)` once the operation has been cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled(self.id.clone()).into());
+        }
+        Ok(())
+    }
+}
+
+impl Operation {
+    /// Register an operation under `id`, or under a fresh ID when `None`
+    pub fn start(id: Option<String>) -> Self {
+        let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        let previous = registry().lock().expect("operation registry poisoned").insert(
+            id.clone(),
+            Registered {
+                generation,
+                token: token.clone(),
+            },
+        );
+        if let Some(previous) = previous {
+            previous.token.cancel();
+        }
+        Self {
+            progress: Progress::for_operation(&id),
+            handle: CancelHandle { id, token },
+            generation,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.handle.id
+    }
+
+    pub fn handle(&self) -> CancelHandle {
+        self.handle.clone()
+    }
+
+    /// Progress reporter, for work outside `run` such as blocking threads
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
+    /// Run `work` until it finishes or the operation is cancelled, with the
+    /// operation's reporter as `progress::current()`
+    pub async fn run<T>(&self, work: impl Future<Output = Result<T>>) -> Result<T> {
+        tokio::select! {
+            biased;
+            _ = self.handle.token.cancelled() => Err(Cancelled(self.handle.id.clone()).into()),
+            result = progress::scope(self.progress.clone(), work) => result,
+        }
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        self.progress.finish();
+        let mut registry = registry().lock().expect("operation registry poisoned");
+        if registry.get(&self.handle.id).is_some_and(|r| r.generation == self.generation) {
+            registry.remove(&self.handle.id);
+        }
+    }
+}
+
+/// Run `work` as the operation `id`
+pub async fn run<T>(id: Option<String>, work: impl Future<Output = Result<T>>) -> Result<T> {
+    Operation::start(id).run(work).await
+}
+
+/// Cancel the operation running under `id`; false when there is none
+pub fn cancel(id: &str) -> bool {
+    match registry().lock().expect("operation registry poisoned").remove(id) {
+        Some(registered) => {
+            registered.token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// IDs of the operations currently running
+pub fn running() -> Vec<String> {
+    let mut ids: Vec<String> = registry().lock().expect("operation registry poisoned").keys().cloned().collect();
+    ids.sort();
+    ids
+}
+
+/// Whether `error` comes from a cancelled operation
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Cancelled>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stops_running_work() {
+        let operation = Operation::start(Some("test-cancel".to_string()));
+        assert!(running().contains(&"test-cancel".to_string()));
+        let handle = operation.handle();
+        let work = operation.run(async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        });
+        let (result, cancelled) = tokio::join!(work, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel("test-cancel")
+        });
+        assert!(cancelled);
+        assert!(is_cancelled(&result.unwrap_err()));
+        assert!(handle.check().is_err());
+        assert!(!cancel("test-cancel"));
+    }
+
+    #[tokio::test]
+    async fn test_restarting_an_id_cancels_the_earlier_operation() {
+        let first = Operation::start(Some("test-restart".to_string()));
+        let second = Operation::start(Some("test-restart".to_string()));
+        assert!(first.handle().is_cancelled());
+        drop(first);
+        assert!(running().contains(&"test-restart".to_string()));
+        assert_eq!(second.run(async { Ok(1) }).await.unwrap(), 1);
+        drop(second);
+        assert!(!running().contains(&"test-restart".to_string()));
+    }
+}
@@ -0,0 +1,748 @@
+//! Portable patches for moving an `IrminDiff` between unrelated stores.
+//!
+//! A patch is plain text, one section per path:
+//!
+//! ```text
+//! irmin-patch 1
+//! from <commit>
+//! to <commit>
+//!
+//! diff /config.json
+//! index <sha256 of old value>..<sha256 of new value>
+//! --- /config.json
+//! +++ /config.json
+//! @@ -1,2 +1,2 @@
+//!  unchanged line
+//! -old line
+//! +new line
+//! diff /images/logo.bin
+//! binary
+//! index <old>..<new>
+//! old <base64>
+//! new <base64>
+//! ```
+//!
+//! Values with carriage returns are written like binary values, as the
+//! text format is line based and would lose them.
+//!
+//! Added and deleted paths carry a `new file` or `deleted file` header and use
+//! `0` for the missing side of `index`. A `rename from <path>` header moves a
+//! value to the section path. The `index` hashes are the preconditions
+//! checked when the patch is applied.
+
+use super::{integration, render::is_binary, tree, types::*};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::fmt;
+
+const FORMAT_HEADER: &str = "irmin-patch 1";
+const NO_NEWLINE: &str = "\\ No newline at end of file";
+const CONTEXT_LINES: usize = 3;
+
+/// A parsed or generated patch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub from_commit: String,
+    pub to_commit: String,
+    pub files: Vec<FilePatch>,
+}
+
+/// Changes to one path. `old_path` is `None` for added values and
+/// `new_path` is `None` for deleted ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    pub content: PatchContent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchContent {
+    Text(Vec<Hunk>),
+    Binary { old: Option<String>, new: Option<String> },
+}
+
+/// One `@@` hunk. Lines keep their trailing newline, except a final line
+/// that had none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<(ChangeTag, String)>,
+}
+
+/// Error while reading patch text
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {})", self.message, self.line)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// SHA-256 of a value, as used by `index` headers
+pub fn value_hash(value: &str) -> String {
+    Sha256::digest(value.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl FilePatch {
+    /// Path the section is written under
+    pub fn path(&self) -> &str {
+        self.new_path.as_deref().or(self.old_path.as_deref()).unwrap_or_default()
+    }
+
+    fn between(old_path: Option<&str>, new_path: Option<&str>, old: Option<&str>, new: Option<&str>) -> Self {
+        let content = if old.is_some_and(needs_base64) || new.is_some_and(needs_base64) {
+            PatchContent::Binary { old: old.map(str::to_string), new: new.map(str::to_string) }
+        } else {
+            PatchContent::Text(text_hunks(old.unwrap_or_default(), new.unwrap_or_default()))
+        };
+        Self {
+            old_path: old_path.map(str::to_string),
+            new_path: new_path.map(str::to_string),
+            old_hash: old.map(value_hash),
+            new_hash: new.map(value_hash),
+            content,
+        }
+    }
+
+    fn reversed(&self) -> Self {
+        let content = match &self.content {
+            PatchContent::Text(hunks) => PatchContent::Text(
+                hunks
+                    .iter()
+                    .map(|h| Hunk {
+                        old_start: h.new_start,
+                        old_len: h.new_len,
+                        new_start: h.old_start,
+                        new_len: h.old_len,
+                        lines: h
+                            .lines
+                            .iter()
+                            .map(|(tag, line)| {
+                                let tag = match tag {
+                                    ChangeTag::Delete => ChangeTag::Insert,
+                                    ChangeTag::Insert => ChangeTag::Delete,
+                                    ChangeTag::Equal => ChangeTag::Equal,
+                                };
+                                (tag, line.clone())
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            ),
+            PatchContent::Binary { old, new } => PatchContent::Binary { old: new.clone(), new: old.clone() },
+        };
+        Self {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            old_hash: self.new_hash.clone(),
+            new_hash: self.old_hash.clone(),
+            content,
+        }
+    }
+
+    /// The value this patch produces from `old` (the current value at `old_path`)
+    fn apply_to(&self, old: &str) -> Result<String, String> {
+        match &self.content {
+            PatchContent::Binary { new, .. } => Ok(new.clone().unwrap_or_default()),
+            PatchContent::Text(hunks) => apply_hunks(old, hunks),
+        }
+    }
+}
+
+/// Whether a value cannot be written as text hunks
+fn needs_base64(value: &str) -> bool {
+    is_binary(value) || value.contains('\r')
+}
+
+fn text_hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| (change.tag(), change.value().to_string()))
+                .collect();
+            Hunk {
+                old_start: hunk_start(old_range.start, old_range.len()),
+                old_len: old_range.len(),
+                new_start: hunk_start(new_range.start, new_range.len()),
+                new_len: new_range.len(),
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Unified diffs number lines from 1, and point at the line before an empty range
+fn hunk_start(start: usize, len: usize) -> usize {
+    if len == 0 {
+        start
+    } else {
+        start + 1
+    }
+}
+
+fn apply_hunks(old: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(old.len());
+    let mut cursor = 0;
+    for hunk in hunks {
+        let start = if hunk.old_len == 0 { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        if start < cursor || start > lines.len() {
+            return Err(format!("hunk at line {} is out of order or out of range", hunk.old_start));
+        }
+        result.extend(lines[cursor..start].iter().copied());
+        cursor = start;
+        for (tag, line) in &hunk.lines {
+            match tag {
+                ChangeTag::Insert => result.push_str(line),
+                ChangeTag::Equal | ChangeTag::Delete => {
+                    if lines.get(cursor) != Some(&line.as_str()) {
+                        return Err(format!("hunk at line {} does not match line {}", hunk.old_start, cursor + 1));
+                    }
+                    if *tag == ChangeTag::Equal {
+                        result.push_str(line);
+                    }
+                    cursor += 1;
+                }
+            }
+        }
+    }
+    result.extend(lines[cursor..].iter().copied());
+    Ok(result)
+}
+
+impl Patch {
+    /// Build a patch from a diff. A deleted and an added path with the same
+    /// value are written as a rename.
+    pub fn from_diff(diff: &IrminDiff) -> Self {
+        let mut changes: Vec<&DiffChange> = diff.changes.iter().collect();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut renamed_to: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut renamed_from: BTreeMap<&str, &str> = BTreeMap::new();
+        for added in changes.iter().filter(|c| c.change_type == ChangeType::Added) {
+            let source = changes.iter().find(|c| {
+                c.change_type == ChangeType::Deleted
+                    && c.old_value == added.new_value
+                    && !renamed_to.contains_key(c.path.as_str())
+            });
+            if let Some(source) = source {
+                renamed_to.insert(&source.path, &added.path);
+                renamed_from.insert(&added.path, &source.path);
+            }
+        }
+
+        let files = changes
+            .iter()
+            .filter_map(|change| {
+                let path = change.path.as_str();
+                match change.change_type {
+                    ChangeType::Modified => Some(FilePatch::between(
+                        Some(path),
+                        Some(path),
+                        Some(change.old_value.as_deref().unwrap_or_default()),
+                        Some(change.new_value.as_deref().unwrap_or_default()),
+                    )),
+                    ChangeType::Added => {
+                        let new = change.new_value.as_deref().unwrap_or_default();
+                        let old_path = renamed_from.get(path).copied();
+                        Some(FilePatch::between(old_path, Some(path), old_path.map(|_| new), Some(new)))
+                    }
+                    ChangeType::Deleted if renamed_to.contains_key(path) => None,
+                    ChangeType::Deleted => Some(FilePatch::between(
+                        Some(path),
+                        None,
+                        Some(change.old_value.as_deref().unwrap_or_default()),
+                        None,
+                    )),
+                }
+            })
+            .collect();
+
+        Self {
+            from_commit: diff.from_commit.clone(),
+            to_commit: diff.to_commit.clone(),
+            files,
+        }
+    }
+
+    /// The patch that undoes this one
+    pub fn reversed(&self) -> Self {
+        Self {
+            from_commit: self.to_commit.clone(),
+            to_commit: self.from_commit.clone(),
+            files: self.files.iter().map(FilePatch::reversed).collect(),
+        }
+    }
+
+    /// Apply the patch to the values of a tree (store path → value).
+    ///
+    /// Every path is checked against its `index` precondition; paths that
+    /// fail are reported as rejects and left untouched, and the remaining
+    /// paths are returned as changes in patch order.
+    pub fn apply(&self, values: &BTreeMap<String, String>) -> (Vec<DiffChange>, Vec<PatchReject>) {
+        let mut values = values.clone();
+        let mut changes = Vec::new();
+        let mut rejects = Vec::new();
+        for file in &self.files {
+            match apply_file(file, &mut values) {
+                Ok(file_changes) => changes.extend(file_changes),
+                Err(reason) => rejects.push(PatchReject { path: file.path().to_string(), reason }),
+            }
+        }
+        (changes, rejects)
+    }
+
+    pub fn parse(input: &str) -> Result<Self, PatchError> {
+        Parser { lines: input.lines().collect(), pos: 0 }.parse()
+    }
+}
+
+fn apply_file(file: &FilePatch, values: &mut BTreeMap<String, String>) -> Result<Vec<DiffChange>, String> {
+    let old = match &file.old_path {
+        Some(path) => {
+            let current = values.get(path).ok_or_else(|| format!("{} does not exist", path))?;
+            if Some(value_hash(current)) != file.old_hash {
+                return Err(format!("current value of {} does not match the patch", path));
+            }
+            Some(current.clone())
+        }
+        None => None,
+    };
+    if let Some(path) = &file.new_path {
+        if file.old_path.as_ref() != Some(path) && values.contains_key(path) {
+            return Err(format!("{} already exists", path));
+        }
+    }
+
+    let mut changes = Vec::new();
+    match (&file.old_path, &file.new_path) {
+        (Some(old_path), Some(new_path)) => {
+            let old = old.unwrap_or_default();
+            let new = file.apply_to(&old)?;
+            check_result(file, &new)?;
+            if old_path == new_path {
+                changes.push(change(new_path, ChangeType::Modified, Some(old), Some(new.clone())));
+            } else {
+                values.remove(old_path);
+                changes.push(change(old_path, ChangeType::Deleted, Some(old), None));
+                changes.push(change(new_path, ChangeType::Added, None, Some(new.clone())));
+            }
+            values.insert(new_path.clone(), new);
+        }
+        (None, Some(new_path)) => {
+            let new = file.apply_to("")?;
+            check_result(file, &new)?;
+            changes.push(change(new_path, ChangeType::Added, None, Some(new.clone())));
+            values.insert(new_path.clone(), new);
+        }
+        (Some(old_path), None) => {
+            values.remove(old_path);
+            changes.push(change(old_path, ChangeType::Deleted, old, None));
+        }
+        (None, None) => return Err("patch section names no path".to_string()),
+    }
+    Ok(changes)
+}
+
+fn check_result(file: &FilePatch, new: &str) -> Result<(), String> {
+    if Some(value_hash(new)) == file.new_hash {
+        Ok(())
+    } else {
+        Err("applying the hunks did not produce the patched value".to_string())
+    }
+}
+
+fn change(path: &str, change_type: ChangeType, old_value: Option<String>, new_value: Option<String>) -> DiffChange {
+    DiffChange { path: path.to_string(), change_type, old_value, new_value }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", FORMAT_HEADER)?;
+        writeln!(f, "from {}", self.from_commit)?;
+        writeln!(f, "to {}", self.to_commit)?;
+        for file in &self.files {
+            writeln!(f)?;
+            writeln!(f, "diff {}", file.path())?;
+            match (&file.old_path, &file.new_path) {
+                (None, Some(_)) => writeln!(f, "new file")?,
+                (Some(_), None) => writeln!(f, "deleted file")?,
+                (Some(old), Some(new)) if old != new => writeln!(f, "rename from {}", old)?,
+                _ => {}
+            }
+            if matches!(file.content, PatchContent::Binary { .. }) {
+                writeln!(f, "binary")?;
+            }
+            writeln!(
+                f,
+                "index {}..{}",
+                file.old_hash.as_deref().unwrap_or("0"),
+                file.new_hash.as_deref().unwrap_or("0")
+            )?;
+            match &file.content {
+                PatchContent::Binary { old, new } => {
+                    if let Some(old) = old {
+                        writeln!(f, "old {}", BASE64.encode(old))?;
+                    }
+                    if let Some(new) = new {
+                        writeln!(f, "new {}", BASE64.encode(new))?;
+                    }
+                }
+                PatchContent::Text(hunks) if !hunks.is_empty() => {
+                    writeln!(f, "--- {}", file.old_path.as_deref().unwrap_or("/dev/null"))?;
+                    writeln!(f, "+++ {}", file.new_path.as_deref().unwrap_or("/dev/null"))?;
+                    for hunk in hunks {
+                        writeln!(f, "@@ -{},{} +{},{} @@", hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len)?;
+                        for (tag, line) in &hunk.lines {
+                            let prefix = match tag {
+                                ChangeTag::Equal => ' ',
+                                ChangeTag::Delete => '-',
+                                ChangeTag::Insert => '+',
+                            };
+                            match line.strip_suffix('\n') {
+                                Some(line) => writeln!(f, "{}{}", prefix, line)?,
+                                None => writeln!(f, "{}{}\n{}", prefix, line, NO_NEWLINE)?,
+                            }
+                        }
+                    }
+                }
+                PatchContent::Text(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>) -> PatchError {
+        PatchError { line: self.pos + 1, message: message.into() }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.lines.get(self.pos).copied()
+    }
+
+    fn expect_prefix(&mut self, prefix: &str) -> Result<&'a str, PatchError> {
+        match self.peek().and_then(|l| l.strip_prefix(prefix)) {
+            Some(rest) => {
+                self.pos += 1;
+                Ok(rest)
+            }
+            None => Err(self.error(format!("expected '{}'", prefix.trim_end()))),
+        }
+    }
+
+    fn skip_blank(&mut self) {
+        while self.peek().is_some_and(|l| l.trim().is_empty()) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse(mut self) -> Result<Patch, PatchError> {
+        self.skip_blank();
+        if self.peek() != Some(FORMAT_HEADER) {
+            return Err(self.error(format!("expected '{}'", FORMAT_HEADER)));
+        }
+        self.pos += 1;
+        let from_commit = self.expect_prefix("from ")?.to_string();
+        let to_commit = self.expect_prefix("to ")?.to_string();
+
+        let mut files = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek().is_none() {
+                break;
+            }
+            files.push(self.parse_file()?);
+        }
+        Ok(Patch { from_commit, to_commit, files })
+    }
+
+    fn parse_file(&mut self) -> Result<FilePatch, PatchError> {
+        let path = self.expect_prefix("diff ")?.to_string();
+        let (mut added, mut deleted, mut binary) = (false, false, false);
+        let mut rename_from = None;
+        loop {
+            match self.peek() {
+                Some("new file") => added = true,
+                Some("deleted file") => deleted = true,
+                Some("binary") => binary = true,
+                Some(line) if line.starts_with("rename from ") => {
+                    rename_from = Some(line["rename from ".len()..].to_string());
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        let index = self.expect_prefix("index ")?;
+        let Some((old_hash, new_hash)) = index.split_once("..").filter(|(o, n)| !o.is_empty() && !n.is_empty()) else {
+            return Err(self.error("expected 'index OLD..NEW'"));
+        };
+        let hash = |h: &str| (h != "0").then(|| h.to_string());
+        let (old_hash, new_hash) = (hash(old_hash), hash(new_hash));
+
+        let old_path = match (added, &rename_from) {
+            (true, _) => None,
+            (false, Some(from)) => Some(from.clone()),
+            (false, None) => Some(path.clone()),
+        };
+        let new_path = (!deleted).then(|| path.clone());
+        if old_path.is_some() != old_hash.is_some() || new_path.is_some() != new_hash.is_some() {
+            return Err(self.error(format!("index of {} does not match its header", path)));
+        }
+
+        let content = if binary {
+            let old = self.parse_base64("old ", old_path.is_some())?;
+            let new = self.parse_base64("new ", new_path.is_some())?;
+            PatchContent::Binary { old, new }
+        } else {
+            PatchContent::Text(self.parse_hunks()?)
+        };
+        Ok(FilePatch { old_path, new_path, old_hash, new_hash, content })
+    }
+
+    fn parse_base64(&mut self, prefix: &str, present: bool) -> Result<Option<String>, PatchError> {
+        if !present {
+            return Ok(None);
+        }
+        let encoded = self.expect_prefix(prefix)?;
+        let bytes = BASE64.decode(encoded.trim()).map_err(|e| self.error(format!("invalid base64: {}", e)))?;
+        String::from_utf8(bytes).map(Some).map_err(|_| self.error("binary value is not valid UTF-8"))
+    }
+
+    fn parse_hunks(&mut self) -> Result<Vec<Hunk>, PatchError> {
+        if !self.peek().is_some_and(|l| l.starts_with("--- ")) {
+            return Ok(Vec::new());
+        }
+        self.pos += 1;
+        self.expect_prefix("+++ ")?;
+
+        let mut hunks = Vec::new();
+        while let Some(header) = self.peek().and_then(|l| l.strip_prefix("@@ ")) {
+            let ranges = header
+                .strip_suffix(" @@")
+                .and_then(|r| r.split_once(' '))
+                .and_then(|(old, new)| Some((parse_range(old.strip_prefix('-')?)?, parse_range(new.strip_prefix('+')?)?)));
+            let Some(((old_start, old_len), (new_start, new_len))) = ranges else {
+                return Err(self.error("invalid hunk header"));
+            };
+            self.pos += 1;
+
+            let mut lines: Vec<(ChangeTag, String)> = Vec::new();
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < old_len || new_seen < new_len {
+                let Some(line) = self.peek() else {
+                    return Err(self.error("hunk ends early"));
+                };
+                let tag = match line.chars().next() {
+                    Some(' ') | None => ChangeTag::Equal,
+                    Some('-') => ChangeTag::Delete,
+                    Some('+') => ChangeTag::Insert,
+                    _ => return Err(self.error("expected a hunk line starting with ' ', '-' or '+'")),
+                };
+                if tag != ChangeTag::Insert {
+                    old_seen += 1;
+                }
+                if tag != ChangeTag::Delete {
+                    new_seen += 1;
+                }
+                lines.push((tag, format!("{}\n", line.get(1..).unwrap_or_default())));
+                self.pos += 1;
+                if self.peek() == Some(NO_NEWLINE) {
+                    if let Some((_, last)) = lines.last_mut() {
+                        last.pop();
+                    }
+                    self.pos += 1;
+                }
+            }
+            hunks.push(Hunk { old_start, old_len, new_start, new_len, lines });
+        }
+        Ok(hunks)
+    }
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Options for `apply_to_branch`
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    pub reverse: bool,
+    pub dry_run: bool,
+    /// Commit the applicable paths even when others are rejected
+    pub partial: bool,
+    pub message: Option<String>,
+}
+
+/// The patch for the diff between two commits of the store
+pub async fn diff_patch(config: &integration::IrminConfig, from_commit: &str, to_commit: &str) -> anyhow::Result<Patch> {
+    let diff = integration::get_irmin_diff(config, from_commit, to_commit).await?;
+    Ok(Patch::from_diff(&diff))
+}
+
+/// Apply a patch to the head of `branch` as one commit.
+///
+/// Any reject leaves the branch untouched unless `partial` is set, in which
+/// case the applicable paths are committed and the rejects reported.
+pub async fn apply_to_branch(
+    config: &integration::IrminConfig,
+    patch: &Patch,
+    branch: &str,
+    options: &ApplyOptions,
+) -> anyhow::Result<PatchReport> {
+    let patch = if options.reverse { patch.reversed() } else { patch.clone() };
+    let values = integration::get_irmin_branch_tree(config, branch)
+        .await?
+        .map(|root| tree::file_values(&root))
+        .unwrap_or_default();
+    let (changes, rejects) = patch.apply(&values);
+
+    let mut report = PatchReport {
+        branch: branch.to_string(),
+        dry_run: options.dry_run,
+        reverse: options.reverse,
+        changes,
+        rejects,
+        commit: None,
+    };
+    if options.dry_run || report.changes.is_empty() || (!report.rejects.is_empty() && !options.partial) {
+        return Ok(report);
+    }
+
+    let request = CommitRequest {
+        branch: branch.to_string(),
+        message: options
+            .message
+            .clone()
+            .unwrap_or_else(|| format!("Apply patch {}..{}", patch.from_commit, patch.to_commit)),
+        author: integration::DEFAULT_AUTHOR.to_string(),
+        changes: report.changes.clone(),
+    };
+    report.commit = Some(integration::commit_irmin_changes(config, &request).await?.hash);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(changes: Vec<DiffChange>) -> IrminDiff {
+        IrminDiff { from_commit: "a1".to_string(), to_commit: "b2".to_string(), changes }
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect()
+    }
+
+    fn sample() -> IrminDiff {
+        diff(vec![
+            change("/config.toml", ChangeType::Modified, Some("a\nb\nc\n".into()), Some("a\nB\nc".into())),
+            change("/new.txt", ChangeType::Added, None, Some("hello\n".into())),
+            change("/gone.txt", ChangeType::Deleted, Some("bye\n".into()), None),
+            change("/old/name.json", ChangeType::Deleted, Some("{}".into()), None),
+            change("/new/name.json", ChangeType::Added, None, Some("{}".into())),
+            change("/logo.bin", ChangeType::Modified, Some("\u{0}\u{1}".into()), Some("\u{0}\u{2}".into())),
+        ])
+    }
+
+    #[test]
+    fn test_patch_text_round_trips() {
+        let patch = Patch::from_diff(&sample());
+        let text = patch.to_string();
+        assert!(text.contains("rename from /old/name.json"));
+        assert!(text.contains("new file") && text.contains("deleted file") && text.contains("binary"));
+        assert!(text.contains("-b\n-c\n+B\n+c\n") && text.contains(NO_NEWLINE));
+        assert_eq!(Patch::parse(&text).unwrap(), patch);
+    }
+
+    #[test]
+    fn test_apply_and_reverse() {
+        let before = values(&[
+            ("/config.toml", "a\nb\nc\n"),
+            ("/gone.txt", "bye\n"),
+            ("/old/name.json", "{}"),
+            ("/logo.bin", "\u{0}\u{1}"),
+        ]);
+        let patch = Patch::parse(&Patch::from_diff(&sample()).to_string()).unwrap();
+        let (changes, rejects) = patch.apply(&before);
+        assert!(rejects.is_empty(), "{:?}", rejects);
+
+        let mut after = before.clone();
+        for change in &changes {
+            match &change.new_value {
+                Some(value) => after.insert(change.path.clone(), value.clone()),
+                None => after.remove(&change.path),
+            };
+        }
+        assert_eq!(after["/config.toml"], "a\nB\nc");
+        assert_eq!(after["/new/name.json"], "{}");
+        assert!(!after.contains_key("/old/name.json"));
+
+        let (undo, rejects) = patch.reversed().apply(&after);
+        assert!(rejects.is_empty(), "{:?}", rejects);
+        for change in &undo {
+            match &change.new_value {
+                Some(value) => after.insert(change.path.clone(), value.clone()),
+                None => after.remove(&change.path),
+            };
+        }
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn test_preconditions_reject_per_path() {
+        let current = values(&[("/config.toml", "changed elsewhere\n"), ("/new.txt", "exists"), ("/old/name.json", "{}")]);
+        let (changes, rejects) = Patch::from_diff(&sample()).apply(&current);
+        let rejected: Vec<&str> = rejects.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(rejected, vec!["/config.toml", "/gone.txt", "/logo.bin", "/new.txt"]);
+        assert_eq!(changes.len(), 2, "the rename still applies");
+
+        assert!(Patch::parse("irmin-patch 1\nfrom a\nto b\ndiff /x\nindex 0..\n").is_err());
+        assert_eq!(Patch::parse("garbage").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_crlf_values_round_trip() {
+        let crlf = diff(vec![change(
+            "/windows.ini",
+            ChangeType::Modified,
+            Some("a\r\nb\r\n".into()),
+            Some("a\r\nc\r\n".into()),
+        )]);
+        let patch = Patch::parse(&Patch::from_diff(&crlf).to_string()).unwrap();
+        let (changes, rejects) = patch.apply(&values(&[("/windows.ini", "a\r\nb\r\n")]));
+        assert!(rejects.is_empty(), "{:?}", rejects);
+        assert_eq!(changes[0].new_value.as_deref(), Some("a\r\nc\r\n"));
+    }
+}
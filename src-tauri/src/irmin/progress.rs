@@ -0,0 +1,253 @@
+//! Progress of long-running operations.
+//!
+//! Every [`Operation`](super::operations::Operation) carries a [`Progress`]
+//! reporter. Backends and engines running inside it report what they are
+//! doing through [`current`]: the phase, items done out of a total when
+//! known, and bytes transferred. Updates go to the process-wide
+//! [`ProgressSink`]; the desktop app installs one that emits
+//! `operation-progress` events. Phase changes and the final update are always
+//! delivered, while updates in between are throttled to one per
+//! [`THROTTLE`].
+//!
+//! [`Recorder`] is a sink that keeps every update, for tests.
+
+use super::types::OperationProgress;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two updates within a phase
+pub const THROTTLE: Duration = Duration::from_millis(100);
+
+/// Receives progress updates
+pub trait ProgressSink: Send + Sync {
+    fn emit(&self, progress: &OperationProgress);
+}
+
+impl<F: Fn(&OperationProgress) + Send + Sync> ProgressSink for F {
+    fn emit(&self, progress: &OperationProgress) {
+        self(progress)
+    }
+}
+
+fn sink() -> &'static RwLock<Option<Arc<dyn ProgressSink>>> {
+    static SINK: OnceLock<RwLock<Option<Arc<dyn ProgressSink>>>> = OnceLock::new();
+    SINK.get_or_init(Default::default)
+}
+
+/// Send the progress of operations started from now on to `new_sink`
+pub fn set_sink(new_sink: Arc<dyn ProgressSink>) {
+    *sink().write().expect("progress sink poisoned") = Some(new_sink);
+}
+
+tokio::task_local! {
+    static CURRENT: Progress;
+}
+
+/// Reporter of the operation the calling task runs in; does nothing outside
+/// an operation
+pub fn current() -> Progress {
+    CURRENT.try_with(Clone::clone).unwrap_or_else(|_| Progress::disabled())
+}
+
+/// Run `work` with `progress` as its [`current`] reporter
+pub async fn scope<T>(progress: Progress, work: impl std::future::Future<Output = T>) -> T {
+    CURRENT.scope(progress, work).await
+}
+
+/// Reports the progress of one operation; clones report to the same state
+#[derive(Clone, Default)]
+pub struct Progress {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    sink: Arc<dyn ProgressSink>,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+struct State {
+    progress: OperationProgress,
+    last_emit: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(operation_id: &str, sink: Arc<dyn ProgressSink>) -> Self {
+        Self {
+            inner: Some(Arc::new(Inner {
+                sink,
+                interval: THROTTLE,
+                state: Mutex::new(State {
+                    progress: OperationProgress {
+                        operation_id: operation_id.to_string(),
+                        phase: String::new(),
+                        items_done: 0,
+                        total_items: None,
+                        bytes: 0,
+                        finished: false,
+                    },
+                    last_emit: None,
+                }),
+            })),
+        }
+    }
+
+    /// Reporter for `operation_id` sending to the installed sink, if any
+    pub fn for_operation(operation_id: &str) -> Self {
+        match sink().read().expect("progress sink poisoned").clone() {
+            Some(sink) => Self::new(operation_id, sink),
+            None => Self::disabled(),
+        }
+    }
+
+    /// Reporter that drops every update
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Minimum time between two updates within a phase (`THROTTLE` by default)
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        if let Some(inner) = self.inner.as_mut().and_then(Arc::get_mut) {
+            inner.interval = interval;
+        }
+        self
+    }
+
+    /// Start a new phase with `total_items` when known; always reported
+    pub fn phase(&self, phase: &str, total_items: Option<u64>) {
+        self.update(true, |p| {
+            p.phase = phase.to_string();
+            p.items_done = 0;
+            p.total_items = total_items;
+            p.bytes = 0;
+        });
+    }
+
+    /// Count `items` more items and `bytes` more bytes in the current phase
+    pub fn advance(&self, items: u64, bytes: u64) {
+        self.update(false, |p| {
+            p.items_done += items;
+            p.bytes += bytes;
+        });
+    }
+
+    /// Set the items and bytes done in the current phase
+    pub fn set(&self, items_done: u64, bytes: u64) {
+        self.update(false, |p| {
+            p.items_done = items_done;
+            p.bytes = bytes;
+        });
+    }
+
+    /// Report the operation as finished, if anything was reported before
+    pub fn finish(&self) {
+        let Some(inner) = &self.inner else { return };
+        let started = inner.state.lock().map(|s| s.last_emit.is_some()).unwrap_or(false);
+        if started {
+            self.update(true, |p| p.finished = true);
+        }
+    }
+
+    fn update(&self, always: bool, change: impl FnOnce(&mut OperationProgress)) {
+        let Some(inner) = &self.inner else { return };
+        let Ok(mut state) = inner.state.lock() else { return };
+        if state.progress.finished {
+            return;
+        }
+        change(&mut state.progress);
+        let now = Instant::now();
+        let due = state.last_emit.is_none_or(|last| now.duration_since(last) >= inner.interval);
+        if always || due {
+            state.last_emit = Some(now);
+            inner.sink.emit(&state.progress);
+        }
+    }
+}
+
+/// Sink keeping every update, for tests
+#[derive(Clone, Default)]
+pub struct Recorder {
+    events: Arc<Mutex<Vec<OperationProgress>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every update received so far
+    pub fn events(&self) -> Vec<OperationProgress> {
+        self.events.lock().expect("recorder poisoned").clone()
+    }
+
+    /// Updates of the operation `operation_id`
+    pub fn events_for(&self, operation_id: &str) -> Vec<OperationProgress> {
+        self.events().into_iter().filter(|p| p.operation_id == operation_id).collect()
+    }
+
+    /// Phases of `operation_id` in the order they started
+    pub fn phases(&self, operation_id: &str) -> Vec<String> {
+        let mut phases: Vec<String> = Vec::new();
+        for event in self.events_for(operation_id) {
+            if phases.last() != Some(&event.phase) {
+                phases.push(event.phase);
+            }
+        }
+        phases
+    }
+}
+
+impl ProgressSink for Recorder {
+    fn emit(&self, progress: &OperationProgress) {
+        self.events.lock().expect("recorder poisoned").push(progress.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updates_within_a_phase_are_throttled() {
+        let recorder = Recorder::new();
+        let progress = Progress::new("op", Arc::new(recorder.clone())).with_interval(Duration::from_secs(3600));
+        progress.phase("Downloading", None);
+        for _ in 0..100 {
+            progress.advance(1, 10);
+        }
+        progress.phase("Decoding", Some(5));
+        progress.set(5, 0);
+        progress.finish();
+        progress.advance(1, 1);
+
+        let events = recorder.events_for("op");
+        assert_eq!(events.len(), 3);
+        assert_eq!(recorder.phases("op"), vec!["Downloading", "Decoding"]);
+        let last = events.last().unwrap();
+        assert!(last.finished);
+        assert_eq!((last.items_done, last.total_items, last.bytes), (5, Some(5), 0));
+    }
+
+    #[test]
+    fn test_every_update_is_sent_without_throttling() {
+        let recorder = Recorder::new();
+        let progress = Progress::new("op", Arc::new(recorder.clone())).with_interval(Duration::ZERO);
+        progress.phase("Streaming tree", None);
+        progress.advance(2, 100);
+        progress.advance(3, 50);
+        let bytes: Vec<u64> = recorder.events().iter().map(|p| p.bytes).collect();
+        assert_eq!(bytes, vec![0, 100, 150]);
+        assert_eq!(recorder.events().last().unwrap().items_done, 5);
+    }
+
+    #[tokio::test]
+    async fn test_current_reports_to_the_scoped_operation() {
+        let recorder = Recorder::new();
+        current().phase("Ignored", None);
+        let progress = Progress::new("scoped", Arc::new(recorder.clone()));
+        scope(progress.clone(), async { current().phase("Loading tree", None) }).await;
+        progress.finish();
+        assert_eq!(recorder.phases("scoped"), vec!["Loading tree"]);
+        assert!(Progress::disabled().inner.is_none());
+    }
+}
@@ -0,0 +1,80 @@
+//! Per-project settings read from `irmin-view.toml`.
+//!
+//! ```toml
+//! [[schemas]]
+//! pattern = "users/*.json"
+//! schema = "schemas/user.schema.json"
+//! ```
+//!
+//! Relative schema paths are resolved against the directory holding the
+//! config file. The file is looked up at `IRMIN_VIEW_CONFIG`, falling back to
+//! `irmin-view.toml` in the working directory; without one every setting
+//! keeps its default.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Name of the project config file in the working directory
+pub const PROJECT_CONFIG_FILE: &str = "irmin-view.toml";
+
+/// A JSON Schema applied to every key matching a glob pattern
+#[derive(Debug, Clone, Deserialize)]
+pub struct SchemaRule {
+    /// Glob over store paths, see `tree::glob_match`
+    pub pattern: String,
+    pub schema: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub schemas: Vec<SchemaRule>,
+}
+
+impl ProjectConfig {
+    /// Load the project config of the working directory, if there is one
+    pub fn load() -> Result<Self> {
+        match env::var("IRMIN_VIEW_CONFIG").ok().filter(|p| !p.is_empty()) {
+            Some(path) => Self::load_from(Path::new(&path)),
+            None if Path::new(PROJECT_CONFIG_FILE).exists() => Self::load_from(Path::new(PROJECT_CONFIG_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut config: ProjectConfig =
+            toml::from_str(&text).with_context(|| format!("Invalid project config {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        for rule in &mut config.schemas {
+            if rule.schema.is_relative() {
+                rule.schema = base.join(&rule.schema);
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_paths_are_relative_to_config() {
+        let dir = std::env::temp_dir().join(format!("irmin-project-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[[schemas]]\npattern = \"users/*.json\"\nschema = \"schemas/user.schema.json\"\n\n[[schemas]]\npattern = \"*.json\"\nschema = \"/etc/root.schema.json\"\n",
+        )
+        .unwrap();
+
+        let config = ProjectConfig::load_from(&path).unwrap();
+        assert_eq!(config.schemas[0].schema, dir.join("schemas/user.schema.json"));
+        assert_eq!(config.schemas[1].schema, PathBuf::from("/etc/root.schema.json"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
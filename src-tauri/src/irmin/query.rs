@@ -0,0 +1,507 @@
+//! JSONPath and jq-style queries over JSON values.
+//!
+//! Two syntaxes compile to the same query:
+//!
+//! ```text
+//! $.role   $.users[*].email   $..id   $['display name']   $.items[-1]   $.items[0:2]
+//! .role    .users[].email     .. | .id?   .items[-1] | keys   .tags | length
+//! ```
+//!
+//! JSONPath skips missing fields and spreads slices, jq yields `null` for
+//! missing fields and an array for slices, like `jq` does.
+//! jq pipelines additionally support the `keys`, `length` and `type` builtins.
+
+use super::{tree, types::*};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Default number of keys `query_values` evaluates before stopping
+pub const DEFAULT_MAX_KEYS: usize = 5_000;
+
+/// Syntax error in a query expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryError {
+    pub message: String,
+    /// Character offset of the error in the expression
+    pub position: usize,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self { message: message.into(), position }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    /// `..name` in JSONPath
+    DescendantField(String),
+    /// `..*` in JSONPath, `..` in jq: the value and everything below it
+    Descendants,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stage {
+    Path(Vec<Step>),
+    Keys,
+    Length,
+    Type,
+}
+
+/// Compiled query expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    stages: Vec<Stage>,
+    /// jq semantics: missing fields yield `null` and slices yield arrays
+    jq: bool,
+}
+
+impl Query {
+    /// Parse a JSONPath (`$...`) or jq (`.…`) expression
+    pub fn parse(expression: &str) -> Result<Query, QueryError> {
+        let chars: Vec<char> = expression.chars().collect();
+        let mut parser = Parser { chars, pos: 0 };
+        parser.skip_whitespace();
+        match parser.peek() {
+            Some('$') => {
+                parser.pos += 1;
+                let steps = parser.parse_steps(false)?;
+                parser.expect_end()?;
+                Ok(Query { stages: vec![Stage::Path(steps)], jq: false })
+            }
+            Some(_) => {
+                let stages = parser.parse_pipeline()?;
+                Ok(Query { stages, jq: true })
+            }
+            None => Err(QueryError::new("empty expression, expected e.g. $.role or .role", 0)),
+        }
+    }
+
+    /// Evaluate the query against one JSON document
+    pub fn evaluate(&self, input: &Value) -> Result<Vec<Value>, String> {
+        let mut values = vec![input.clone()];
+        for stage in &self.stages {
+            values = match stage {
+                Stage::Path(steps) => steps.iter().fold(values, |current, step| {
+                    current
+                        .iter()
+                        .flat_map(|v| apply_step(step, v, self.jq))
+                        .collect()
+                }),
+                Stage::Keys => values.iter().map(keys).collect::<Result<_, _>>()?,
+                Stage::Length => values.iter().map(length).collect::<Result<_, _>>()?,
+                Stage::Type => values.iter().map(|v| Value::String(type_name(v).to_string())).collect(),
+            };
+        }
+        Ok(values)
+    }
+}
+
+fn apply_step(step: &Step, value: &Value, jq: bool) -> Vec<Value> {
+    match step {
+        Step::Field(name) => match value.get(name.as_str()) {
+            Some(v) => vec![v.clone()],
+            None if jq && (value.is_object() || value.is_null()) => vec![Value::Null],
+            None => Vec::new(),
+        },
+        Step::Index(index) => match value.as_array() {
+            Some(items) => resolve_index(*index, items.len())
+                .map(|i| vec![items[i].clone()])
+                .unwrap_or_else(|| if jq { vec![Value::Null] } else { Vec::new() }),
+            None => Vec::new(),
+        },
+        Step::Slice(start, end) => match value.as_array() {
+            Some(items) => {
+                let len = items.len() as i64;
+                let clamp = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) } as usize;
+                let start = start.map_or(0, clamp);
+                let end = end.map_or(items.len(), clamp);
+                let slice = if start < end { items[start..end].to_vec() } else { Vec::new() };
+                if jq {
+                    vec![Value::Array(slice)]
+                } else {
+                    slice
+                }
+            }
+            None => Vec::new(),
+        },
+        Step::Wildcard => children(value),
+        Step::DescendantField(name) => {
+            let mut out = Vec::new();
+            for v in descendants(value) {
+                if let Some(found) = v.get(name.as_str()) {
+                    if v.is_object() {
+                        out.push(found.clone());
+                    }
+                }
+            }
+            out
+        }
+        Step::Descendants => descendants(value).into_iter().cloned().collect(),
+    }
+}
+
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let resolved = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&resolved).then_some(resolved as usize)
+}
+
+fn children(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.clone(),
+        Value::Object(map) => map.values().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The value itself followed by every value nested in it, breadth first
+fn descendants(value: &Value) -> Vec<&Value> {
+    let mut out = vec![value];
+    let mut i = 0;
+    while i < out.len() {
+        let current = out[i];
+        let nested: Vec<&Value> = match current {
+            Value::Array(items) => items.iter().collect(),
+            Value::Object(map) => map.values().collect(),
+            _ => Vec::new(),
+        };
+        out.extend(nested);
+        i += 1;
+    }
+    out
+}
+
+fn keys(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            Ok(Value::Array(keys.into_iter().map(|k| Value::String(k.clone())).collect()))
+        }
+        Value::Array(items) => Ok(Value::Array((0..items.len()).map(|i| Value::from(i as u64)).collect())),
+        other => Err(format!("{} has no keys", type_name(other))),
+    }
+}
+
+fn length(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Null => Ok(Value::from(0)),
+        Value::String(s) => Ok(Value::from(s.chars().count() as u64)),
+        Value::Array(items) => Ok(Value::from(items.len() as u64)),
+        Value::Object(map) => Ok(Value::from(map.len() as u64)),
+        Value::Number(n) => Ok(Value::from(n.as_f64().map_or(0.0, f64::abs))),
+        Value::Bool(_) => Err("boolean has no length".to_string()),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), QueryError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(c) => Err(QueryError::new(format!("unexpected '{}'", c), self.pos)),
+        }
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Vec<Stage>, QueryError> {
+        let mut stages = Vec::new();
+        loop {
+            self.skip_whitespace();
+            stages.push(self.parse_stage()?);
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Ok(stages),
+                Some('|') => self.pos += 1,
+                Some(c) => return Err(QueryError::new(format!("unexpected '{}', expected '|' or end", c), self.pos)),
+            }
+        }
+    }
+
+    fn parse_stage(&mut self) -> Result<Stage, QueryError> {
+        let start = self.pos;
+        match self.peek() {
+            Some('.') => Ok(Stage::Path(self.parse_steps(true)?)),
+            Some(c) if c.is_alphabetic() => {
+                let name = self.parse_identifier();
+                match name.as_str() {
+                    "keys" => Ok(Stage::Keys),
+                    "length" => Ok(Stage::Length),
+                    "type" => Ok(Stage::Type),
+                    _ => Err(QueryError::new(
+                        format!("unknown function '{}', supported: keys, length, type", name),
+                        start,
+                    )),
+                }
+            }
+            Some(c) => Err(QueryError::new(format!("unexpected '{}', expected a path such as .role", c), start)),
+            None => Err(QueryError::new("unexpected end of expression after '|'", start)),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Parse path steps; in jq mode a lone `.` is the identity path
+    fn parse_steps(&mut self, jq: bool) -> Result<Vec<Step>, QueryError> {
+        let mut steps = Vec::new();
+        loop {
+            match self.peek() {
+                Some('.') if self.peek_at(1) == Some('.') => {
+                    self.pos += 2;
+                    match self.peek() {
+                        Some('*') => {
+                            self.pos += 1;
+                            steps.push(Step::Descendants);
+                        }
+                        Some(c) if !jq && (c.is_alphanumeric() || c == '_') => {
+                            steps.push(Step::DescendantField(self.parse_identifier()));
+                        }
+                        Some('[') if !jq => {
+                            steps.push(Step::Descendants);
+                        }
+                        _ if jq => steps.push(Step::Descendants),
+                        _ => return Err(QueryError::new("expected a field name or '*' after '..'", self.pos)),
+                    }
+                }
+                Some('.') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('*') if !jq => {
+                            self.pos += 1;
+                            steps.push(Step::Wildcard);
+                        }
+                        Some('[') if jq => {}
+                        Some('"') => steps.push(Step::Field(self.parse_quoted()?)),
+                        Some(c) if c.is_alphanumeric() || c == '_' => {
+                            steps.push(Step::Field(self.parse_identifier()));
+                        }
+                        _ if jq && steps.is_empty() => {}
+                        _ => return Err(QueryError::new("expected a field name after '.'", self.pos)),
+                    }
+                }
+                Some('[') => steps.push(self.parse_bracket(jq)?),
+                Some('?') if jq => self.pos += 1,
+                _ => return Ok(steps),
+            }
+        }
+    }
+
+    fn parse_bracket(&mut self, jq: bool) -> Result<Step, QueryError> {
+        let open = self.pos;
+        self.pos += 1;
+        self.skip_whitespace();
+        let step = match self.peek() {
+            Some(']') if jq => Step::Wildcard,
+            Some('*') => {
+                self.pos += 1;
+                Step::Wildcard
+            }
+            Some('\'') | Some('"') => Step::Field(self.parse_quoted()?),
+            Some(c) if c == '-' || c == ':' || c.is_ascii_digit() => {
+                let start = self.parse_integer()?;
+                self.skip_whitespace();
+                if self.peek() == Some(':') {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let end = if self.peek() == Some(']') { None } else { self.parse_integer()? };
+                    Step::Slice(start, end)
+                } else {
+                    Step::Index(start.ok_or_else(|| QueryError::new("expected an index", self.pos))?)
+                }
+            }
+            _ => return Err(QueryError::new("expected an index, '*' or a quoted field name", self.pos)),
+        };
+        self.skip_whitespace();
+        if self.peek() != Some(']') {
+            return Err(QueryError::new("unclosed '['", open));
+        }
+        self.pos += 1;
+        Ok(step)
+    }
+
+    /// Parse an optional (possibly negative) integer
+    fn parse_integer(&mut self) -> Result<Option<i64>, QueryError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        text.parse()
+            .map(Some)
+            .map_err(|_| QueryError::new(format!("invalid index '{}'", text), start))
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        let quote = self.chars[self.pos];
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(QueryError::new("unterminated string", start)),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') if self.peek_at(1).is_some() => {
+                    text.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Run `query` against every file below `root` whose path matches `path_glob`,
+/// evaluating at most `max_keys` values
+pub fn query_tree(root: &IrminNode, path_glob: &str, query: &Query, max_keys: usize) -> QueryTable {
+    let mut rows = Vec::new();
+    let mut visited = 0;
+    let mut truncated = false;
+
+    tree::walk(root, &mut |path, _depth, node| {
+        if truncated || node.node_type == NodeType::Directory || !tree::glob_match(path_glob, path) {
+            return;
+        }
+        if visited >= max_keys {
+            truncated = true;
+            return;
+        }
+        visited += 1;
+
+        let row = match node.value.as_deref().map(serde_json::from_str::<Value>) {
+            None => QueryRow { path: path.to_string(), values: Vec::new(), error: Some("no value".to_string()) },
+            Some(Err(e)) => QueryRow {
+                path: path.to_string(),
+                values: Vec::new(),
+                error: Some(format!("not valid JSON: {}", e)),
+            },
+            Some(Ok(document)) => match query.evaluate(&document) {
+                Ok(values) => QueryRow { path: path.to_string(), values, error: None },
+                Err(e) => QueryRow { path: path.to_string(), values: Vec::new(), error: Some(e) },
+            },
+        };
+        rows.push(row);
+    });
+
+    QueryTable { rows, keys_visited: visited, truncated }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(expression: &str, input: &Value) -> Vec<Value> {
+        Query::parse(expression).unwrap().evaluate(input).unwrap()
+    }
+
+    #[test]
+    fn test_jsonpath() {
+        let doc = json!({"role": "admin", "users": [{"id": 1, "email": "a@x"}, {"id": 2}], "meta": {"id": 9}});
+        assert_eq!(eval("$.role", &doc), vec![json!("admin")]);
+        assert_eq!(eval("$.users[*].email", &doc), vec![json!("a@x")]);
+        assert_eq!(eval("$['users'][-1].id", &doc), vec![json!(2)]);
+        assert_eq!(eval("$.users[0:1]", &doc), vec![json!({"id": 1, "email": "a@x"})]);
+        assert_eq!(eval("$.missing", &doc), Vec::<Value>::new());
+
+        let mut ids = eval("$..id", &doc);
+        ids.sort_by_key(|v| v.as_i64());
+        assert_eq!(ids, vec![json!(1), json!(2), json!(9)]);
+    }
+
+    #[test]
+    fn test_jq_subset() {
+        let doc = json!({"role": "user", "tags": ["a", "b"], "profile": {"name": "Bob", "age": 3}});
+        assert_eq!(eval(".role", &doc), vec![json!("user")]);
+        assert_eq!(eval(".", &doc), vec![doc.clone()]);
+        assert_eq!(eval(".tags[]", &doc), vec![json!("a"), json!("b")]);
+        assert_eq!(eval(".tags | length", &doc), vec![json!(2)]);
+        assert_eq!(eval(".tags[1:]", &doc), vec![json!(["b"])]);
+        assert_eq!(eval(".profile | keys", &doc), vec![json!(["age", "name"])]);
+        assert_eq!(eval(".missing", &doc), vec![Value::Null]);
+        assert_eq!(eval(".profile.name | type", &doc), vec![json!("string")]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("").is_err());
+        assert_eq!(Query::parse("$.users[0").unwrap_err().message, "unclosed '['");
+        assert!(Query::parse(".role | frobnicate").unwrap_err().message.contains("unknown function"));
+        assert!(Query::parse("$.").is_err());
+    }
+
+    #[test]
+    fn test_query_tree_caps_visited_keys() {
+        let tree = super::super::mock_data::generate_mock_tree();
+        let query = Query::parse("$.role").unwrap();
+
+        let table = query_tree(&tree, "users/*.json", &query, 10);
+        assert_eq!(table.keys_visited, 2);
+        assert!(!table.truncated);
+        assert_eq!(table.rows[0].path, "/users/alice.json");
+        assert_eq!(table.rows[0].values, vec![json!("admin")]);
+
+        let table = query_tree(&tree, "**", &query, 1);
+        assert_eq!((table.rows.len(), table.keys_visited), (1, 1));
+        assert!(table.truncated);
+    }
+}
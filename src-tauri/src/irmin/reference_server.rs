@@ -0,0 +1,524 @@
+//! In-process implementation of the bridge HTTP API (`ocaml-bridge/server`)
+//! for testing `IrminHttpClient` and the HTTP paths of `integration` without
+//! OCaml.
+//!
+//! It answers `/health` and `/api/{tree,commits,branches,search,diff,commit}`,
+//! plus the NDJSON `/api/tree/stream`, with the same routes, query parameters, status codes, error bodies and
+//! CORS headers as `server.ml`, serving a [`FixtureStore`]. Commits are
+//! applied in memory, so later reads see them.
+//!
+//! Bodies have the bridge's shapes rather than what the Rust types
+//! serialize to: commit timestamps are epoch seconds as strings, dates have
+//! no fractional seconds, the root node is keyed `root`, tree nodes carry
+//! their hash, file sizes are value lengths and search results carry no
+//! children, hashes or matched indices.
+//!
+//! Faults can be injected per endpoint (or for every endpoint with `"*"`)
+//! to exercise timeouts, server errors and responses that do not parse:
+//!
+//! ```ignore
+//! let server = ReferenceServer::from_fixtures("sample")?;
+//! server.inject("/api/commits", Fault::Error(503, "store locked".into()));
+//! server.inject_once("*", Fault::Latency(Duration::from_secs(2)));
+//! let running = server.start().await?;
+//! let client = IrminHttpClient::new(IrminHttpConfig::new().with_url(running.url.clone()));
+//! ```
+
+use super::fixtures::FixtureStore;
+use super::tree;
+use super::types::*;
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Misbehaviour injected into the responses of an endpoint
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Delay the response; combines with the other faults
+    Latency(Duration),
+    /// Answer with this status and `{"error": message}`
+    Error(u16, String),
+    /// Answer 200 with a body that does not decode as the expected type
+    Malformed(Malformed),
+}
+
+/// Ways a successful response body can be broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// The first half of the real body
+    Truncated,
+    /// Valid JSON of the wrong shape
+    WrongShape,
+    /// An HTML error page from a proxy in between
+    Html,
+    /// No body at all
+    Empty,
+}
+
+struct FaultRule {
+    endpoint: String,
+    fault: Fault,
+    /// Responses left to affect, `None` for all of them
+    remaining: Option<usize>,
+}
+
+/// Body of `POST /api/commit`; everything but the changes has a default
+#[derive(Deserialize)]
+struct CommitBody {
+    #[serde(default = "default_branch")]
+    branch: String,
+    #[serde(default = "default_message")]
+    message: String,
+    #[serde(default = "default_author")]
+    author: String,
+    #[serde(default)]
+    changes: Vec<DiffChange>,
+}
+
+fn default_branch() -> String {
+    "main".to_string()
+}
+
+fn default_message() -> String {
+    "Commit from IrminView".to_string()
+}
+
+fn default_author() -> String {
+    "IrminView".to_string()
+}
+
+/// Reference bridge server; cheap to clone, clones share state
+#[derive(Clone)]
+pub struct ReferenceServer {
+    store: Arc<RwLock<Arc<FixtureStore>>>,
+    faults: Arc<Mutex<Vec<FaultRule>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+/// A reference server listening on a local port, stopped on drop
+pub struct RunningServer {
+    /// Base URL to configure clients with, e.g. `http://127.0.0.1:40123`
+    pub url: String,
+    pub server: ReferenceServer,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl ReferenceServer {
+    pub fn new(store: FixtureStore) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(Arc::new(store))),
+            faults: Arc::new(Mutex::new(Vec::new())),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Serve a fixture scenario, see [`FixtureStore::load`]
+    pub fn from_fixtures(spec: &str) -> Result<Self> {
+        Ok(Self::new(FixtureStore::load(spec)?.as_ref().clone()))
+    }
+
+    /// The store as it is now, including commits made through the server
+    pub fn store(&self) -> Arc<FixtureStore> {
+        self.store.read().expect("store lock poisoned").clone()
+    }
+
+    /// Apply `fault` to every response of `endpoint` (a path such as
+    /// `/api/tree`, or `*`) until the faults are cleared
+    pub fn inject(&self, endpoint: &str, fault: Fault) {
+        self.add_rule(endpoint, fault, None);
+    }
+
+    /// Apply `fault` to the next response of `endpoint` only
+    pub fn inject_once(&self, endpoint: &str, fault: Fault) {
+        self.add_rule(endpoint, fault, Some(1));
+    }
+
+    pub fn clear_faults(&self) {
+        self.faults.lock().expect("fault lock poisoned").clear();
+    }
+
+    /// Requests received so far, as `METHOD /path?query`
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().expect("request lock poisoned").clone()
+    }
+
+    fn add_rule(&self, endpoint: &str, fault: Fault, remaining: Option<usize>) {
+        self.faults.lock().expect("fault lock poisoned").push(FaultRule {
+            endpoint: endpoint.to_string(),
+            fault,
+            remaining,
+        });
+    }
+
+    /// Faults for one response of `path`, using up one-shot rules
+    fn take_faults(&self, path: &str) -> Vec<Fault> {
+        let mut rules = self.faults.lock().expect("fault lock poisoned");
+        let mut faults = Vec::new();
+        for rule in rules.iter_mut().filter(|r| r.endpoint == "*" || r.endpoint == path) {
+            faults.push(rule.fault.clone());
+            if let Some(remaining) = &mut rule.remaining {
+                *remaining -= 1;
+            }
+        }
+        rules.retain(|r| r.remaining != Some(0));
+        faults
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new().fallback(handle).with_state(self.clone())
+    }
+
+    /// Listen on an ephemeral local port
+    pub async fn start(&self) -> Result<RunningServer> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let router = self.router();
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        Ok(RunningServer {
+            url,
+            server: self.clone(),
+            task,
+        })
+    }
+
+    /// Answer a request the way `server.ml` does, before faults
+    fn respond(&self, method: &Method, path: &str, query: &HashMap<String, String>, body: &[u8]) -> Reply {
+        let store = self.store();
+        let result = match (method, path) {
+            (&Method::GET, "/health") => Ok(json!({ "status": "healthy", "service": "irmin-bridge-server" })),
+            (&Method::GET, "/api/tree") => match query.get("commit") {
+                Some(commit) => store.tree_at(commit).map(|tree| tree_json(&tree)),
+                None => store.tree().map(|tree| tree_json(&tree)),
+            },
+            (&Method::GET, "/api/tree/stream") => {
+                let tree = match query.get("commit") {
+                    Some(commit) => store.tree_at(commit),
+                    None => store.tree(),
+                };
+                return match tree {
+                    Ok(tree) => Reply::Ndjson(entries_ndjson(&tree)),
+                    Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+                };
+            }
+            (&Method::GET, "/api/commits") => Ok(store.commits().iter().map(commit_json).collect()),
+            (&Method::GET, "/api/branches") => Ok(store.branches().iter().map(branch_json).collect()),
+            (&Method::GET, "/api/search") => match query.get("q") {
+                Some(q) => store.search(q).map(|results| results.iter().map(search_result_json).collect()),
+                None => return error(StatusCode::BAD_REQUEST, "Missing query parameter 'q'"),
+            },
+            (&Method::GET, "/api/diff") => match (query.get("from"), query.get("to")) {
+                (Some(from), Some(to)) => store.diff(from, to).map(|diff| json!(diff)),
+                _ => return error(StatusCode::BAD_REQUEST, "Missing 'from' or 'to' query parameters"),
+            },
+            (&Method::POST, "/api/commit") => {
+                let body: CommitBody = match serde_json::from_slice(body) {
+                    Ok(body) => body,
+                    Err(e) => return error(StatusCode::BAD_REQUEST, &format!("Invalid JSON body: {}", e)),
+                };
+                let request = CommitRequest {
+                    branch: body.branch,
+                    message: body.message,
+                    author: body.author,
+                    changes: body.changes,
+                };
+                self.commit(&request).map(|hash| json!(CommitResult { hash }))
+            }
+            _ => return error(StatusCode::NOT_FOUND, "Endpoint not found"),
+        };
+        match result {
+            Ok(value) => Reply::Json(StatusCode::OK, value),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        }
+    }
+
+    fn commit(&self, request: &CommitRequest) -> Result<String> {
+        let mut store = self.store.write().expect("store lock poisoned");
+        let (next, hash) = store.committed(request, Utc::now())?;
+        *store = Arc::new(next);
+        Ok(hash)
+    }
+}
+
+/// Date as the bridge writes it, e.g. `2024-01-01T00:00:00Z`
+fn bridge_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Node type as the bridge writes it; it knows no links
+fn bridge_node_type(node_type: &NodeType) -> &'static str {
+    match node_type {
+        NodeType::Directory => "Directory",
+        NodeType::File | NodeType::Link => "File",
+    }
+}
+
+/// Size as the bridge reports it: the value length of files, none for
+/// directories
+fn bridge_size(node: &IrminNode) -> Option<usize> {
+    match node.node_type {
+        NodeType::Directory => None,
+        _ => node.value.as_ref().map(String::len),
+    }
+}
+
+/// The tree as `get_tree_json` of `irmin_bridge.ml` writes it, every node
+/// with its hash
+fn tree_json(root: &IrminNode) -> Value {
+    fn with_children(path: &str, key: &str, node: &IrminNode, hashes: &HashMap<String, String>) -> Value {
+        let mut json = node_json(key, node);
+        for (key, child) in &node.children {
+            json["children"][key] = with_children(&tree::join_path(path, key), key, child, hashes);
+        }
+        json["hash"] = json!(hashes.get(path));
+        json
+    }
+    with_children("/", "root", root, &tree::content_hashes(root))
+}
+
+/// `node_to_json` of `irmin_bridge.ml` without children or hash, as in
+/// search results
+fn node_json(key: &str, node: &IrminNode) -> Value {
+    let default_permissions = if node.node_type == NodeType::Directory { "755" } else { "644" };
+    json!({
+        "key": key,
+        "value": node.value,
+        "node_type": bridge_node_type(&node.node_type),
+        "children": {},
+        "metadata": {
+            "last_modified": bridge_date(&node.metadata.last_modified),
+            "size": bridge_size(node),
+            "permissions": node.metadata.permissions.as_deref().unwrap_or(default_permissions),
+        },
+    })
+}
+
+fn commit_json(commit: &IrminCommit) -> Value {
+    json!({
+        "hash": commit.hash,
+        "message": commit.message,
+        "author": commit.author,
+        "timestamp": commit.timestamp.timestamp().to_string(),
+        "parents": commit.parents,
+        "branch": commit.branch,
+    })
+}
+
+fn branch_json(branch: &IrminBranch) -> Value {
+    json!({
+        "name": branch.name,
+        "head_commit": branch.head_commit,
+        "last_updated": bridge_date(&branch.last_updated),
+        "commit_count": branch.commit_count,
+    })
+}
+
+/// Search results have paths without a leading slash and childless nodes
+fn search_result_json(result: &SearchResult) -> Value {
+    json!({
+        "path": result.path.trim_start_matches('/'),
+        "node": node_json(&result.node.key, &result.node),
+        "relevance_score": result.relevance_score,
+    })
+}
+
+/// `stream_tree_ndjson` of `irmin_bridge.ml`: a `{path, type, size, hash}`
+/// line per node, parents before children
+fn entries_ndjson(root: &IrminNode) -> String {
+    let hashes = tree::content_hashes(root);
+    let mut lines = String::new();
+    tree::walk(root, &mut |path, _, node| {
+        let entry = json!({
+            "path": path,
+            "type": bridge_node_type(&node.node_type),
+            "size": bridge_size(node),
+            "hash": hashes.get(path),
+        });
+        lines.push_str(&entry.to_string());
+        lines.push('\n');
+    });
+    lines
+}
+
+/// Response before faults are applied
+enum Reply {
+    Json(StatusCode, Value),
+    /// 200 with one JSON value per line
+    Ndjson(String),
+}
+
+impl Reply {
+    fn body(&self) -> String {
+        match self {
+            Reply::Json(_, value) => value.to_string(),
+            Reply::Ndjson(lines) => lines.clone(),
+        }
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        match self {
+            Reply::Json(status, value) => json_response(status, &value),
+            Reply::Ndjson(lines) => (StatusCode::OK, [(header::CONTENT_TYPE, "application/x-ndjson")], lines).into_response(),
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Reply {
+    Reply::Json(status, json!({ "error": message }))
+}
+
+fn with_cors(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET, POST, OPTIONS"));
+    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("Content-Type"));
+    response
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response {
+    let body = serde_json::to_string(body).unwrap_or_default();
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+async fn handle(State(server): State<ReferenceServer>, method: Method, uri: Uri, body: Bytes) -> Response {
+    let path = uri.path().to_string();
+    server.requests.lock().expect("request lock poisoned").push(format!(
+        "{} {}",
+        method,
+        uri.path_and_query().map(|p| p.as_str()).unwrap_or(&path)
+    ));
+    if method == Method::OPTIONS {
+        return with_cors(StatusCode::OK.into_response());
+    }
+
+    let query = Query::<HashMap<String, String>>::try_from_uri(&uri)
+        .map(|Query(query)| query)
+        .unwrap_or_default();
+    let reply = server.respond(&method, &path, &query, &body);
+
+    let mut response = None;
+    for fault in server.take_faults(&path) {
+        match fault {
+            Fault::Latency(delay) => tokio::time::sleep(delay).await,
+            Fault::Error(status, message) if response.is_none() => {
+                let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                response = Some(json_response(status, &json!({ "error": message })));
+            }
+            Fault::Malformed(kind) if response.is_none() => response = Some(malformed(kind, &reply)),
+            _ => {}
+        }
+    }
+    with_cors(response.unwrap_or_else(|| reply.into_response()))
+}
+
+fn malformed(kind: Malformed, reply: &Reply) -> Response {
+    let json = [(header::CONTENT_TYPE, "application/json")];
+    match kind {
+        Malformed::Truncated => {
+            let body = reply.body();
+            let mut end = body.len() / 2;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            (StatusCode::OK, json, body[..end].to_string()).into_response()
+        }
+        Malformed::WrongShape if matches!(reply, Reply::Json(_, value) if value.is_array()) => (StatusCode::OK, json, r#"{"unexpected":true}"#).into_response(),
+        Malformed::WrongShape => (StatusCode::OK, json, "[1,2,3]").into_response(),
+        Malformed::Html => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/html")],
+            "<html><body><h1>502 Bad Gateway</h1></body></html>",
+        )
+            .into_response(),
+        Malformed::Empty => (StatusCode::OK, json, "").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_matches_bridge_server_routes_and_errors() {
+        let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+        let client = reqwest::Client::new();
+
+        let health: Value = client.get(format!("{}/health", running.url)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(health["status"], "healthy");
+
+        let missing = client.get(format!("{}/api/search", running.url)).send().await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(missing.headers()["access-control-allow-origin"], "*");
+        let body: Value = missing.json().await.unwrap();
+        assert_eq!(body["error"], "Missing query parameter 'q'");
+
+        let unknown = client.get(format!("{}/api/nope", running.url)).send().await.unwrap();
+        assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let invalid = client
+            .post(format!("{}/api/commit", running.url))
+            .body("{not json")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(running.server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_commits_are_visible_to_later_reads() {
+        let server = ReferenceServer::from_fixtures("sample").unwrap();
+        let before = server.store().commits().len();
+        let request = CommitRequest {
+            branch: "feature/new".to_string(),
+            message: "Add notes".to_string(),
+            author: "Tester".to_string(),
+            changes: vec![DiffChange {
+                path: "/notes.txt".to_string(),
+                change_type: ChangeType::Added,
+                old_value: None,
+                new_value: Some("hello".to_string()),
+            }],
+        };
+        let hash = server.commit(&request).unwrap();
+
+        let store = server.store();
+        assert_eq!(store.commits().len(), before + 1);
+        let branch = store.branches().into_iter().find(|b| b.name == "feature/new").unwrap();
+        assert_eq!(branch.head_commit, hash);
+        assert_eq!(store.tree_at(&hash).unwrap().children["notes.txt"].value.as_deref(), Some("hello"));
+        assert!(store.tree_at(&hash).unwrap().children.len() == 1);
+    }
+
+    #[test]
+    fn test_one_shot_faults_are_used_up() {
+        let server = ReferenceServer::from_fixtures("sample").unwrap();
+        server.inject_once("/api/tree", Fault::Malformed(Malformed::Empty));
+        server.inject("*", Fault::Latency(Duration::from_millis(1)));
+        assert_eq!(server.take_faults("/api/tree").len(), 2);
+        assert_eq!(server.take_faults("/api/tree").len(), 1);
+        assert_eq!(server.take_faults("/api/commits").len(), 1);
+        server.clear_faults();
+        assert!(server.take_faults("/api/tree").is_empty());
+    }
+}
@@ -0,0 +1,896 @@
+//! Content-type detection and syntax rendering of stored values.
+//!
+//! The registry picks a renderer from the key's extension, falling back to
+//! sniffing the content in registration order. Binary values always get a
+//! hex dump and values nothing claims are shown as plain text. Renderers
+//! pretty-print what they can parse and otherwise return the value as is,
+//! tokenized on a best-effort basis, together with the parse errors.
+
+use super::{tree, types::*};
+
+/// Bytes per line of a hex dump
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// Indentation used when pretty-printing JSON
+const JSON_INDENT: &str = "  ";
+
+/// A renderer for one content type
+pub trait Renderer: Send + Sync {
+    fn content_type(&self) -> ContentType;
+
+    /// Lower-case extensions, without the dot, that select this renderer
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether a value without a known extension looks like this content
+    fn sniff(&self, value: &str) -> bool;
+
+    fn render(&self, value: &str) -> RenderedValue;
+}
+
+/// Renderers by content type, consulted in registration order
+pub struct RendererRegistry {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl Default for RendererRegistry {
+    /// Registry with the built-in JSON, XML, TOML, YAML, CSV and Markdown renderers
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(JsonRenderer));
+        registry.register(Box::new(XmlRenderer));
+        registry.register(Box::new(TomlRenderer));
+        registry.register(Box::new(YamlRenderer));
+        registry.register(Box::new(CsvRenderer));
+        registry.register(Box::new(MarkdownRenderer));
+        registry
+    }
+}
+
+impl RendererRegistry {
+    pub fn empty() -> Self {
+        Self { renderers: Vec::new() }
+    }
+
+    pub fn register(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderers.push(renderer);
+    }
+
+    /// Content type of the value stored under `key`, and whether the
+    /// extension decided it
+    pub fn detect(&self, key: &str, value: &str) -> (ContentType, bool) {
+        match self.select(key, value) {
+            Selection::Binary => (ContentType::Binary, false),
+            Selection::Renderer(renderer, from_extension) => (renderer.content_type(), from_extension),
+            Selection::Text => (ContentType::Text, false),
+        }
+    }
+
+    pub fn render(&self, key: &str, value: &str) -> RenderedValue {
+        match self.select(key, value) {
+            Selection::Binary => hex_dump(value),
+            Selection::Renderer(renderer, from_extension) => RenderedValue {
+                from_extension,
+                ..renderer.render(value)
+            },
+            Selection::Text => {
+                let mut tokens = Tokens::default();
+                tokens.push(TokenKind::Text, value);
+                tokens.finish(ContentType::Text, value.to_string(), Vec::new())
+            }
+        }
+    }
+
+    fn select(&self, key: &str, value: &str) -> Selection<'_> {
+        if is_binary(value) {
+            return Selection::Binary;
+        }
+        let name = key.rsplit('/').next().unwrap_or(key);
+        let extension = tree::extension(name);
+        if let Some(renderer) = self.renderers.iter().find(|r| r.extensions().contains(&extension.as_str())) {
+            return Selection::Renderer(renderer.as_ref(), true);
+        }
+        match self.renderers.iter().find(|r| r.sniff(value)) {
+            Some(renderer) => Selection::Renderer(renderer.as_ref(), false),
+            None => Selection::Text,
+        }
+    }
+}
+
+enum Selection<'a> {
+    Binary,
+    Renderer(&'a dyn Renderer, bool),
+    Text,
+}
+
+/// Values that cannot be shown as text
+pub fn is_binary(value: &str) -> bool {
+    value.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+}
+
+/// Token list that merges adjacent runs of the same kind
+#[derive(Default)]
+struct Tokens(Vec<SyntaxToken>);
+
+impl Tokens {
+    fn push(&mut self, kind: TokenKind, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(text),
+            _ => self.0.push(SyntaxToken {
+                kind,
+                text: text.to_string(),
+            }),
+        }
+    }
+
+    fn finish(self, content_type: ContentType, text: String, errors: Vec<RenderError>) -> RenderedValue {
+        RenderedValue {
+            content_type,
+            from_extension: false,
+            text,
+            tokens: self.0,
+            errors,
+        }
+    }
+}
+
+/// 1-based line and column (in characters) of a byte offset
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..floor_char_boundary(text, offset)];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn error_at(text: &str, offset: usize, message: impl ToString) -> RenderError {
+    let (line, column) = line_column(text, offset);
+    RenderError {
+        line,
+        column,
+        message: message.to_string(),
+    }
+}
+
+/// End (exclusive) of the quoted string starting at `start`; backslash
+/// escapes apply inside double quotes only
+fn quoted_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if quote == b'"' && matches!(bytes.get(i + 1), Some(b'"' | b'\\')) => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Position of the first byte matching `pred` outside quotes
+fn find_unquoted(text: &str, pred: impl Fn(u8, Option<u8>) -> bool) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => i = quoted_end(text, i),
+            b if pred(b, bytes.get(i + 1).copied()) => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Leading whitespace, the line body and its line break
+fn split_line(line: &str) -> (&str, &str, &str) {
+    let body_end = line.trim_end_matches(['\n', '\r']).len();
+    let (body, newline) = line.split_at(body_end);
+    let indent = body.len() - body.trim_start().len();
+    let (indent, body) = body.split_at(indent);
+    (indent, body, newline)
+}
+
+/// Scalars, strings, flow punctuation and comments of TOML and YAML values
+fn lex_value(text: &str, tokens: &mut Tokens) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            b'"' | b'\'' => {
+                i = quoted_end(text, i);
+                TokenKind::String
+            }
+            b'#' if i == 0 || bytes[i - 1].is_ascii_whitespace() => {
+                i = bytes.len();
+                TokenKind::Comment
+            }
+            b'[' | b']' | b'{' | b'}' | b',' => {
+                i += 1;
+                TokenKind::Punctuation
+            }
+            b if b.is_ascii_whitespace() => {
+                while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                TokenKind::Whitespace
+            }
+            _ => {
+                while i < bytes.len() && !matches!(bytes[i], b' ' | b'\t' | b'[' | b']' | b'{' | b'}' | b',') {
+                    i += 1;
+                }
+                scalar_kind(&text[start..i])
+            }
+        };
+        tokens.push(kind, &text[start..i]);
+    }
+}
+
+fn scalar_kind(word: &str) -> TokenKind {
+    match word {
+        "true" | "false" | "True" | "False" => TokenKind::Boolean,
+        "null" | "~" | "Null" => TokenKind::Null,
+        _ if word.replace('_', "").parse::<f64>().is_ok() => TokenKind::Number,
+        _ => TokenKind::String,
+    }
+}
+
+// JSON
+
+struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Json
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json", "geojson", "jsonc"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        let trimmed = value.trim_start();
+        (trimmed.starts_with('{') || trimmed.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(value).is_ok()
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let raw = lex_json(value);
+        match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(_) => {
+                let tokens = pretty_json(&raw);
+                let text = tokens.0.iter().map(|t| t.text.as_str()).collect();
+                tokens.finish(ContentType::Json, text, Vec::new())
+            }
+            Err(e) => {
+                let mut tokens = Tokens::default();
+                for (kind, text) in raw {
+                    tokens.push(kind, text);
+                }
+                let error = RenderError {
+                    line: e.line().max(1),
+                    column: e.column().max(1),
+                    message: e.to_string(),
+                };
+                tokens.finish(ContentType::Json, value.to_string(), vec![error])
+            }
+        }
+    }
+}
+
+fn lex_json(text: &str) -> Vec<(TokenKind, &str)> {
+    let bytes = text.as_bytes();
+    let mut raw: Vec<(TokenKind, &str)> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            b'"' => {
+                i = quoted_end(text, i);
+                TokenKind::String
+            }
+            b'{' | b'}' | b'[' | b']' | b',' | b':' => {
+                i += 1;
+                TokenKind::Punctuation
+            }
+            b if b.is_ascii_whitespace() => {
+                while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                TokenKind::Whitespace
+            }
+            _ => {
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !matches!(bytes[i], b'"' | b'{' | b'}' | b'[' | b']' | b',' | b':')
+                {
+                    i += 1;
+                }
+                match &text[start..i] {
+                    "true" | "false" => TokenKind::Boolean,
+                    "null" => TokenKind::Null,
+                    word if word.parse::<f64>().is_ok() => TokenKind::Number,
+                    _ => TokenKind::Text,
+                }
+            }
+        };
+        raw.push((kind, &text[start..i]));
+    }
+
+    // Strings followed by a colon are object keys
+    for idx in 0..raw.len() {
+        if raw[idx].0 == TokenKind::String {
+            let next = raw[idx + 1..].iter().find(|(kind, _)| *kind != TokenKind::Whitespace);
+            if matches!(next, Some((TokenKind::Punctuation, ":"))) {
+                raw[idx].0 = TokenKind::Key;
+            }
+        }
+    }
+    raw
+}
+
+/// Re-indent valid JSON, keeping key order and number spelling
+fn pretty_json(raw: &[(TokenKind, &str)]) -> Tokens {
+    let significant: Vec<&(TokenKind, &str)> = raw.iter().filter(|(kind, _)| *kind != TokenKind::Whitespace).collect();
+    let mut tokens = Tokens::default();
+    let mut depth = 0;
+    let newline = |tokens: &mut Tokens, depth: usize| tokens.push(TokenKind::Whitespace, &format!("\n{}", JSON_INDENT.repeat(depth)));
+    for (idx, (kind, text)) in significant.iter().enumerate() {
+        match *text {
+            "{" | "[" => {
+                tokens.push(*kind, text);
+                let empty = matches!(significant.get(idx + 1), Some((_, "}" | "]")));
+                if !empty {
+                    depth += 1;
+                    newline(&mut tokens, depth);
+                }
+            }
+            "}" | "]" => {
+                let empty = idx > 0 && matches!(significant[idx - 1], (_, "{" | "["));
+                if !empty {
+                    depth = depth.saturating_sub(1);
+                    newline(&mut tokens, depth);
+                }
+                tokens.push(*kind, text);
+            }
+            "," if *kind == TokenKind::Punctuation => {
+                tokens.push(*kind, text);
+                newline(&mut tokens, depth);
+            }
+            ":" if *kind == TokenKind::Punctuation => {
+                tokens.push(*kind, text);
+                tokens.push(TokenKind::Whitespace, " ");
+            }
+            _ => tokens.push(*kind, text),
+        }
+    }
+    tokens
+}
+
+// TOML
+
+struct TomlRenderer;
+
+impl Renderer for TomlRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Toml
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["toml"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        let looks_like_toml = value.lines().map(str::trim).any(|line| {
+            (line.starts_with('[') && line.ends_with(']')) || find_unquoted(line, |b, _| b == b'=').is_some()
+        });
+        looks_like_toml && value.parse::<toml::Table>().is_ok()
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let mut tokens = Tokens::default();
+        for line in value.split_inclusive('\n') {
+            let (indent, body, newline) = split_line(line);
+            tokens.push(TokenKind::Whitespace, indent);
+            if body.starts_with('#') {
+                tokens.push(TokenKind::Comment, body);
+            } else if body.starts_with('[') {
+                let end = body.rfind(']').map(|i| i + 1).unwrap_or(body.len());
+                tokens.push(TokenKind::Tag, &body[..end]);
+                lex_value(&body[end..], &mut tokens);
+            } else if let Some(eq) = find_unquoted(body, |b, _| b == b'=') {
+                let key = body[..eq].trim_end();
+                tokens.push(TokenKind::Key, key);
+                tokens.push(TokenKind::Whitespace, &body[key.len()..eq]);
+                tokens.push(TokenKind::Punctuation, "=");
+                lex_value(&body[eq + 1..], &mut tokens);
+            } else {
+                lex_value(body, &mut tokens);
+            }
+            tokens.push(TokenKind::Whitespace, newline);
+        }
+        let errors = match value.parse::<toml::Table>() {
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                let offset = e.span().map(|s| s.start).unwrap_or_default();
+                vec![error_at(value, offset, e.message())]
+            }
+        };
+        tokens.finish(ContentType::Toml, value.to_string(), errors)
+    }
+}
+
+// YAML
+
+struct YamlRenderer;
+
+impl Renderer for YamlRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Yaml
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["yaml", "yml"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        if value.starts_with("---") {
+            return serde_yaml::from_str::<serde_yaml::Value>(value).is_ok();
+        }
+        matches!(
+            serde_yaml::from_str::<serde_yaml::Value>(value),
+            Ok(serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_))
+        )
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let mut tokens = Tokens::default();
+        for line in value.split_inclusive('\n') {
+            let (indent, mut body, newline) = split_line(line);
+            tokens.push(TokenKind::Whitespace, indent);
+            if body.starts_with('#') {
+                tokens.push(TokenKind::Comment, body);
+                body = "";
+            } else if body == "---" || body == "..." {
+                tokens.push(TokenKind::Punctuation, body);
+                body = "";
+            }
+            while body == "-" || body.starts_with("- ") {
+                tokens.push(TokenKind::Punctuation, "-");
+                let rest = &body[1..];
+                let trimmed = rest.trim_start();
+                tokens.push(TokenKind::Whitespace, &rest[..rest.len() - trimmed.len()]);
+                body = trimmed;
+            }
+            let colon = find_unquoted(body, |b, next| b == b':' && matches!(next, None | Some(b' ' | b'\t')));
+            let comment = find_unquoted(body, |b, _| b == b'#');
+            match colon {
+                Some(colon) if comment.is_none_or(|c| colon < c) => {
+                    tokens.push(TokenKind::Key, &body[..colon]);
+                    tokens.push(TokenKind::Punctuation, ":");
+                    lex_value(&body[colon + 1..], &mut tokens);
+                }
+                _ => lex_value(body, &mut tokens),
+            }
+            tokens.push(TokenKind::Whitespace, newline);
+        }
+        let errors = match serde_yaml::from_str::<serde_yaml::Value>(value) {
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                let (line, column) = e.location().map(|l| (l.line(), l.column())).unwrap_or((1, 1));
+                vec![RenderError {
+                    line,
+                    column,
+                    message: e.to_string(),
+                }]
+            }
+        };
+        tokens.finish(ContentType::Yaml, value.to_string(), errors)
+    }
+}
+
+// XML
+
+struct XmlRenderer;
+
+impl Renderer for XmlRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Xml
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xml", "svg", "xhtml", "plist", "xsd", "xsl"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        let trimmed = value.trim_start();
+        trimmed.starts_with("<?xml") || (trimmed.starts_with('<') && validate_xml(value).is_none())
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let errors = validate_xml(value).into_iter().collect();
+        lex_xml(value).finish(ContentType::Xml, value.to_string(), errors)
+    }
+}
+
+fn validate_xml(value: &str) -> Option<RenderError> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(value);
+    let mut open: Vec<Vec<u8>> = Vec::new();
+    let mut root_seen = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                root_seen = true;
+                open.push(start.name().as_ref().to_vec());
+            }
+            Ok(Event::Empty(_)) => root_seen = true,
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Some(error_at(value, reader.error_position() as usize, e)),
+        }
+    }
+    match open.last() {
+        Some(name) => Some(error_at(
+            value,
+            value.len(),
+            format!("Unclosed element <{}>", String::from_utf8_lossy(name)),
+        )),
+        None if !root_seen => Some(error_at(value, 0, "No root element")),
+        None => None,
+    }
+}
+
+fn lex_xml(text: &str) -> Tokens {
+    let bytes = text.as_bytes();
+    let mut tokens = Tokens::default();
+    let mut i = 0;
+    let end_of = |from: usize, terminator: &str| text[from..].find(terminator).map(|p| from + p + terminator.len()).unwrap_or(text.len());
+    while i < bytes.len() {
+        let rest = &text[i..];
+        if rest.starts_with("<!--") {
+            let end = end_of(i, "-->");
+            tokens.push(TokenKind::Comment, &text[i..end]);
+            i = end;
+        } else if rest.starts_with("<![CDATA[") {
+            let end = end_of(i, "]]>");
+            tokens.push(TokenKind::String, &text[i..end]);
+            i = end;
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            let end = end_of(i, ">");
+            tokens.push(TokenKind::Tag, &text[i..end]);
+            i = end;
+        } else if rest.starts_with('<') {
+            let open = if rest.starts_with("</") { 2 } else { 1 };
+            tokens.push(TokenKind::Punctuation, &rest[..open]);
+            i += open;
+            let name_end = text[i..]
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .map(|p| i + p)
+                .unwrap_or(text.len());
+            tokens.push(TokenKind::Tag, &text[i..name_end]);
+            i = name_end;
+            // Attributes up to the end of the tag
+            while i < bytes.len() && bytes[i] != b'>' {
+                let start = i;
+                let kind = match bytes[i] {
+                    b'"' | b'\'' => {
+                        i = quoted_end(text, i);
+                        TokenKind::String
+                    }
+                    b'=' | b'/' => {
+                        i += 1;
+                        TokenKind::Punctuation
+                    }
+                    b if b.is_ascii_whitespace() => {
+                        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                            i += 1;
+                        }
+                        TokenKind::Whitespace
+                    }
+                    _ => {
+                        while i < bytes.len() && !matches!(bytes[i], b'=' | b'>' | b'/' | b'"' | b'\'') && !bytes[i].is_ascii_whitespace() {
+                            i += 1;
+                        }
+                        TokenKind::Attribute
+                    }
+                };
+                tokens.push(kind, &text[start..i]);
+            }
+            if i < bytes.len() {
+                tokens.push(TokenKind::Punctuation, ">");
+                i += 1;
+            }
+        } else {
+            let end = rest.find('<').map(|p| i + p).unwrap_or(text.len());
+            let chunk = &text[i..end];
+            let kind = if chunk.trim().is_empty() { TokenKind::Whitespace } else { TokenKind::Text };
+            tokens.push(kind, chunk);
+            i = end;
+        }
+    }
+    tokens
+}
+
+// CSV
+
+struct CsvRenderer;
+
+impl Renderer for CsvRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Csv
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["csv"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        let lines: Vec<&str> = value.lines().filter(|l| !l.trim().is_empty()).collect();
+        let commas = |line: &str| line.matches(',').count();
+        lines.len() >= 2 && commas(lines[0]) > 0 && lines.iter().all(|l| commas(l) == commas(lines[0])) && validate_csv(value).is_empty()
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let bytes = value.as_bytes();
+        let mut tokens = Tokens::default();
+        let mut row = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            let kind = match bytes[i] {
+                b'"' => {
+                    // Doubled quotes escape a quote inside a quoted field
+                    i += 1;
+                    while i < bytes.len() {
+                        if bytes[i] == b'"' {
+                            if bytes.get(i + 1) == Some(&b'"') {
+                                i += 2;
+                                continue;
+                            }
+                            i += 1;
+                            break;
+                        }
+                        i += 1;
+                    }
+                    if row == 0 { TokenKind::Key } else { TokenKind::String }
+                }
+                b',' => {
+                    i += 1;
+                    TokenKind::Punctuation
+                }
+                b'\r' | b'\n' => {
+                    while i < bytes.len() && matches!(bytes[i], b'\r' | b'\n') {
+                        row += bytes[i..=i].iter().filter(|b| **b == b'\n').count();
+                        i += 1;
+                    }
+                    TokenKind::Whitespace
+                }
+                _ => {
+                    while i < bytes.len() && !matches!(bytes[i], b',' | b'\r' | b'\n') {
+                        i += 1;
+                    }
+                    match &value[start..i] {
+                        _ if row == 0 => TokenKind::Key,
+                        field if field.trim().parse::<f64>().is_ok() => TokenKind::Number,
+                        _ => TokenKind::String,
+                    }
+                }
+            };
+            tokens.push(kind, &value[start..i]);
+        }
+        tokens.finish(ContentType::Csv, value.to_string(), validate_csv(value))
+    }
+}
+
+fn validate_csv(value: &str) -> Vec<RenderError> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(value.as_bytes());
+    reader
+        .records()
+        .filter_map(Result::err)
+        .map(|e| RenderError {
+            line: e.position().map(|p| p.line() as usize).unwrap_or(1),
+            column: 1,
+            message: e.to_string(),
+        })
+        .collect()
+}
+
+// Markdown
+
+struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn content_type(&self) -> ContentType {
+        ContentType::Markdown
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["md", "markdown", "mdx"]
+    }
+
+    fn sniff(&self, value: &str) -> bool {
+        value.lines().any(|line| {
+            let hashes = line.len() - line.trim_start_matches('#').len();
+            ((1..=6).contains(&hashes) && line[hashes..].starts_with(' ')) || line.starts_with("```")
+        })
+    }
+
+    fn render(&self, value: &str) -> RenderedValue {
+        let mut tokens = Tokens::default();
+        let mut in_fence = false;
+        for line in value.split_inclusive('\n') {
+            let (indent, body, newline) = split_line(line);
+            tokens.push(TokenKind::Whitespace, indent);
+            let hashes = body.len() - body.trim_start_matches('#').len();
+            if body.starts_with("```") {
+                in_fence = !in_fence;
+                tokens.push(TokenKind::Code, body);
+            } else if in_fence {
+                tokens.push(TokenKind::Code, body);
+            } else if (1..=6).contains(&hashes) && body[hashes..].starts_with(' ') {
+                tokens.push(TokenKind::Heading, body);
+            } else {
+                let marker = list_marker(body);
+                tokens.push(TokenKind::Punctuation, &body[..marker]);
+                lex_inline_markdown(&body[marker..], &mut tokens);
+            }
+            tokens.push(TokenKind::Whitespace, newline);
+        }
+        tokens.finish(ContentType::Markdown, value.to_string(), Vec::new())
+    }
+}
+
+/// Length of a leading quote or list marker, including the space after it
+fn list_marker(body: &str) -> usize {
+    for marker in ["> ", "- ", "* ", "+ "] {
+        if body.starts_with(marker) {
+            return marker.len();
+        }
+    }
+    let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && body[digits..].starts_with(". ") {
+        digits + 2
+    } else {
+        0
+    }
+}
+
+fn lex_inline_markdown(text: &str, tokens: &mut Tokens) {
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let span = if let Some(code) = rest.strip_prefix('`') {
+            code.find('`').map(|end| (TokenKind::Code, end + 2))
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            rest[2..].find(&rest[..2]).map(|end| (TokenKind::Emphasis, end + 4))
+        } else if rest.starts_with('*') || rest.starts_with('_') {
+            rest[1..].find(&rest[..1]).filter(|end| *end > 0).map(|end| (TokenKind::Emphasis, end + 2))
+        } else if rest.starts_with('[') {
+            rest.find("](")
+                .and_then(|close| rest[close..].find(')').map(|end| (TokenKind::Link, close + end + 1)))
+        } else {
+            None
+        };
+        match span {
+            Some((kind, len)) => {
+                tokens.push(kind, &rest[..len]);
+                i += len;
+            }
+            None => {
+                let len = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+                tokens.push(TokenKind::Text, &rest[..len]);
+                i += len;
+            }
+        }
+    }
+}
+
+// Binary
+
+/// Offset, hex and ASCII columns of the value's bytes, like `hexdump -C`
+fn hex_dump(value: &str) -> RenderedValue {
+    let mut tokens = Tokens::default();
+    for (line, chunk) in value.as_bytes().chunks(HEX_DUMP_WIDTH).enumerate() {
+        tokens.push(TokenKind::Offset, &format!("{:08x}", line * HEX_DUMP_WIDTH));
+        tokens.push(TokenKind::Whitespace, "  ");
+        let mut hex = String::new();
+        for i in 0..HEX_DUMP_WIDTH {
+            match chunk.get(i) {
+                Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                None => hex.push_str("   "),
+            }
+            if i == HEX_DUMP_WIDTH / 2 - 1 {
+                hex.push(' ');
+            }
+        }
+        tokens.push(TokenKind::Hex, hex.trim_end());
+        tokens.push(TokenKind::Whitespace, &" ".repeat(hex.len() - hex.trim_end().len() + 1));
+        tokens.push(TokenKind::Punctuation, "|");
+        let ascii: String = chunk
+            .iter()
+            .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+            .collect();
+        tokens.push(TokenKind::Ascii, &ascii);
+        tokens.push(TokenKind::Punctuation, "|");
+        tokens.push(TokenKind::Whitespace, "\n");
+    }
+    let text = tokens.0.iter().map(|t| t.text.as_str()).collect();
+    tokens.finish(ContentType::Binary, text, Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(rendered: &RenderedValue) -> String {
+        rendered.tokens.iter().map(|t| t.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_detection_by_extension_and_content() {
+        let registry = RendererRegistry::default();
+        assert_eq!(registry.detect("/config.json", "{}"), (ContentType::Json, true));
+        assert_eq!(registry.detect("/settings", "[server]\nport = 8080\n"), (ContentType::Toml, false));
+        assert_eq!(registry.detect("/deploy", "name: api\nreplicas: 3\n"), (ContentType::Yaml, false));
+        assert_eq!(registry.detect("/feed", "<?xml version=\"1.0\"?><a/>"), (ContentType::Xml, false));
+        assert_eq!(registry.detect("/people", "name,age\nalice,30\nbob,41\n"), (ContentType::Csv, false));
+        assert_eq!(registry.detect("/notes", "# Title\n\nSome *text*"), (ContentType::Markdown, false));
+        assert_eq!(registry.detect("/logs/app.log", "10:00 INFO started\nstopped"), (ContentType::Text, false));
+        assert_eq!(registry.detect("/image.json", "PNG\u{0}\u{1a}"), (ContentType::Binary, false));
+    }
+
+    #[test]
+    fn test_json_is_pretty_printed_in_order() {
+        let rendered = RendererRegistry::default().render("/a.json", r#"{"b": [1, true], "a": {}, "c": null}"#);
+        assert_eq!(rendered.text, "{\n  \"b\": [\n    1,\n    true\n  ],\n  \"a\": {},\n  \"c\": null\n}");
+        assert_eq!(joined(&rendered), rendered.text);
+        let kinds: Vec<TokenKind> = rendered.tokens.iter().map(|t| t.kind).filter(|k| *k != TokenKind::Whitespace).collect();
+        assert_eq!(&kinds[..3], &[TokenKind::Punctuation, TokenKind::Key, TokenKind::Punctuation]);
+        assert!(rendered.tokens.iter().any(|t| t.kind == TokenKind::Null));
+
+        let broken = RendererRegistry::default().render("/a.json", "{\n  \"a\": 1,\n}");
+        assert_eq!(broken.text, "{\n  \"a\": 1,\n}");
+        assert_eq!(broken.errors[0].line, 3);
+    }
+
+    #[test]
+    fn test_parse_errors_and_hex_dump() {
+        let registry = RendererRegistry::default();
+        let toml = registry.render("/c.toml", "[a]\nx = 1\nx = 2\n");
+        assert_eq!(toml.errors.len(), 1);
+        assert_eq!(toml.errors[0].line, 3);
+        assert_eq!(joined(&toml), "[a]\nx = 1\nx = 2\n");
+
+        let xml = registry.render("/a.xml", "<a>\n  <b></c>\n</a>");
+        assert_eq!(xml.errors[0].line, 2);
+        assert!(xml.tokens.iter().any(|t| t.kind == TokenKind::Tag && t.text == "b"));
+        assert!(registry.render("/a.xml", "<a><b/></a>").errors.is_empty());
+
+        let csv = registry.render("/t.csv", "a,b\n1,2\n3\n");
+        assert_eq!(csv.errors[0].line, 3);
+
+        let yaml = registry.render("/t.yaml", "a: [1, 2\n");
+        assert!(!yaml.errors.is_empty());
+
+        let binary = registry.render("/blob", "AB\u{0}");
+        assert_eq!(binary.content_type, ContentType::Binary);
+        assert!(binary.text.starts_with("00000000  41 42 00 "));
+        assert!(binary.text.ends_with(" |AB.|\n"));
+        assert_eq!(binary.text.find('|'), Some(60), "ASCII column is aligned with full lines");
+    }
+}
@@ -0,0 +1,525 @@
+use super::{fuzzy, tree, types::*};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Version of the on-disk index format, bumped whenever `IndexEntry` changes
+const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Indexes kept in memory after loading or building, across stores
+const LOADED_INDEXES: usize = 8;
+
+/// Relevance given to nodes that only match on their value
+const VALUE_MATCH_SCORE: f32 = 0.3;
+
+/// A single indexed node, stored without its children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub key: String,
+    pub value: Option<String>,
+    pub node_type: NodeType,
+    pub metadata: NodeMetadata,
+}
+
+impl IndexEntry {
+    fn from_node(node: &IrminNode) -> Self {
+        Self {
+            key: node.key.clone(),
+            value: node.value.clone(),
+            node_type: node.node_type.clone(),
+            metadata: node.metadata.clone(),
+        }
+    }
+
+    fn to_node(&self) -> IrminNode {
+        IrminNode {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            node_type: self.node_type.clone(),
+            children: HashMap::new(),
+            metadata: self.metadata.clone(),
+            hash: None,
+        }
+    }
+}
+
+/// Summary of an index, returned to the frontend after building one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndexInfo {
+    pub commit: String,
+    pub entries: usize,
+    pub key_chars: usize,
+    pub value_terms: usize,
+}
+
+/// Inverted index over the keys and values of the tree at one commit.
+///
+/// Paths are indexed by their lowercase characters so a fuzzy query is only
+/// scored against paths containing every character of it, values are indexed
+/// by lowercase word terms. Only the entries are persisted; the posting lists
+/// are rebuilt on load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    pub commit: String,
+    entries: BTreeMap<String, IndexEntry>,
+    #[serde(skip)]
+    key_chars: HashMap<char, BTreeSet<String>>,
+    #[serde(skip)]
+    value_terms: HashMap<String, BTreeSet<String>>,
+}
+
+impl SearchIndex {
+    /// Build an index from scratch by walking the full tree of a commit
+    pub fn build(commit: &str, root: &IrminNode) -> Self {
+        let mut entries = BTreeMap::new();
+        tree::walk(root, &mut |path, _depth, node| {
+            entries.insert(path.to_string(), IndexEntry::from_node(node));
+        });
+
+        let mut index = Self {
+            version: INDEX_FORMAT_VERSION,
+            commit: commit.to_string(),
+            entries,
+            key_chars: HashMap::new(),
+            value_terms: HashMap::new(),
+        };
+        index.rebuild_postings();
+        index
+    }
+
+    /// Derive the index of a child commit by applying the parent-to-child diff.
+    ///
+    /// Commits are immutable, so the parent index plus the diff describes the
+    /// child tree exactly. Diffs carry no metadata, so touched entries take the
+    /// child commit's timestamp as their modification time.
+    pub fn derive(&self, child: &IrminCommit, diff: &IrminDiff) -> Self {
+        let mut index = Self {
+            version: INDEX_FORMAT_VERSION,
+            commit: child.hash.clone(),
+            entries: self.entries.clone(),
+            key_chars: self.key_chars.clone(),
+            value_terms: self.value_terms.clone(),
+        };
+
+        for change in &diff.changes {
+            let path = tree::normalize_path(&change.path);
+            match change.change_type {
+                ChangeType::Added | ChangeType::Modified => {
+                    let value = change.new_value.clone();
+                    index.ensure_parents(&path, child.timestamp);
+                    let entry = IndexEntry {
+                        key: tree::split_path(&path).1,
+                        metadata: NodeMetadata {
+                            last_modified: child.timestamp,
+                            size: value.as_ref().map(|v| v.len() as u64),
+                            permissions: index
+                                .entries
+                                .get(&path)
+                                .and_then(|e| e.metadata.permissions.clone())
+                                .or_else(|| Some("644".to_string())),
+                        },
+                        value,
+                        node_type: NodeType::File,
+                    };
+                    index.remove_entry(&path);
+                    index.insert_entry(path, entry);
+                }
+                ChangeType::Deleted => {
+                    index.remove_entry(&path);
+                    index.prune_empty_parents(&path);
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Search paths (fuzzy, case-insensitive) and value terms
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let pattern_len = query.chars().count();
+        let mut results = Vec::new();
+        let mut matched = BTreeSet::new();
+
+        for path in self.path_candidates(&query) {
+            let Some(m) = fuzzy::fuzzy_match(&query, path) else {
+                continue;
+            };
+            matched.insert(path.clone());
+            results.push(SearchResult {
+                path: path.clone(),
+                node: self.entries[path].to_node(),
+                relevance_score: m.relevance(pattern_len),
+                matched_indices: m.indices,
+            });
+        }
+
+        for term in terms(&query) {
+            if let Some(paths) = self.value_terms.get(&term) {
+                for path in paths {
+                    if matched.insert(path.clone()) {
+                        results.push(SearchResult {
+                            path: path.clone(),
+                            node: self.entries[path].to_node(),
+                            relevance_score: VALUE_MATCH_SCORE,
+                            matched_indices: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+
+        results.sort_by(fuzzy::compare_results);
+        results
+    }
+
+    pub fn info(&self) -> SearchIndexInfo {
+        SearchIndexInfo {
+            commit: self.commit.clone(),
+            entries: self.entries.len(),
+            key_chars: self.key_chars.len(),
+            value_terms: self.value_terms.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Paths containing every character of `query`, from the postings
+    fn path_candidates(&self, query: &str) -> Vec<&String> {
+        let chars: BTreeSet<char> = query.chars().collect();
+        let mut postings: Vec<&BTreeSet<String>> = Vec::with_capacity(chars.len());
+        for c in &chars {
+            match self.key_chars.get(c) {
+                Some(paths) => postings.push(paths),
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_by_key(|paths| paths.len());
+
+        postings[0]
+            .iter()
+            .filter(|path| postings[1..].iter().all(|paths| paths.contains(*path)))
+            .collect()
+    }
+
+    fn rebuild_postings(&mut self) {
+        self.key_chars.clear();
+        self.value_terms.clear();
+        let entries: Vec<(String, IndexEntry)> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        for (path, entry) in &entries {
+            self.add_postings(path, entry);
+        }
+    }
+
+    fn add_postings(&mut self, path: &str, entry: &IndexEntry) {
+        for c in path_chars(path) {
+            self.key_chars.entry(c).or_default().insert(path.to_string());
+        }
+        if let Some(value) = &entry.value {
+            for term in terms(value) {
+                self.value_terms.entry(term).or_default().insert(path.to_string());
+            }
+        }
+    }
+
+    fn insert_entry(&mut self, path: String, entry: IndexEntry) {
+        self.add_postings(&path, &entry);
+        self.entries.insert(path, entry);
+    }
+
+    fn remove_entry(&mut self, path: &str) {
+        let Some(entry) = self.entries.remove(path) else {
+            return;
+        };
+        for c in path_chars(path) {
+            remove_posting(&mut self.key_chars, &c, path);
+        }
+        if let Some(value) = &entry.value {
+            for term in terms(value) {
+                remove_posting(&mut self.value_terms, term.as_str(), path);
+            }
+        }
+    }
+
+    /// Create directory entries for any missing ancestors of `path`
+    fn ensure_parents(&mut self, path: &str, timestamp: DateTime<Utc>) {
+        let (parent, _) = tree::split_path(path);
+        if parent == "/" || self.entries.contains_key(&parent) {
+            return;
+        }
+        self.ensure_parents(&parent, timestamp);
+        let entry = IndexEntry {
+            key: tree::split_path(&parent).1,
+            value: None,
+            node_type: NodeType::Directory,
+            metadata: NodeMetadata {
+                last_modified: timestamp,
+                size: None,
+                permissions: Some("755".to_string()),
+            },
+        };
+        self.insert_entry(parent, entry);
+    }
+
+    /// Irmin has no empty directories, so drop ancestors left without children
+    fn prune_empty_parents(&mut self, path: &str) {
+        let (parent, _) = tree::split_path(path);
+        if parent == "/" {
+            return;
+        }
+        let prefix = format!("{}/", parent);
+        let has_children = self
+            .entries
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(p, _)| p.starts_with(&prefix));
+        if !has_children {
+            self.remove_entry(&parent);
+            self.prune_empty_parents(&parent);
+        }
+    }
+}
+
+fn remove_posting<K, Q>(postings: &mut HashMap<K, BTreeSet<String>>, token: &Q, path: &str)
+where
+    K: std::borrow::Borrow<Q> + std::hash::Hash + Eq,
+    Q: std::hash::Hash + Eq + ?Sized,
+{
+    if let Some(paths) = postings.get_mut(token) {
+        paths.remove(path);
+        if paths.is_empty() {
+            postings.remove(token);
+        }
+    }
+}
+
+/// Distinct lowercase characters of a path
+fn path_chars(path: &str) -> BTreeSet<char> {
+    path.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Lowercase alphanumeric word terms of `text`
+fn terms(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Indexes in memory, keyed by store directory and commit hash
+type Loaded = LruCache<(PathBuf, String), Arc<SearchIndex>>;
+
+fn loaded() -> &'static Mutex<Loaded> {
+    static LOADED: OnceLock<Mutex<Loaded>> = OnceLock::new();
+    LOADED.get_or_init(|| Mutex::new(LruCache::new(NonZeroUsize::new(LOADED_INDEXES).expect("capacity is not zero"))))
+}
+
+/// On-disk store of search indexes, one JSON file per commit hash.
+///
+/// The most recently used indexes stay in memory, so repeated searches of a
+/// commit do not read and rebuild its index again.
+pub struct SearchIndexStore {
+    dir: PathBuf,
+}
+
+impl SearchIndexStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn index_path(&self, commit: &str) -> PathBuf {
+        let file_name: String = commit
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.json", file_name))
+    }
+
+    pub fn contains(&self, commit: &str) -> bool {
+        self.index_path(commit).exists()
+    }
+
+    /// Load the index for `commit`, from memory when it was used recently.
+    ///
+    /// Returns `None` if it was never built, was written by an incompatible
+    /// version, or the file holds the index of another commit whose name maps
+    /// to the same file name.
+    pub fn load(&self, commit: &str) -> Result<Option<Arc<SearchIndex>>> {
+        let key = (self.dir.clone(), commit.to_string());
+        if let Some(index) = loaded().lock().expect("index cache poisoned").get(&key) {
+            return Ok(Some(index.clone()));
+        }
+        let path = self.index_path(commit);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut index: SearchIndex = serde_json::from_slice(&fs::read(&path)?)?;
+        if index.version != INDEX_FORMAT_VERSION || index.commit != commit {
+            return Ok(None);
+        }
+        index.rebuild_postings();
+        let index = Arc::new(index);
+        loaded().lock().expect("index cache poisoned").put(key, index.clone());
+        Ok(Some(index))
+    }
+
+    /// Write `index` to disk and keep it in memory
+    pub fn save(&self, index: SearchIndex) -> Result<Arc<SearchIndex>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.index_path(&index.commit);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(&index)?)?;
+        fs::rename(tmp, path)?;
+        let index = Arc::new(index);
+        loaded()
+            .lock()
+            .expect("index cache poisoned")
+            .put((self.dir.clone(), index.commit.clone()), index.clone());
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::{fixtures::FixtureStore, mock_data};
+
+    fn commit(hash: &str, parent: &str) -> IrminCommit {
+        IrminCommit {
+            hash: hash.to_string(),
+            message: "test".to_string(),
+            author: "Test <test@example.com>".to_string(),
+            timestamp: Utc::now(),
+            parents: vec![parent.to_string()],
+            branch: "main".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_build_and_search_keys() {
+        let index = SearchIndex::build("c1", &mock_data::generate_mock_tree());
+
+        let results = index.search("alice");
+        assert_eq!(results[0].path, "/users/alice.json");
+        assert_eq!(results[0].matched_indices, vec![7, 8, 9, 10, 11]);
+
+        let results = index.search("json");
+        assert!(results.iter().any(|r| r.path == "/config.json"));
+        assert!(results.iter().any(|r| r.path == "/users/bob.json"));
+    }
+
+    #[test]
+    fn test_search_matches_value_terms() {
+        let index = SearchIndex::build("c1", &mock_data::generate_mock_tree());
+        let results = index.search("localhost");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "/config.json");
+        assert_eq!(results[0].relevance_score, VALUE_MATCH_SCORE);
+    }
+
+    #[test]
+    fn test_derive_applies_diff() {
+        let parent = SearchIndex::build("c1", &mock_data::generate_mock_tree());
+        let diff = IrminDiff {
+            from_commit: "c1".to_string(),
+            to_commit: "c2".to_string(),
+            changes: vec![
+                DiffChange {
+                    path: "/teams/platform/members.txt".to_string(),
+                    change_type: ChangeType::Added,
+                    old_value: None,
+                    new_value: Some("carol dave".to_string()),
+                },
+                DiffChange {
+                    path: "/logs/app.log".to_string(),
+                    change_type: ChangeType::Deleted,
+                    old_value: Some("old".to_string()),
+                    new_value: None,
+                },
+            ],
+        };
+
+        let child = parent.derive(&commit("c2", "c1"), &diff);
+        assert_eq!(child.commit, "c2");
+        assert!(child.search("members").iter().any(|r| r.path == "/teams/platform/members.txt"));
+        assert!(child.search("platform").iter().any(|r| r.node.node_type == NodeType::Directory));
+        assert!(child.search("carol").iter().any(|r| r.path == "/teams/platform/members.txt"));
+        assert!(child.search("app.log").is_empty());
+        assert!(child.search("/logs").is_empty(), "empty directory should be pruned");
+
+        // The parent index is untouched
+        assert!(!parent.search("app.log").is_empty());
+    }
+
+    #[test]
+    fn test_derive_follows_sample_scenario() {
+        let store = FixtureStore::load("sample").unwrap();
+        let head = store.commits().remove(0);
+        let parent = SearchIndex::build(&head.parents[0], &store.tree_at(&head.parents[0]).unwrap());
+        let child = parent.derive(&head, &store.diff(&head.parents[0], &head.hash).unwrap());
+        let built = SearchIndex::build(&head.hash, &store.tree_at(&head.hash).unwrap());
+
+        assert_eq!(child.len(), built.len());
+        for query in ["log", "cache", "users"] {
+            let paths = |index: &SearchIndex| index.search(query).into_iter().map(|r| r.path).collect::<Vec<_>>();
+            assert_eq!(paths(&child), paths(&built), "query {}", query);
+        }
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("irmin-view-index-{}", uuid::Uuid::new_v4()));
+        let store = SearchIndexStore::new(&dir);
+        let index = SearchIndex::build("abc/123", &mock_data::generate_mock_tree());
+
+        assert!(store.load("abc/123").unwrap().is_none());
+        let (len, alice) = (index.len(), index.search("alice").len());
+        store.save(index).unwrap();
+        assert!(store.contains("abc/123"));
+
+        let loaded = SearchIndexStore::new(&dir).load("abc/123").unwrap().unwrap();
+        assert_eq!(loaded.len(), len);
+        assert_eq!(loaded.search("alice").len(), alice);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_loaded_indexes_stay_in_memory_and_match_their_commit() {
+        let dir = std::env::temp_dir().join(format!("irmin-view-index-{}", uuid::Uuid::new_v4()));
+        let store = SearchIndexStore::new(&dir);
+        store.save(SearchIndex::build("abc/123", &mock_data::generate_mock_tree())).unwrap();
+
+        // "abc_123" shares the file of "abc/123"
+        assert!(store.contains("abc_123"));
+        assert!(store.load("abc_123").unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(store.load("abc/123").unwrap().is_some());
+    }
+}
@@ -0,0 +1,249 @@
+//! Offline snapshots of a store.
+//!
+//! A snapshot is a single gzip-compressed JSON file holding the commit log,
+//! the branches and the trees of a store, taken through whichever backend is
+//! configured. By default only the trees at the branch heads are included;
+//! with full history the tree of every commit is. Trees are stored once per
+//! content hash. An opened snapshot is served read-only by a
+//! [`FixtureStore`], so every read command works on it as on the original.
+
+use super::{fixtures::FixtureStore, integration::{self, IrminConfig}, tree, types::*};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
+
+/// Value of the `format` field of every snapshot file
+pub const SNAPSHOT_FORMAT: &str = "irmin-view-snapshot";
+
+/// Version written by this build; older versions are read as well
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot file as written on disk
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub format: String,
+    pub version: u32,
+    /// Server URL or store path the snapshot was taken from
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+    pub full_history: bool,
+    pub commits: Vec<IrminCommit>,
+    pub branches: Vec<IrminBranch>,
+    /// Content hash of the tree of each included commit
+    pub trees: BTreeMap<String, String>,
+    /// Trees by content hash
+    pub objects: BTreeMap<String, IrminNode>,
+}
+
+impl Snapshot {
+    /// Download commits, branches and trees from the configured store
+    pub async fn take(config: &IrminConfig, full_history: bool) -> Result<Self> {
+        let commits = integration::get_irmin_commits(config).await?;
+        let branches = integration::get_irmin_branches(config).await?;
+        let mut wanted: Vec<&str> = branches.iter().map(|b| b.head_commit.as_str()).collect();
+        if full_history {
+            wanted.extend(commits.iter().map(|c| c.hash.as_str()));
+        }
+        if wanted.is_empty() {
+            bail!("The store has no branches or commits to snapshot");
+        }
+
+        let mut trees = BTreeMap::new();
+        let mut objects = BTreeMap::new();
+        for commit in wanted {
+            if trees.contains_key(commit) {
+                continue;
+            }
+            let node = integration::get_irmin_tree_at(config, commit)
+                .await
+                .with_context(|| format!("Failed to download the tree of {}", commit))?;
+            let hash = tree::content_hash(&node);
+            objects.entry(hash.clone()).or_insert(node);
+            trees.insert(commit.to_string(), hash);
+        }
+
+        Ok(Snapshot {
+            format: SNAPSHOT_FORMAT.to_string(),
+            version: SNAPSHOT_VERSION,
+            source: source(config),
+            created_at: Utc::now(),
+            full_history,
+            commits,
+            branches,
+            trees,
+            objects,
+        })
+    }
+
+    /// Write the snapshot to `path`, replacing any snapshot opened from there
+    pub fn write(&self, path: &Path) -> Result<()> {
+        opened().lock().unwrap().remove(path.to_string_lossy().as_ref());
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Read a snapshot file, compressed or plain JSON
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .with_context(|| format!("Failed to open snapshot {}", path.display()))?
+            .read_to_end(&mut bytes)?;
+        let snapshot: Snapshot = if bytes.starts_with(&[0x1f, 0x8b]) {
+            serde_json::from_reader(BufReader::new(GzDecoder::new(bytes.as_slice())))
+        } else {
+            serde_json::from_slice(&bytes)
+        }
+        .with_context(|| format!("{} is not a snapshot file", path.display()))?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            bail!("{} is not a snapshot file (format '{}')", path.display(), snapshot.format);
+        }
+        if snapshot.version > SNAPSHOT_VERSION {
+            bail!("Snapshot version {} is newer than this build supports ({})", snapshot.version, SNAPSHOT_VERSION);
+        }
+        Ok(snapshot)
+    }
+
+    pub fn info(&self, path: &str) -> SnapshotInfo {
+        SnapshotInfo {
+            path: path.to_string(),
+            source: self.source.clone(),
+            created_at: self.created_at,
+            full_history: self.full_history,
+            commit_count: self.commits.len(),
+            branch_count: self.branches.len(),
+            tree_count: self.trees.len(),
+        }
+    }
+
+    /// Read-only store serving the snapshot
+    pub fn into_store(self, name: String) -> Result<FixtureStore> {
+        let mut trees = HashMap::new();
+        for (commit, hash) in &self.trees {
+            let node = self
+                .objects
+                .get(hash)
+                .with_context(|| format!("Snapshot is missing tree {} of commit {}", hash, commit))?;
+            trees.insert(commit.clone(), node.clone());
+        }
+        let description = format!("Snapshot of {} taken {}", self.source, self.created_at.to_rfc3339());
+        Ok(FixtureStore::from_trees(name, description, self.commits, self.branches, &trees))
+    }
+}
+
+/// Modification time and length of a snapshot file, which tell whether an
+/// opened snapshot is still the one on disk
+type FileStamp = (Option<SystemTime>, u64);
+
+/// Snapshots opened so far, by path
+type Opened = HashMap<String, (FileStamp, Arc<FixtureStore>)>;
+
+fn opened() -> &'static Mutex<Opened> {
+    static STORES: OnceLock<Mutex<Opened>> = OnceLock::new();
+    STORES.get_or_init(Default::default)
+}
+
+/// Open a snapshot file as a store, reusing the store read earlier until the
+/// file changes
+pub fn open(path: &str) -> Result<Arc<FixtureStore>> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to open snapshot {}", path))?;
+    let stamp = (metadata.modified().ok(), metadata.len());
+    if let Some((opened_stamp, store)) = opened().lock().unwrap().get(path) {
+        if *opened_stamp == stamp {
+            return Ok(store.clone());
+        }
+    }
+    let store = Arc::new(Snapshot::read(Path::new(path))?.into_store(path.to_string())?);
+    opened().lock().unwrap().insert(path.to_string(), (stamp, store.clone()));
+    Ok(store)
+}
+
+fn active_snapshot() -> &'static RwLock<Option<String>> {
+    static ACTIVE: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+    ACTIVE.get_or_init(Default::default)
+}
+
+/// Serve the snapshot at `path` instead of the configured store, or stop
+/// serving one with `None`
+pub fn set_active(path: Option<String>) {
+    *active_snapshot().write().unwrap() = path;
+}
+
+/// Path of the snapshot opened in the app, if any
+pub fn active() -> Option<String> {
+    active_snapshot().read().unwrap().clone()
+}
+
+fn source(config: &IrminConfig) -> String {
+    match (&config.snapshot, &config.fixtures) {
+        (Some(path), _) => format!("snapshot {}", path),
+        (None, Some(scenario)) => format!("fixtures {}", scenario),
+        _ if config.use_http => config.server_url.clone().unwrap_or_else(|| "Irmin HTTP server".to_string()),
+        _ => config.store_path.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("irmin-snapshot-{}.json.gz", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_full_history_snapshot_serves_every_commit() {
+        let config = IrminConfig::new().with_fixtures("sample".to_string());
+        let snapshot = Snapshot::take(&config, true).await.unwrap();
+        assert_eq!(snapshot.trees.len(), 4);
+        let path = temp_path();
+        snapshot.write(&path).unwrap();
+
+        let offline = IrminConfig::new().with_snapshot(path.display().to_string());
+        let original = integration::get_irmin_diff(&config, "b2a1f6e5d4c3", "main").await.unwrap();
+        let diff = integration::get_irmin_diff(&offline, "b2a1f6e5d4c3", "main").await.unwrap();
+        let paths = |d: &IrminDiff| d.changes.iter().map(|c| c.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&diff), paths(&original));
+        assert_eq!(integration::get_irmin_commits(&offline).await.unwrap().len(), 4);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_head_snapshot_only_has_branch_trees() {
+        let config = IrminConfig::new().with_fixtures("sample".to_string());
+        let path = temp_path();
+        Snapshot::take(&config, false).await.unwrap().write(&path).unwrap();
+
+        let offline = IrminConfig::new().with_snapshot(path.display().to_string());
+        let tree = integration::get_irmin_tree(&offline).await.unwrap();
+        assert_eq!(tree::file_values(&tree), tree::file_values(&integration::get_irmin_tree(&config).await.unwrap()));
+        assert!(integration::get_irmin_tree_at(&offline, "b2a1f6e5d4c3").await.is_err());
+        assert_eq!(Snapshot::read(&path).unwrap().info("x").tree_count, 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rewritten_snapshot_is_read_again() {
+        let config = IrminConfig::new().with_fixtures("sample".to_string());
+        let path = temp_path();
+        let name = path.display().to_string();
+        Snapshot::take(&config, false).await.unwrap().write(&path).unwrap();
+        assert_eq!(open(&name).unwrap().commits().len(), 4);
+        assert!(Arc::ptr_eq(&open(&name).unwrap(), &open(&name).unwrap()));
+
+        let mut smaller = Snapshot::take(&config, false).await.unwrap();
+        smaller.commits.truncate(1);
+        smaller.write(&path).unwrap();
+        assert_eq!(open(&name).unwrap().commits().len(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+use super::{tree, types::*};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::sync::Arc;
+
+/// Number of largest values reported when none is requested
+pub const DEFAULT_LARGEST: usize = 10;
+
+/// Number of commits the growth series covers when none is requested
+pub const DEFAULT_HISTORY: usize = 10;
+
+/// Cached aggregates are dropped wholesale beyond this many subtrees
+const MAX_CACHED_SUBTREES: usize = 50_000;
+
+/// Totals of one directory, independent of where it sits in the tree
+#[derive(Debug, Default)]
+struct Aggregate {
+    size: u64,
+    keys: usize,
+    directories: usize,
+    /// Keys per depth below the directory, index 0 holding its direct children
+    depths: Vec<usize>,
+    /// Keys and bytes per extension
+    types: BTreeMap<String, (usize, u64)>,
+}
+
+impl Aggregate {
+    fn add_key(&mut self, key: &str, size: u64) {
+        self.size += size;
+        self.keys += 1;
+        let entry = self.types.entry(tree::extension(key)).or_default();
+        entry.0 += 1;
+        entry.1 += size;
+    }
+
+    fn add_directory(&mut self, child: &Aggregate) {
+        self.size += child.size;
+        self.keys += child.keys;
+        self.directories += child.directories + 1;
+        if self.depths.len() < child.depths.len() + 1 {
+            self.depths.resize(child.depths.len() + 1, 0);
+        }
+        for (depth, count) in child.depths.iter().enumerate() {
+            self.depths[depth + 1] += count;
+        }
+        for (ext, (keys, size)) in &child.types {
+            let entry = self.types.entry(ext.clone()).or_default();
+            entry.0 += keys;
+            entry.1 += size;
+        }
+    }
+}
+
+/// Computes store statistics bottom-up, remembering the totals of every
+/// directory by its content hash.
+///
+/// Directory hashes cover the keys and values below them, so a directory
+/// that did not change between commits is only summed once. Sizes are the
+/// lengths of the values, which the hashes cover; `NodeMetadata.size` is
+/// only used for keys listed without their value.
+#[derive(Default)]
+pub struct StatsCache {
+    aggregates: HashMap<String, Arc<Aggregate>>,
+    subtrees_computed: usize,
+}
+
+impl StatsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of directories summed so far rather than taken from the cache
+    pub fn subtrees_computed(&self) -> usize {
+        self.subtrees_computed
+    }
+
+    fn aggregate(&mut self, node: &IrminNode, path: &str, hashes: &HashMap<String, String>) -> Arc<Aggregate> {
+        let hash = &hashes[path];
+        if let Some(aggregate) = self.aggregates.get(hash) {
+            return aggregate.clone();
+        }
+        let mut aggregate = Aggregate::default();
+        for (key, child) in &node.children {
+            if child.node_type == NodeType::Directory {
+                let child_aggregate = self.aggregate(child, &tree::join_path(path, key), hashes);
+                aggregate.add_directory(&child_aggregate);
+            } else {
+                aggregate.add_key(key, value_size(child));
+            }
+        }
+        let direct_keys = node.children.values().filter(|c| c.node_type != NodeType::Directory).count();
+        if direct_keys > 0 {
+            if aggregate.depths.is_empty() {
+                aggregate.depths.push(0);
+            }
+            aggregate.depths[0] += direct_keys;
+        }
+
+        if self.aggregates.len() >= MAX_CACHED_SUBTREES {
+            self.aggregates.clear();
+        }
+        self.subtrees_computed += 1;
+        let aggregate = Arc::new(aggregate);
+        self.aggregates.insert(hash.clone(), aggregate.clone());
+        aggregate
+    }
+
+    /// Statistics of the directory at `path` in `root`, without growth
+    pub fn store_stats(&mut self, commit: &str, root: &IrminNode, path: &str, largest: usize) -> Result<StoreStats, String> {
+        let path = tree::normalize_path(path);
+        let node = tree::find_node(root, &path)
+            .filter(|n| n.node_type == NodeType::Directory)
+            .ok_or_else(|| format!("{} is not a directory at {}", path, commit))?;
+        let hashes = tree::content_hashes(node);
+        let total = self.aggregate(node, "/", &hashes);
+
+        let mut subtrees = Vec::new();
+        let mut heap = BinaryHeap::new();
+        tree::walk(node, &mut |relative, _, child| {
+            let full_path = tree::join_path(&path, relative.trim_start_matches('/'));
+            if child.node_type == NodeType::Directory {
+                let aggregate = self.aggregate(child, relative, &hashes);
+                subtrees.push(SubtreeStats {
+                    path: full_path,
+                    total_size: aggregate.size,
+                    key_count: aggregate.keys,
+                    directory_count: aggregate.directories,
+                });
+            } else if largest > 0 {
+                heap.push(Reverse((value_size(child), Reverse(full_path))));
+                if heap.len() > largest {
+                    heap.pop();
+                }
+            }
+        });
+        subtrees.sort_by(|a, b| b.total_size.cmp(&a.total_size).then_with(|| a.path.cmp(&b.path)));
+        let largest_values = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, Reverse(path)))| ValueSize { path, size })
+            .collect();
+        let mut file_types: Vec<FileTypeStats> = total
+            .types
+            .iter()
+            .map(|(extension, (key_count, total_size))| FileTypeStats {
+                extension: extension.clone(),
+                key_count: *key_count,
+                total_size: *total_size,
+            })
+            .collect();
+        file_types.sort_by(|a, b| b.total_size.cmp(&a.total_size).then_with(|| a.extension.cmp(&b.extension)));
+
+        Ok(StoreStats {
+            commit: commit.to_string(),
+            path,
+            tree_hash: hashes["/"].clone(),
+            total_size: total.size,
+            key_count: total.keys,
+            directory_count: total.directories,
+            depth_histogram: total.depths.clone(),
+            subtrees,
+            largest_values,
+            file_types,
+            growth: Vec::new(),
+        })
+    }
+
+    /// Total size and key count of `path` at a commit, zero where it is missing
+    pub fn growth_point(&mut self, commit: &IrminCommit, root: &IrminNode, path: &str) -> GrowthPoint {
+        let (total_size, key_count) = match tree::find_node(root, path) {
+            Some(node) if node.node_type == NodeType::Directory => {
+                let aggregate = self.aggregate(node, "/", &tree::content_hashes(node));
+                (aggregate.size, aggregate.keys)
+            }
+            Some(node) => (value_size(node), 1),
+            None => (0, 0),
+        };
+        GrowthPoint {
+            commit: commit.hash.clone(),
+            timestamp: commit.timestamp,
+            total_size,
+            key_count,
+        }
+    }
+}
+
+/// Up to `count` commits ending at `head`, following first parents, oldest first
+pub fn recent_history(commits: &[IrminCommit], head: &str, count: usize) -> Vec<IrminCommit> {
+    let by_hash: HashMap<&str, &IrminCommit> = commits.iter().map(|c| (c.hash.as_str(), c)).collect();
+    let mut history = Vec::new();
+    let mut next = by_hash.get(head).copied();
+    while let Some(commit) = next.filter(|_| history.len() < count) {
+        history.push(commit.clone());
+        next = commit.parents.first().and_then(|p| by_hash.get(p.as_str()).copied());
+    }
+    history.reverse();
+    history
+}
+
+fn value_size(node: &IrminNode) -> u64 {
+    match &node.value {
+        Some(value) => value.len() as u64,
+        None => node.metadata.size.unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::fixtures::FixtureStore;
+
+    fn sample() -> std::sync::Arc<FixtureStore> {
+        FixtureStore::load("sample").unwrap()
+    }
+
+    #[test]
+    fn test_store_stats_aggregates() {
+        let tree = sample().tree().unwrap();
+        let stats = StatsCache::new().store_stats("c1", &tree, "/", 2).unwrap();
+        assert_eq!((stats.key_count, stats.directory_count), (7, 2));
+        assert_eq!(stats.depth_histogram, vec![2, 5]);
+        assert_eq!(stats.total_size, 345 + 90 + 200 + 108 + 107 + 100 + 108);
+        assert_eq!(stats.subtrees[0].path, "/users");
+        assert_eq!(stats.largest_values[0].path, "/README.md");
+        assert_eq!(stats.largest_values.len(), 2);
+        let json = stats.file_types.iter().find(|t| t.extension == "json").unwrap();
+        assert_eq!(json.key_count, 4);
+
+        let users = StatsCache::new().store_stats("c1", &tree, "users/", 10).unwrap();
+        assert_eq!(users.depth_histogram, vec![3]);
+        assert_eq!(users.largest_values[0].path, "/users/charlie.json");
+        assert!(StatsCache::new().store_stats("c1", &tree, "/README.md", 10).is_err());
+    }
+
+    #[test]
+    fn test_unchanged_subtrees_come_from_cache() {
+        let tree = sample().tree().unwrap();
+        let mut cache = StatsCache::new();
+        cache.store_stats("c1", &tree, "/", 10).unwrap();
+        assert_eq!(cache.subtrees_computed(), 3);
+
+        let mut changed = tree.clone();
+        changed.children.get_mut("logs").unwrap().children.remove("error.log");
+        let stats = cache.store_stats("c2", &changed, "/", 10).unwrap();
+        assert_eq!(cache.subtrees_computed(), 5, "only the root and /logs are summed again");
+        assert_eq!(stats.key_count, 6);
+    }
+
+    fn alice(tree: &mut IrminNode) -> &mut IrminNode {
+        tree.children.get_mut("users").unwrap().children.get_mut("alice.json").unwrap()
+    }
+
+    #[test]
+    fn test_sizes_follow_values_not_stale_metadata() {
+        let tree = sample().tree().unwrap();
+        let mut cache = StatsCache::new();
+        let before = cache.store_stats("c1", &tree, "/", 10).unwrap();
+
+        let mut resized = tree.clone();
+        alice(&mut resized).metadata.size = Some(1_000_000);
+        let after = cache.store_stats("c2", &resized, "/", 10).unwrap();
+        assert_eq!(after.total_size, before.total_size);
+        assert_eq!(StatsCache::new().store_stats("c2", &resized, "/", 10).unwrap().total_size, before.total_size);
+
+        alice(&mut resized).value = None;
+        let listed = cache.store_stats("c3", &resized, "/", 10).unwrap();
+        assert_eq!(listed.total_size, before.total_size - 107 + 1_000_000);
+    }
+
+    #[test]
+    fn test_growth_over_recent_history() {
+        let commits = sample().commits();
+        let history = recent_history(&commits, "a1b2c3d4e5f6", 2);
+        let hashes: Vec<&str> = history.iter().map(|c| c.hash.as_str()).collect();
+        assert_eq!(hashes, vec!["f6e5d4c3b2a1", "a1b2c3d4e5f6"]);
+
+        let mut cache = StatsCache::new();
+        let tree = sample().tree().unwrap();
+        let point = cache.growth_point(&history[1], &tree, "/users");
+        assert_eq!((point.key_count, point.total_size), (3, 107 + 100 + 108));
+        assert_eq!(cache.growth_point(&history[1], &tree, "/missing").key_count, 0);
+    }
+}
@@ -0,0 +1,235 @@
+use super::types::*;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// Join a parent path and a child key into a store path ("/users/alice.json")
+pub fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() || parent == "/" {
+        format!("/{}", key)
+    } else {
+        format!("{}/{}", parent, key)
+    }
+}
+
+/// Normalize a user supplied path ("users/", "/users") to the store path form
+pub fn normalize_path(path: &str) -> String {
+    let trimmed = path.trim_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Visit every node below `root` (the root itself is not visited).
+///
+/// The visitor receives the store path, the depth (1 for direct children of
+/// the root) and the node. Children are visited in key order so that results
+/// built from a walk are deterministic.
+pub fn walk<F>(root: &IrminNode, visitor: &mut F)
+where
+    F: FnMut(&str, usize, &IrminNode),
+{
+    walk_children(root, "", 1, visitor);
+}
+
+fn walk_children<F>(node: &IrminNode, path: &str, depth: usize, visitor: &mut F)
+where
+    F: FnMut(&str, usize, &IrminNode),
+{
+    let mut keys: Vec<&String> = node.children.keys().collect();
+    keys.sort();
+    for key in keys {
+        let child = &node.children[key];
+        let child_path = join_path(path, key);
+        visitor(&child_path, depth, child);
+        walk_children(child, &child_path, depth + 1, visitor);
+    }
+}
+
+/// Every node below `root` as flat entries of the streaming tree transfer,
+/// parents before children
+pub fn entries(root: &IrminNode) -> Vec<TreeEntry> {
+    let hashes = content_hashes(root);
+    let mut entries = Vec::new();
+    walk(root, &mut |path, _, node| {
+        entries.push(TreeEntry {
+            path: path.to_string(),
+            node_type: node.node_type.clone(),
+            size: node.metadata.size,
+            hash: hashes.get(path).cloned(),
+        })
+    });
+    entries
+}
+
+/// Values of every file and link below `root`, keyed by store path
+pub fn file_values(root: &IrminNode) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    walk(root, &mut |path, _, node| {
+        if node.node_type != NodeType::Directory {
+            values.insert(path.to_string(), node.value.clone().unwrap_or_default());
+        }
+    });
+    values
+}
+
+/// Find the node at `path` below `root`, where "/" or "" is the root itself
+pub fn find_node<'a>(root: &'a IrminNode, path: &str) -> Option<&'a IrminNode> {
+    let mut node = root;
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        node = node.children.get(segment)?;
+    }
+    Some(node)
+}
+
+/// Split a store path into its parent path and final key
+pub fn split_path(path: &str) -> (String, String) {
+    let normalized = normalize_path(path);
+    match normalized.rfind('/') {
+        Some(0) => ("/".to_string(), normalized[1..].to_string()),
+        Some(idx) => (normalized[..idx].to_string(), normalized[idx + 1..].to_string()),
+        None => ("/".to_string(), normalized),
+    }
+}
+
+/// Content hash of a subtree, independent of its own key and metadata.
+///
+/// Two subtrees with the same children and values hash the same wherever
+/// they appear, which lets walks over many commits skip unchanged subtrees.
+pub fn content_hash(node: &IrminNode) -> String {
+    to_hex(&merkle_hash(node, "/", &mut None))
+}
+
+/// Content hashes of the root and every subtree below it, keyed by store
+/// path ("/" for the root), computed bottom-up in a single pass
+pub fn content_hashes(root: &IrminNode) -> HashMap<String, String> {
+    let mut hashes = HashMap::new();
+    merkle_hash(root, "/", &mut Some(&mut hashes));
+    hashes
+}
+
+fn merkle_hash(node: &IrminNode, path: &str, out: &mut Option<&mut HashMap<String, String>>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    match node.node_type {
+        NodeType::Directory => hasher.update(b"tree\0"),
+        NodeType::File => hasher.update(b"blob\0"),
+        NodeType::Link => hasher.update(b"link\0"),
+    }
+    if let Some(value) = &node.value {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    }
+    let mut keys: Vec<&String> = node.children.keys().collect();
+    keys.sort();
+    for key in keys {
+        let child = merkle_hash(&node.children[key], &join_path(path, key), out);
+        hasher.update((key.len() as u64).to_le_bytes());
+        hasher.update(key.as_bytes());
+        hasher.update(child);
+    }
+    let digest: [u8; 32] = hasher.finalize().into();
+    if let Some(hashes) = out {
+        hashes.insert(path.to_string(), to_hex(&digest));
+    }
+    digest
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Number of segments in a store path ("/" is 0, "/users/alice.json" is 2)
+pub fn path_depth(path: &str) -> usize {
+    path.split('/').filter(|s| !s.is_empty()).count()
+}
+
+/// Lower-cased extension of a key without the dot, empty when it has none
+pub fn extension(key: &str) -> String {
+    match key.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext.to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+/// Match a store path against a glob pattern.
+///
+/// `*` and `?` never cross a `/`, `**` matches any number of segments. A
+/// leading `/` is optional on both sides, so `users/*.json` matches
+/// `/users/alice.json`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim_start_matches('/').chars().collect();
+    let path: Vec<char> = path.trim_start_matches('/').chars().collect();
+    glob_match_from(&pattern, &path)
+}
+
+fn glob_match_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // "**/" also matches zero segments
+            let rest = &pattern[2..];
+            if rest.first() == Some(&'/') && glob_match_from(&rest[1..], path) {
+                return true;
+            }
+            (0..=path.len()).any(|i| glob_match_from(rest, &path[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=path.len() {
+                if glob_match_from(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => matches!(path.first(), Some(c) if *c != '/') && glob_match_from(&pattern[1..], &path[1..]),
+        Some(c) => path.first() == Some(c) && glob_match_from(&pattern[1..], &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("users/*.json", "/users/alice.json"));
+        assert!(!glob_match("users/*.json", "/users/admins/root.json"));
+        assert!(glob_match("users/**/*.json", "/users/admins/root.json"));
+        assert!(glob_match("users/**/*.json", "/users/alice.json"));
+        assert!(glob_match("**", "/a/b/c"));
+        assert!(glob_match("/logs/app.?og", "/logs/app.log"));
+        assert!(!glob_match("*.json", "/users/alice.json"));
+    }
+
+    #[test]
+    fn test_path_helpers() {
+        assert_eq!(normalize_path("users/"), "/users");
+        assert_eq!(normalize_path(""), "/");
+        assert_eq!(split_path("/users/alice.json"), ("/users".to_string(), "alice.json".to_string()));
+        assert_eq!(split_path("config.json"), ("/".to_string(), "config.json".to_string()));
+        assert_eq!(path_depth("/users/alice.json"), 2);
+        assert_eq!(join_path("/", "users"), "/users");
+    }
+
+    #[test]
+    fn test_content_hash_ignores_key_and_metadata() {
+        let tree = crate::irmin::mock_data::generate_mock_tree();
+        let mut moved = tree.children["users"].clone();
+        moved.key = "people".to_string();
+        moved.metadata.permissions = None;
+        assert_eq!(content_hash(&moved), content_hash(&tree.children["users"]));
+
+        moved.children.get_mut("bob.json").unwrap().value = Some("{}".to_string());
+        assert_ne!(content_hash(&moved), content_hash(&tree.children["users"]));
+
+        let hashes = content_hashes(&tree);
+        assert_eq!(hashes["/"], content_hash(&tree));
+        assert_eq!(hashes["/users"], content_hash(&tree.children["users"]));
+        assert!(hashes.contains_key("/users/alice.json"));
+    }
+}
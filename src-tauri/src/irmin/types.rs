@@ -1,31 +1,46 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use super::wire;
 
 /// Represents a commit in the Irmin store
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IrminCommit {
     pub hash: String,
     pub message: String,
     pub author: String,
+    #[serde(deserialize_with = "wire::timestamp")]
+    #[schemars(schema_with = "wire::timestamp_schema")]
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
     pub parents: Vec<String>,
     pub branch: String,
 }
 
 /// Represents a node in the Irmin tree
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IrminNode {
     pub key: String,
     pub value: Option<String>,
+    #[serde(deserialize_with = "wire::node_type")]
+    #[schemars(schema_with = "wire::node_type_schema")]
     pub node_type: NodeType,
+    #[serde(default, deserialize_with = "wire::or_default")]
+    #[schemars(with = "Option<HashMap<String, IrminNode>>")]
     pub children: HashMap<String, IrminNode>,
+    #[serde(default, deserialize_with = "wire::or_default")]
+    #[schemars(with = "Option<NodeMetadata>")]
     pub metadata: NodeMetadata,
+    /// Content hash reported by the backend (Irmin's node or contents hash);
+    /// equal hashes at a path mean an unchanged subtree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
 }
 
 /// Types of nodes in the Irmin tree
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum NodeType {
     Directory,
     File,
@@ -33,24 +48,28 @@ pub enum NodeType {
 }
 
 /// Metadata associated with a node
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct NodeMetadata {
+    #[serde(default, deserialize_with = "wire::timestamp_or_default")]
+    #[schemars(schema_with = "wire::optional_timestamp_schema")]
     pub last_modified: DateTime<Utc>,
     pub size: Option<u64>,
     pub permissions: Option<String>,
 }
 
 /// Represents a branch in the Irmin store
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IrminBranch {
     pub name: String,
     pub head_commit: String,
+    #[serde(deserialize_with = "wire::timestamp")]
+    #[schemars(schema_with = "wire::timestamp_schema")]
     pub last_updated: DateTime<Utc>,
     pub commit_count: usize,
 }
 
 /// Represents a diff between two commits
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IrminDiff {
     pub from_commit: String,
     pub to_commit: String,
@@ -58,16 +77,18 @@ pub struct IrminDiff {
 }
 
 /// Individual change in a diff
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffChange {
     pub path: String,
+    #[serde(deserialize_with = "wire::change_type")]
+    #[schemars(schema_with = "wire::change_type_schema")]
     pub change_type: ChangeType,
     pub old_value: Option<String>,
     pub new_value: Option<String>,
 }
 
 /// Types of changes in a diff
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum ChangeType {
     Added,
     Modified,
@@ -75,11 +96,339 @@ pub enum ChangeType {
 }
 
 /// Search result for keys
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchResult {
     pub path: String,
     pub node: IrminNode,
     pub relevance_score: f32,
+    /// Character positions in `path` matched by the query, for highlighting
+    #[serde(default)]
+    pub matched_indices: Vec<usize>,
+}
+
+/// Node matched by a metadata filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterMatch {
+    pub path: String,
+    pub depth: usize,
+    pub node: IrminNode,
+}
+
+/// Results of a value query for one key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryRow {
+    pub path: String,
+    pub values: Vec<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// Per-key results of a value query across a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryTable {
+    pub rows: Vec<QueryRow>,
+    pub keys_visited: usize,
+    /// Set when the key limit was reached before the walk finished
+    pub truncated: bool,
+}
+
+/// A key that matched a history search, with the commits it matched in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMatch {
+    pub path: String,
+    pub matched_key: bool,
+    pub matched_value: bool,
+    pub first_commit: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_commit: String,
+    pub last_seen: DateTime<Utc>,
+    /// Number of scanned commits in which the match was present
+    pub commit_count: usize,
+    /// Whether the match is still present in the newest scanned commit
+    pub present_at_end: bool,
+}
+
+/// Manifest written alongside an exported subtree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub source_commit: String,
+    pub revision: String,
+    pub path: String,
+    pub format: String,
+    pub exported_at: DateTime<Utc>,
+    pub file_count: usize,
+    pub directory_count: usize,
+    pub total_bytes: u64,
+}
+
+/// Progress of a running export, sent to the UI as `export-progress` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub destination: String,
+    pub current_path: Option<String>,
+    pub entries_written: usize,
+    pub total_entries: usize,
+    pub bytes_written: u64,
+    pub total_bytes: u64,
+    pub finished: bool,
+}
+
+/// Changes to apply to a branch as a single commit
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommitRequest {
+    pub branch: String,
+    pub message: String,
+    pub author: String,
+    pub changes: Vec<DiffChange>,
+}
+
+/// Commit created from a `CommitRequest`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommitResult {
+    pub hash: String,
+}
+
+/// Outcome of an import, or the changes it would make on a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub branch: String,
+    pub dry_run: bool,
+    pub changes: Vec<DiffChange>,
+    /// Hash of the created commit; `None` on a dry run or when nothing changed
+    pub commit: Option<String>,
+}
+
+/// A patch section that could not be applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchReject {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Outcome of applying a patch, or the changes it would make on a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchReport {
+    pub branch: String,
+    pub dry_run: bool,
+    pub reverse: bool,
+    pub changes: Vec<DiffChange>,
+    pub rejects: Vec<PatchReject>,
+    /// Hash of the created commit; `None` on a dry run, when nothing changed
+    /// or when rejects blocked the commit
+    pub commit: Option<String>,
+}
+
+/// Size statistics of a subtree at one commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreStats {
+    pub commit: String,
+    pub path: String,
+    pub tree_hash: String,
+    pub total_size: u64,
+    pub key_count: usize,
+    pub directory_count: usize,
+    /// Number of keys per depth below `path`; index 0 holds depth 1
+    pub depth_histogram: Vec<usize>,
+    /// Every directory below `path`, largest first
+    pub subtrees: Vec<SubtreeStats>,
+    pub largest_values: Vec<ValueSize>,
+    /// Keys and bytes per file extension, largest first
+    pub file_types: Vec<FileTypeStats>,
+    /// Size of `path` over the last commits, oldest first
+    pub growth: Vec<GrowthPoint>,
+}
+
+/// Aggregate size of one directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtreeStats {
+    pub path: String,
+    pub total_size: u64,
+    pub key_count: usize,
+    pub directory_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueSize {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileTypeStats {
+    /// Lower-cased extension without the dot, empty for keys without one
+    pub extension: String,
+    pub key_count: usize,
+    pub total_size: u64,
+}
+
+/// Size of a subtree at one commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthPoint {
+    pub commit: String,
+    pub timestamp: DateTime<Utc>,
+    pub total_size: u64,
+    pub key_count: usize,
+}
+
+/// Kinds of content a value can be rendered as
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ContentType {
+    Json,
+    Toml,
+    Yaml,
+    Xml,
+    Markdown,
+    Csv,
+    Text,
+    Binary,
+}
+
+/// Syntax classes of rendered tokens
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TokenKind {
+    Key,
+    String,
+    Number,
+    Boolean,
+    Null,
+    Punctuation,
+    Comment,
+    Tag,
+    Attribute,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+    Offset,
+    Hex,
+    Ascii,
+    Whitespace,
+    Text,
+}
+
+/// A run of rendered text of one syntax class
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+/// Parse error in a value, with 1-based line and column
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RenderError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// A value prepared for display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedValue {
+    pub content_type: ContentType,
+    /// Whether the content type came from the key's extension rather than sniffing
+    pub from_extension: bool,
+    /// Pretty-printed value, the value itself when it does not parse, or a
+    /// hex dump for binary content
+    pub text: String,
+    /// Tokens covering `text` exactly
+    pub tokens: Vec<SyntaxToken>,
+    pub errors: Vec<RenderError>,
+}
+
+/// A stored value that does not satisfy a schema rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    pub path: String,
+    /// File name of the violated schema
+    pub schema: String,
+    /// JSON pointer to the offending part of the value, empty for the whole value
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Result of checking every value of a revision against the schema rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub commit: String,
+    pub keys_checked: usize,
+    pub violations: Vec<SchemaViolation>,
+}
+
+/// Hit/miss counters and configuration of the response cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheMetrics {
+    /// Requests answered from memory
+    pub hits: u64,
+    /// Requests answered from the on-disk cache
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+    pub ttl_secs: u64,
+    pub disk_dir: Option<String>,
+}
+
+/// Summary of an offline snapshot file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub path: String,
+    /// Server URL or store path the snapshot was taken from
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+    pub full_history: bool,
+    pub commit_count: usize,
+    pub branch_count: usize,
+    /// Commits whose tree is included
+    pub tree_count: usize,
+}
+
+/// One node of a tree in the streaming transfer, a line of NDJSON
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TreeEntry {
+    /// Store path, e.g. `/users/alice.json`; parents come before children
+    pub path: String,
+    #[serde(rename = "type", deserialize_with = "wire::node_type")]
+    #[schemars(schema_with = "wire::node_type_schema")]
+    pub node_type: NodeType,
+    pub size: Option<u64>,
+    /// Content hash of the node
+    pub hash: Option<String>,
+}
+
+/// Entries of a streamed tree, sent to the UI over a channel as they arrive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeBatch {
+    pub entries: Vec<TreeEntry>,
+    /// Entries sent so far, including these
+    pub entries_sent: usize,
+    pub finished: bool,
+}
+
+/// Progress of a long-running operation, sent to the UI as
+/// `operation-progress` events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationProgress {
+    pub operation_id: String,
+    /// What the operation is doing, e.g. "Downloading"
+    pub phase: String,
+    pub items_done: u64,
+    /// Items in this phase, when known up front
+    pub total_items: Option<u64>,
+    pub bytes: u64,
+    pub finished: bool,
+}
+
+/// A branch head that moved outside IrminView, sent to the UI as
+/// `branch-updated` events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchUpdate {
+    pub branch: String,
+    /// `None` when the branch was created
+    pub old_head: Option<String>,
+    /// `None` when the branch was deleted
+    pub new_head: Option<String>,
+    /// Commits reachable from the new head but not from the old one
+    pub new_commits: usize,
 }
 
 /// Connection information for an Irmin store
@@ -0,0 +1,172 @@
+use super::{project::ProjectConfig, tree, types::*};
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+
+/// A compiled schema and the keys it applies to
+struct CompiledRule {
+    pattern: String,
+    schema_name: String,
+    validator: jsonschema::Validator,
+}
+
+/// JSON Schema rules of a project, compiled once and applied to values
+pub struct SchemaRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl SchemaRules {
+    /// Read and compile every schema named by the project config
+    pub fn from_config(config: &ProjectConfig) -> Result<Self> {
+        let mut rules = Vec::new();
+        for rule in &config.schemas {
+            let text = std::fs::read_to_string(&rule.schema)
+                .with_context(|| format!("Failed to read schema {}", rule.schema.display()))?;
+            let schema: Value = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in schema {}", rule.schema.display()))?;
+            rules.push(CompiledRule {
+                pattern: rule.pattern.clone(),
+                schema_name: schema_name(&rule.schema),
+                validator: jsonschema::validator_for(&schema)
+                    .map_err(|e| anyhow!("Invalid schema {}: {}", rule.schema.display(), e))?,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Rules of the project config of the working directory
+    pub fn load() -> Result<Self> {
+        Self::from_config(&ProjectConfig::load()?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether any rule applies to `path`
+    pub fn covers(&self, path: &str) -> bool {
+        self.rules.iter().any(|r| tree::glob_match(&r.pattern, path))
+    }
+
+    /// Violations of every rule matching `path` by `value`
+    pub fn validate(&self, path: &str, value: &str) -> Vec<SchemaViolation> {
+        let matching: Vec<&CompiledRule> = self.rules.iter().filter(|r| tree::glob_match(&r.pattern, path)).collect();
+        if matching.is_empty() {
+            return Vec::new();
+        }
+        let instance: Value = match serde_json::from_str(value) {
+            Ok(instance) => instance,
+            Err(e) => {
+                return matching
+                    .iter()
+                    .map(|rule| SchemaViolation {
+                        path: path.to_string(),
+                        schema: rule.schema_name.clone(),
+                        pointer: String::new(),
+                        message: format!("Value is not valid JSON: {}", e),
+                    })
+                    .collect()
+            }
+        };
+        matching
+            .iter()
+            .flat_map(|rule| {
+                rule.validator.iter_errors(&instance).map(|error| SchemaViolation {
+                    path: path.to_string(),
+                    schema: rule.schema_name.clone(),
+                    pointer: error.instance_path.to_string(),
+                    message: error.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Validate every value of a tree that some rule applies to
+    pub fn validate_tree(&self, commit: &str, root: &IrminNode) -> ValidationReport {
+        let mut report = ValidationReport {
+            commit: commit.to_string(),
+            keys_checked: 0,
+            violations: Vec::new(),
+        };
+        for (path, value) in tree::file_values(root) {
+            if self.covers(&path) {
+                report.keys_checked += 1;
+                report.violations.extend(self.validate(&path, &value));
+            }
+        }
+        report
+    }
+
+    /// Violations introduced by the added and modified values of a commit
+    pub fn validate_changes(&self, changes: &[DiffChange]) -> Vec<SchemaViolation> {
+        changes
+            .iter()
+            .filter(|c| c.change_type != ChangeType::Deleted)
+            .flat_map(|c| self.validate(&c.path, c.new_value.as_deref().unwrap_or_default()))
+            .collect()
+    }
+}
+
+fn schema_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::{fixtures::FixtureStore, project::SchemaRule};
+
+    fn user_rules() -> (SchemaRules, std::path::PathBuf) {
+        let schema = std::env::temp_dir().join(format!("user-{}.schema.json", uuid::Uuid::new_v4()));
+        let text = serde_json::json!({
+            "type": "object",
+            "required": ["name", "email", "role"],
+            "properties": {"role": {"enum": ["admin", "user"]}}
+        });
+        std::fs::write(&schema, text.to_string()).unwrap();
+        let config = ProjectConfig {
+            schemas: vec![SchemaRule {
+                pattern: "users/*.json".to_string(),
+                schema: schema.clone(),
+            }],
+        };
+        (SchemaRules::from_config(&config).unwrap(), schema)
+    }
+
+    #[test]
+    fn test_validate_tree_reports_violations_per_key() {
+        let (rules, schema) = user_rules();
+        let mut tree = FixtureStore::load("sample").unwrap().tree().unwrap();
+        let bob = tree.children.get_mut("users").unwrap().children.get_mut("bob.json").unwrap();
+        bob.value = Some(r#"{"name": "Bob", "email": "bob@example.com", "role": "owner"}"#.to_string());
+
+        let report = rules.validate_tree("c1", &tree);
+        assert_eq!(report.keys_checked, 3);
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.violations[0].path, "/users/bob.json");
+        assert_eq!(report.violations[0].pointer, "/role");
+        std::fs::remove_file(schema).unwrap();
+    }
+
+    #[test]
+    fn test_validate_changes_ignores_deletes_and_other_paths() {
+        let (rules, schema) = user_rules();
+        let change = |path: &str, change_type, value: Option<&str>| DiffChange {
+            path: path.to_string(),
+            change_type,
+            old_value: None,
+            new_value: value.map(str::to_string),
+        };
+        let violations = rules.validate_changes(&[
+            change("/users/dave.json", ChangeType::Added, Some("{\"name\": \"Dave\"}")),
+            change("/users/erin.json", ChangeType::Added, Some("not json")),
+            change("/users/bob.json", ChangeType::Deleted, None),
+            change("/config.json", ChangeType::Modified, Some("{}")),
+        ]);
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["/users/dave.json", "/users/dave.json", "/users/erin.json"]);
+        assert!(violations[2].message.contains("not valid JSON"));
+        std::fs::remove_file(schema).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Live refresh when the store changes outside IrminView.
+//!
+//! A [`Watcher`] reports a [`BranchUpdate`] whenever a branch head moves, is
+//! created or is deleted. With the bridge CLI it watches the store's refs
+//! directory for filesystem notifications and re-reads the branches once
+//! they settle; if notifications cannot be set up it polls instead. With the
+//! HTTP backend it polls the branch list. Snapshots and fixture scenarios
+//! never change and are not watched.
+//!
+//! Cached responses that depend on branch heads are dropped before updates
+//! are reported, so views reloading on an update see the new heads.
+
+use super::integration::{self, IrminConfig};
+use super::types::{BranchUpdate, IrminBranch, IrminCommit};
+use anyhow::Result;
+use notify::{RecursiveMode, Watcher as _};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How often branch heads are polled when there are no notifications
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to wait for a burst of filesystem events to settle
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches a store for branch changes
+pub struct Watcher {
+    config: IrminConfig,
+    poll_interval: Duration,
+    debounce: Duration,
+}
+
+/// A running watcher; stops when dropped
+pub struct WatchHandle {
+    task: tokio::task::JoinHandle<()>,
+    _notifications: Option<notify::RecommendedWatcher>,
+    polling: bool,
+}
+
+impl WatchHandle {
+    /// Whether branch heads are polled rather than watched
+    pub fn is_polling(&self) -> bool {
+        self.polling
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Watcher {
+    pub fn new(config: IrminConfig) -> Self {
+        Self {
+            config: config.without_cache(),
+            poll_interval: POLL_INTERVAL,
+            debounce: DEBOUNCE,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Directory of a local store whose changes mean a branch may have moved:
+    /// the refs of a Git store, or the store directory itself
+    pub fn refs_dir(&self) -> Option<PathBuf> {
+        if self.config.use_http {
+            return None;
+        }
+        let store = Path::new(&self.config.store_path);
+        [".git/refs", "refs"]
+            .iter()
+            .map(|refs| store.join(refs))
+            .find(|refs| refs.is_dir())
+            .or_else(|| store.is_dir().then(|| store.to_path_buf()))
+    }
+
+    /// Read the current branch heads and report changes to them to
+    /// `on_update` until the returned handle is dropped
+    pub async fn start<F>(self, on_update: F) -> Result<WatchHandle>
+    where
+        F: Fn(BranchUpdate) + Send + Sync + 'static,
+    {
+        if let Some(path) = &self.config.snapshot {
+            anyhow::bail!("Snapshot {} does not change", path);
+        }
+        if let Some(fixtures) = &self.config.fixtures {
+            anyhow::bail!("Fixture scenario '{}' does not change", fixtures);
+        }
+        let mut heads = integration::get_irmin_branches(&self.config).await?;
+
+        let (changed, mut notified) = mpsc::unbounded_channel();
+        let notifications = match self.refs_dir() {
+            Some(dir) => match watch_dir(&dir, changed) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    eprintln!("Cannot watch {} for changes: {}. Polling instead.", dir.display(), e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let polling = notifications.is_none();
+
+        let Watcher { config, poll_interval, debounce } = self;
+        let task = tokio::spawn(async move {
+            loop {
+                if polling {
+                    tokio::time::sleep(poll_interval).await;
+                } else {
+                    if notified.recv().await.is_none() {
+                        return;
+                    }
+                    tokio::time::sleep(debounce).await;
+                    while notified.try_recv().is_ok() {}
+                }
+
+                let branches = match integration::get_irmin_branches(&config).await {
+                    Ok(branches) => branches,
+                    Err(e) => {
+                        eprintln!("Failed to check branches for changes: {}", e);
+                        continue;
+                    }
+                };
+                let mut updates = changes(&heads, &branches);
+                heads = branches;
+                if updates.is_empty() {
+                    continue;
+                }
+                integration::invalidate_cached_heads(&config);
+                match integration::get_irmin_commits(&config).await {
+                    Ok(commits) => {
+                        for update in &mut updates {
+                            if let Some(new_head) = &update.new_head {
+                                update.new_commits = count_new_commits(&commits, update.old_head.as_deref(), new_head);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to count new commits: {}", e),
+                }
+                for update in updates {
+                    on_update(update);
+                }
+            }
+        });
+
+        Ok(WatchHandle {
+            task,
+            _notifications: notifications,
+            polling,
+        })
+    }
+}
+
+/// Signal `changed` on every modification below `dir`
+fn watch_dir(dir: &Path, changed: mpsc::UnboundedSender<()>) -> notify::Result<notify::RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|event| !event.kind.is_access()) {
+            let _ = changed.send(());
+        }
+    })?;
+    watcher.watch(dir, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// Branches whose heads differ between `before` and `after`, without commit
+/// counts
+pub fn changes(before: &[IrminBranch], after: &[IrminBranch]) -> Vec<BranchUpdate> {
+    let old_heads: HashMap<&str, &str> = before.iter().map(|b| (b.name.as_str(), b.head_commit.as_str())).collect();
+    let new_names: HashSet<&str> = after.iter().map(|b| b.name.as_str()).collect();
+
+    let moved = after
+        .iter()
+        .filter(|b| old_heads.get(b.name.as_str()) != Some(&b.head_commit.as_str()))
+        .map(|b| BranchUpdate {
+            branch: b.name.clone(),
+            old_head: old_heads.get(b.name.as_str()).map(|h| h.to_string()),
+            new_head: Some(b.head_commit.clone()),
+            new_commits: 0,
+        });
+    let deleted = before
+        .iter()
+        .filter(|b| !new_names.contains(b.name.as_str()))
+        .map(|b| BranchUpdate {
+            branch: b.name.clone(),
+            old_head: Some(b.head_commit.clone()),
+            new_head: None,
+            new_commits: 0,
+        });
+    moved.chain(deleted).collect()
+}
+
+/// Commits reachable from `new_head` but not from `old_head`
+pub fn count_new_commits(commits: &[IrminCommit], old_head: Option<&str>, new_head: &str) -> usize {
+    let parents: HashMap<&str, &[String]> = commits.iter().map(|c| (c.hash.as_str(), c.parents.as_slice())).collect();
+    let old = old_head
+        .map(|head| ancestors(&parents, head, &HashSet::new()))
+        .unwrap_or_default();
+    ancestors(&parents, new_head, &old).len()
+}
+
+/// `head` and its ancestors, not going past commits in `known`
+fn ancestors<'a>(parents: &HashMap<&'a str, &'a [String]>, head: &'a str, known: &HashSet<&'a str>) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([head]);
+    while let Some(hash) = queue.pop_front() {
+        let Some(hash_parents) = parents.get(hash) else { continue };
+        if known.contains(hash) || !seen.insert(hash) {
+            continue;
+        }
+        queue.extend(hash_parents.iter().map(String::as_str));
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn branch(name: &str, head: &str) -> IrminBranch {
+        IrminBranch {
+            name: name.to_string(),
+            head_commit: head.to_string(),
+            last_updated: Utc::now(),
+            commit_count: 0,
+        }
+    }
+
+    fn commit(hash: &str, parents: &[&str]) -> IrminCommit {
+        IrminCommit {
+            hash: hash.to_string(),
+            message: String::new(),
+            author: String::new(),
+            timestamp: Utc::now(),
+            parents: parents.iter().map(|p| p.to_string()).collect(),
+            branch: "main".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_changes_between_branch_lists() {
+        let before = [branch("main", "a"), branch("old", "x"), branch("same", "s")];
+        let after = [branch("main", "b"), branch("new", "n"), branch("same", "s")];
+        let updates = changes(&before, &after);
+        let summary: Vec<(&str, Option<&str>, Option<&str>)> = updates
+            .iter()
+            .map(|u| (u.branch.as_str(), u.old_head.as_deref(), u.new_head.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("main", Some("a"), Some("b")), ("new", None, Some("n")), ("old", Some("x"), None)]
+        );
+    }
+
+    #[test]
+    fn test_count_new_commits_stops_at_old_head() {
+        // a <- b <- c <- m, with m also merging d <- a
+        let commits = [
+            commit("m", &["c", "d"]),
+            commit("d", &["a"]),
+            commit("c", &["b"]),
+            commit("b", &["a"]),
+            commit("a", &[]),
+        ];
+        assert_eq!(count_new_commits(&commits, Some("b"), "m"), 3);
+        assert_eq!(count_new_commits(&commits, Some("m"), "m"), 0);
+        assert_eq!(count_new_commits(&commits, None, "c"), 3);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refs_changes_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A store with a refs directory, and a bridge reading branches from a file
+        let store = std::env::temp_dir().join(format!("irmin-watch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(store.join("refs/heads")).unwrap();
+        let branches = store.join("branches.json");
+        let write_branches = |head: &str| {
+            std::fs::write(&branches, serde_json::to_string(&[branch("main", head)]).unwrap()).unwrap();
+            std::fs::write(store.join("refs/heads/main"), head).unwrap();
+        };
+        write_branches("a");
+        let bridge = store.join("bridge.sh");
+        let script = format!("#!/bin/sh\ncase \"$1\" in\n  branches) cat {} ;;\n  *) echo '[]' ;;\nesac\n", branches.display());
+        std::fs::write(&bridge, script).unwrap();
+        std::fs::set_permissions(&bridge, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = IrminConfig::new().with_path(store.display().to_string());
+        config.use_http = false;
+        config.fixtures = None;
+        config.snapshot = None;
+        config.bridge_executable = bridge.display().to_string();
+
+        let (sender, mut updates) = mpsc::unbounded_channel();
+        let watcher = Watcher::new(config).with_debounce(Duration::from_millis(20));
+        assert_eq!(watcher.refs_dir(), Some(store.join("refs")));
+        let handle = watcher
+            .start(move |update| {
+                let _ = sender.send(update);
+            })
+            .await
+            .unwrap();
+        assert!(!handle.is_polling());
+
+        write_branches("b");
+        let update = tokio::time::timeout(Duration::from_secs(10), updates.recv()).await.unwrap().unwrap();
+        assert_eq!(update.branch, "main");
+        assert_eq!((update.old_head.as_deref(), update.new_head.as_deref()), (Some("a"), Some("b")));
+        drop(handle);
+        std::fs::remove_dir_all(store).unwrap();
+    }
+}
@@ -0,0 +1,453 @@
+//! Decoding of bridge output (CLI and HTTP server) into the Rust types.
+//!
+//! The bridge does not emit exactly what the derived deserializers expect:
+//! commit timestamps are Irmin's Int64 epoch seconds as a string, and node
+//! types are plain strings. The field helpers here are attached to the types
+//! with `deserialize_with` and accept every form seen so far:
+//!
+//! - timestamps as RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC), or epoch seconds
+//!   or milliseconds, as a number or a string
+//! - node and change types in any case, with Irmin's names (`contents`,
+//!   `tree`) as aliases; unknown node types are read as files
+//! - nodes without `children` or `metadata`, and metadata without dates
+//!
+//! Serialization is unchanged, so everything the app writes is RFC 3339.
+//!
+//! Responses are either bare payloads (wire version 1, what the bridge emits
+//! today) or wrapped as `{"wire_version": n, "data": ...}`. [`decode`]
+//! unwraps both and rejects versions newer than [`WIRE_VERSION`] with an
+//! error naming the version, instead of a parse error on some field.
+//!
+//! The same leniency is described by JSON Schemas generated from the types
+//! ([`schemas`], written out by `irmin-view-cli schema`). Debug builds check
+//! every response against them and print the path of each mismatch, so
+//! drift between the bridge and the types shows up before a field fails to
+//! decode.
+//!
+//! Large trees can be transferred as NDJSON, one flat [`TreeEntry`] per line,
+//! decoded as chunks arrive by [`NdjsonDecoder`]. A stream may start with a
+//! `{"wire_version": n}` line, and a `{"error": "..."}` line ends it with
+//! that error.
+
+use super::types::*;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::de::{DeserializeOwned, Deserializer, Error};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Newest wire format this build understands
+pub const WIRE_VERSION: u64 = 1;
+
+/// Epoch values at or above this are milliseconds (in seconds it would be
+/// the year 5138)
+const MILLIS_THRESHOLD: u64 = 100_000_000_000;
+
+/// Decode a bridge response
+pub fn decode<T: DeserializeOwned + JsonSchema>(json: &str) -> Result<T> {
+    let value: Value = serde_json::from_str(json)
+        .with_context(|| format!("Bridge returned invalid JSON for {}", type_name::<T>()))?;
+    decode_value(value)
+}
+
+/// Decode an already parsed bridge response
+pub fn decode_value<T: DeserializeOwned + JsonSchema>(value: Value) -> Result<T> {
+    let payload = match value {
+        Value::Object(mut envelope) if envelope.contains_key("wire_version") => {
+            let version = envelope["wire_version"]
+                .as_u64()
+                .ok_or_else(|| anyhow!("Invalid wire_version {}", envelope["wire_version"]))?;
+            if version > WIRE_VERSION {
+                bail!(
+                    "Bridge wire format version {} is newer than this IrminView supports ({})",
+                    version,
+                    WIRE_VERSION
+                );
+            }
+            envelope
+                .remove("data")
+                .ok_or_else(|| anyhow!("Wire format envelope without data"))?
+        }
+        value => value,
+    };
+    if cfg!(debug_assertions) {
+        let violations = validate::<T>(&payload);
+        if !violations.is_empty() {
+            eprintln!(
+                "Bridge response does not match the {} schema:\n  {}",
+                type_name::<T>(),
+                violations.join("\n  ")
+            );
+        }
+    }
+    serde_json::from_value(payload).with_context(|| format!("Unexpected {} from the bridge", type_name::<T>()))
+}
+
+/// Incremental decoder of newline-delimited JSON, fed with chunks of a body
+pub struct NdjsonDecoder<T> {
+    buffer: Vec<u8>,
+    line: usize,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Default for NdjsonDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> NdjsonDecoder<T> {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            line: 0,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Decode the lines completed by `chunk`, keeping a partial last line
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<T>> {
+        self.buffer.extend_from_slice(chunk);
+        let Some(end) = self.buffer.iter().rposition(|b| *b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.buffer.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.buffer, rest);
+        complete
+            .split(|b| *b == b'\n')
+            .filter_map(|line| self.decode_line(line).transpose())
+            .collect()
+    }
+
+    /// Decode what is left once the body has ended
+    pub fn finish(mut self) -> Result<Vec<T>> {
+        let rest = std::mem::take(&mut self.buffer);
+        Ok(self.decode_line(&rest)?.into_iter().collect())
+    }
+
+    fn decode_line(&mut self, line: &[u8]) -> Result<Option<T>> {
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        self.line += 1;
+        let value: Value = serde_json::from_slice(line)
+            .with_context(|| format!("Invalid JSON on line {} of the stream", self.line))?;
+        if let Value::Object(fields) = &value {
+            if let Some(error) = fields.get("error").and_then(Value::as_str) {
+                bail!("Server error: {}", error);
+            }
+            if fields.len() == 1 {
+                if let Some(version) = fields.get("wire_version") {
+                    match version.as_u64() {
+                        Some(version) if version <= WIRE_VERSION => return Ok(None),
+                        _ => bail!(
+                            "Bridge wire format version {} is newer than this IrminView supports ({})",
+                            version,
+                            WIRE_VERSION
+                        ),
+                    }
+                }
+            }
+        }
+        serde_json::from_value(value)
+            .map(Some)
+            .with_context(|| format!("Unexpected {} on line {} of the stream", type_name::<T>(), self.line))
+    }
+}
+
+/// JSON Schema of the wire form of `T`
+pub fn schema<T: JsonSchema>() -> Value {
+    schemars::schema_for!(T).to_value()
+}
+
+/// Schemas of the types exchanged with the bridge, by type name
+pub fn schemas() -> Vec<(&'static str, Value)> {
+    vec![
+        ("IrminNode", schema::<IrminNode>()),
+        ("IrminCommit", schema::<IrminCommit>()),
+        ("IrminBranch", schema::<IrminBranch>()),
+        ("IrminDiff", schema::<IrminDiff>()),
+        ("SearchResult", schema::<SearchResult>()),
+        ("CommitRequest", schema::<CommitRequest>()),
+        ("CommitResult", schema::<CommitResult>()),
+        ("TreeEntry", schema::<TreeEntry>()),
+    ]
+}
+
+/// Write `<type>.schema.json` for each of [`schemas`] into `dir`
+pub fn write_schemas(dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    schemas()
+        .into_iter()
+        .map(|(name, schema)| {
+            let path = dir.join(format!("{}.schema.json", name));
+            std::fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Where `value` does not match the schema of `T`, as `<pointer>: <message>`
+pub fn validate<T: JsonSchema>(value: &Value) -> Vec<String> {
+    static VALIDATORS: OnceLock<Mutex<HashMap<String, Arc<jsonschema::Validator>>>> = OnceLock::new();
+    let name = type_name::<T>();
+    let validator = {
+        let mut validators = VALIDATORS.get_or_init(Default::default).lock().expect("validator lock poisoned");
+        match validators.get(&name) {
+            Some(validator) => validator.clone(),
+            None => {
+                let validator = match jsonschema::options().should_validate_formats(true).build(&schema::<T>()) {
+                    Ok(validator) => Arc::new(validator),
+                    Err(e) => return vec![format!("Invalid schema for {}: {}", name, e)],
+                };
+                validators.insert(name, validator.clone());
+                validator
+            }
+        }
+    };
+    validator
+        .iter_errors(value)
+        .map(|error| {
+            let pointer = error.instance_path.to_string();
+            format!("{}: {}", if pointer.is_empty() { "/" } else { &pointer }, error)
+        })
+        .collect()
+}
+
+const TIMESTAMP_FORMS: &str = "RFC 3339, or epoch seconds or milliseconds as a number or string";
+
+pub fn timestamp_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "description": TIMESTAMP_FORMS,
+        "anyOf": [
+            { "type": "string", "format": "date-time" },
+            { "type": "string", "pattern": "^\\s*-?[0-9]+(\\.[0-9]+)?\\s*$" },
+            { "type": "string", "pattern": "^[0-9]{4}-[0-9]{2}-[0-9]{2}[ T][0-9]{2}:[0-9]{2}:[0-9]{2}(\\.[0-9]+)?$" },
+            { "type": "number" }
+        ]
+    })
+}
+
+pub fn optional_timestamp_schema(generator: &mut SchemaGenerator) -> Schema {
+    let mut schema = timestamp_schema(generator);
+    if let Some(Value::Array(forms)) = schema.get_mut("anyOf") {
+        forms.push(serde_json::json!({ "type": "null" }));
+    }
+    schema
+}
+
+pub fn node_type_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "description": "Directory, File or Link in any case, or Irmin's tree or contents; other names are read as File",
+        "type": "string"
+    })
+}
+
+pub fn change_type_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "enum": [
+            "Added", "Modified", "Deleted",
+            "added", "modified", "deleted",
+            "add", "updated", "changed", "removed", "delete", "remove"
+        ]
+    })
+}
+
+/// Type name without module paths, e.g. `Vec<IrminCommit>`
+fn type_name<T>() -> String {
+    let mut name = String::new();
+    let mut segment = String::new();
+    for c in std::any::type_name::<T>().chars().chain(std::iter::once(' ')) {
+        if matches!(c, '<' | '>' | ',' | ' ') {
+            name.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            name.push(c);
+        } else {
+            segment.push(c);
+        }
+    }
+    name.trim_end().to_string()
+}
+
+/// Parse a timestamp in any of the accepted forms
+pub fn parse_timestamp(value: &Value) -> Result<DateTime<Utc>, String> {
+    match value {
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                from_epoch(i)
+            } else {
+                let secs = n.as_f64().ok_or_else(|| format!("invalid timestamp {}", n))?;
+                Utc.timestamp_millis_opt((secs * 1000.0).round() as i64)
+                    .single()
+                    .ok_or_else(|| format!("timestamp {} out of range", n))
+            }
+        }
+        Value::String(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i64>() {
+                return from_epoch(i);
+            }
+            if let Ok(secs) = s.parse::<f64>() {
+                return parse_timestamp(&Value::from(secs));
+            }
+            if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+                return Ok(t.with_timezone(&Utc));
+            }
+            for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+                if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+                    return Ok(t.and_utc());
+                }
+            }
+            Err(format!("unrecognized timestamp '{}'", s))
+        }
+        other => Err(format!("expected a timestamp, found {}", other)),
+    }
+}
+
+fn from_epoch(value: i64) -> Result<DateTime<Utc>, String> {
+    let parsed = if value.unsigned_abs() >= MILLIS_THRESHOLD {
+        Utc.timestamp_millis_opt(value)
+    } else {
+        Utc.timestamp_opt(value, 0)
+    };
+    parsed.single().ok_or_else(|| format!("timestamp {} out of range", value))
+}
+
+pub fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    parse_timestamp(&Value::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Like [`timestamp`], with `null` read as the epoch
+pub fn timestamp_or_default<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(DateTime::default()),
+        value => parse_timestamp(&value).map_err(D::Error::custom),
+    }
+}
+
+/// Read `null` as the type's default
+pub fn or_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+pub fn node_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NodeType, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Ok(match name.to_ascii_lowercase().as_str() {
+        "directory" | "dir" | "tree" | "node" => NodeType::Directory,
+        "link" | "symlink" => NodeType::Link,
+        _ => NodeType::File,
+    })
+}
+
+pub fn change_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ChangeType, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.to_ascii_lowercase().as_str() {
+        "added" | "add" => Ok(ChangeType::Added),
+        "modified" | "updated" | "changed" => Ok(ChangeType::Modified),
+        "deleted" | "removed" | "delete" | "remove" => Ok(ChangeType::Deleted),
+        _ => Err(D::Error::custom(format!("unknown change type '{}'", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_timestamps_in_every_form() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 15, 10, 25, 0).unwrap();
+        for value in [
+            json!(1705314300),
+            json!("1705314300"),
+            json!(1705314300000i64),
+            json!("1705314300000"),
+            json!(1705314300.0),
+            json!("2024-01-15T10:25:00Z"),
+            json!("2024-01-15T12:25:00+02:00"),
+            json!("2024-01-15 10:25:00"),
+        ] {
+            assert_eq!(parse_timestamp(&value), Ok(expected), "{}", value);
+        }
+        assert!(parse_timestamp(&json!("yesterday")).is_err());
+        assert!(parse_timestamp(&json!(null)).is_err());
+        assert!(parse_timestamp(&json!(i64::MIN)).is_err());
+        assert!(parse_timestamp(&json!(i64::MIN.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_tolerates_missing_metadata_and_unknown_node_types() {
+        let node: IrminNode = decode(
+            r#"{"key": "/", "value": null, "node_type": "tree", "children": {
+                "a": {"key": "a", "value": "x", "node_type": "contents"},
+                "b": {"key": "b", "value": null, "node_type": "Commit", "children": null,
+                      "metadata": {"last_modified": null, "size": null, "permissions": null}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(node.node_type, NodeType::Directory);
+        assert_eq!(node.children["a"].node_type, NodeType::File);
+        assert_eq!(node.children["b"].node_type, NodeType::File);
+        assert_eq!(node.metadata.last_modified, DateTime::<Utc>::default());
+    }
+
+    #[test]
+    fn test_schema_reports_paths() {
+        let value = serde_json::json!([
+            { "hash": "a1", "message": "m", "author": "x", "timestamp": "1705314300", "branch": "main" },
+            { "hash": "b2", "message": "m", "author": "x", "timestamp": "soon", "branch": "main" },
+        ]);
+        let violations = validate::<Vec<IrminCommit>>(&value);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert!(violations[0].starts_with("/1/timestamp: "), "{}", violations[0]);
+
+        let names: Vec<&str> = schemas().iter().map(|(name, _)| *name).collect();
+        assert!(names.contains(&"IrminNode") && names.contains(&"CommitRequest"));
+    }
+
+    #[test]
+    fn test_ndjson_across_chunk_boundaries() {
+        let body = "{\"wire_version\":1}\n{\"path\":\"/a\",\"type\":\"Directory\",\"size\":null,\"hash\":\"h1\"}\n\
+                    {\"path\":\"/a/b\",\"type\":\"contents\",\"size\":3,\"hash\":\"h2\"}";
+        let mut decoder = NdjsonDecoder::<TreeEntry>::new();
+        let mut entries = Vec::new();
+        for chunk in body.as_bytes().chunks(7) {
+            entries.extend(decoder.push(chunk).unwrap());
+        }
+        entries.extend(decoder.finish().unwrap());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].node_type, NodeType::File);
+        assert_eq!(entries[1].size, Some(3));
+
+        let mut decoder = NdjsonDecoder::<TreeEntry>::new();
+        let error = decoder.push(b"{\"error\":\"Unknown commit: abc\"}\n").unwrap_err();
+        assert!(error.to_string().contains("Unknown commit: abc"));
+        let mut decoder = NdjsonDecoder::<TreeEntry>::new();
+        assert_eq!(decoder.push(b"{\"path\":\"/a\",\"type\":\"File\"}\n{\"pa").unwrap().len(), 1);
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn test_versioned_envelopes() {
+        let commits: Vec<IrminCommit> = decode(
+            r#"{"wire_version": 1, "data": [{"hash": "a1", "message": "m", "author": "x",
+                "timestamp": "1705314300", "parents": [], "branch": "main"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(commits[0].timestamp.timestamp(), 1705314300);
+
+        let error = decode::<Vec<IrminCommit>>(r#"{"wire_version": 2, "data": []}"#).unwrap_err();
+        assert!(error.to_string().contains("version 2"));
+        let error = decode::<Vec<IrminCommit>>("{}").unwrap_err();
+        assert!(error.to_string().contains("Vec<IrminCommit>"), "{}", error);
+    }
+}
@@ -1,2 +1,3 @@
 pub mod irmin;
-pub mod ui;
\ No newline at end of file
+pub mod ui;
+pub mod web;
\ No newline at end of file
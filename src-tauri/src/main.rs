@@ -4,7 +4,8 @@
 mod irmin;
 mod ui;
 
-use tauri::Manager;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
 
 fn main() {
     tauri::Builder::default()
@@ -13,15 +14,42 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             irmin::commands::get_tree,
+            irmin::commands::stream_tree,
             irmin::commands::get_commits,
             irmin::commands::get_branches,
             irmin::commands::get_commit_diff,
             irmin::commands::search_keys,
+            irmin::commands::build_search_index,
+            irmin::commands::filter_tree,
+            irmin::commands::query_values,
+            irmin::commands::search_history,
+            irmin::commands::store_stats,
+            irmin::commands::render_value,
+            irmin::commands::validate_store,
+            irmin::commands::export_tree,
+            irmin::commands::import_tree,
+            irmin::commands::export_commits,
+            irmin::commands::export_patch,
+            irmin::commands::apply_patch,
             irmin::commands::connect_to_irmin_store,
+            irmin::commands::create_snapshot,
+            irmin::commands::open_snapshot,
             irmin::commands::check_irmin_availability,
+            irmin::commands::cancel_operation,
+            irmin::commands::watch_store,
+            irmin::commands::cache_stats,
+            irmin::commands::refresh_heads,
+            irmin::commands::clear_cache,
             ui::commands::toggle_theme
         ])
         .setup(|app| {
+            let handle = app.handle().clone();
+            irmin::progress::set_sink(Arc::new(move |progress: &irmin::types::OperationProgress| {
+                if let Err(e) = handle.emit("operation-progress", progress) {
+                    eprintln!("Failed to emit operation progress: {}", e);
+                }
+            }));
+
             #[cfg(debug_assertions)]
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -1,11 +1,12 @@
 use crate::irmin::types::Theme;
+#[cfg(feature = "desktop")]
 use tauri::command;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static DARK_MODE: AtomicBool = AtomicBool::new(false);
 
 /// Toggle between light and dark theme
-#[command]
+#[cfg_attr(feature = "desktop", command)]
 pub async fn toggle_theme() -> Result<Theme, String> {
     let is_dark = DARK_MODE.load(Ordering::Relaxed);
     let new_theme = if is_dark {
@@ -20,7 +21,7 @@ pub async fn toggle_theme() -> Result<Theme, String> {
 }
 
 /// Get current theme
-#[command]
+#[cfg_attr(feature = "desktop", command)]
 pub async fn get_current_theme() -> Result<Theme, String> {
     let is_dark = DARK_MODE.load(Ordering::Relaxed);
     Ok(if is_dark { Theme::Dark } else { Theme::Light })
@@ -0,0 +1,569 @@
+//! Web mode: serves `frontend/dist` to a plain browser and exposes every
+//! Tauri command as `POST /invoke/<command>`.
+//!
+//! The request body is a JSON object of the command's arguments, with the
+//! names `invoke` uses (snake_case or camelCase). A successful command
+//! answers its result as JSON; a failing one answers its error string with a
+//! non-2xx status. `index.html` is served with a shim that provides
+//! `window.__TAURI__.core.invoke` on top of these endpoints, so `app.js`
+//! runs unchanged.
+//!
+//! The two commands that keep sending after they are invoked answer NDJSON
+//! instead: `stream_tree` one `TreeBatch` per line, and `watch_store` a
+//! `{"polling": bool}` line followed by one `BranchUpdate` per line for as
+//! long as the client stays connected. A failure after the first line is
+//! sent as an `{"error": message}` line. Only `STREAM_LINES` lines are
+//! buffered per client: a command producing lines faster than the client
+//! reads them waits for it.
+//!
+//! In read-only mode (the default) commands that commit to the store, write
+//! files on the server or change which store is served are rejected, and
+//! `search_keys` searches the store directly instead of saving an index.
+
+use crate::irmin::{commands, integration::IrminConfig, search_index::SearchIndexStore, watcher};
+use crate::ui;
+use anyhow::Context;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::StreamExt;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tower_http::services::ServeDir;
+
+/// Address the server listens on when none is configured
+pub const DEFAULT_BIND: &str = "127.0.0.1:8090";
+
+const SHIM: &str = include_str!("shim.js");
+const SHIM_PATH: &str = "/irmin-view-web.js";
+
+/// Lines of a streaming response buffered ahead of a slow client
+const STREAM_LINES: usize = 16;
+
+/// Commands rejected in read-only mode
+const WRITE_COMMANDS: &[&str] = &[
+    "import_tree",
+    "apply_patch",
+    "build_search_index",
+    "export_tree",
+    "export_commits",
+    "create_snapshot",
+    "open_snapshot",
+    "connect_to_irmin_store",
+    "clear_cache",
+];
+
+/// Configuration of the web server
+#[derive(Debug, Clone)]
+pub struct WebConfig {
+    pub bind: SocketAddr,
+    pub dist_dir: PathBuf,
+    /// Where search indexes are kept (the desktop app uses its data dir).
+    /// Unused in read-only mode.
+    pub data_dir: PathBuf,
+    pub read_only: bool,
+}
+
+impl WebConfig {
+    /// Read-only server on `DEFAULT_BIND` serving the default frontend
+    pub fn new() -> Self {
+        Self {
+            bind: DEFAULT_BIND.parse().expect("default bind address is valid"),
+            dist_dir: default_dist_dir(),
+            data_dir: PathBuf::from("./irmin-view-data"),
+            read_only: true,
+        }
+    }
+
+    /// Configuration from `IRMIN_WEB_BIND`, `IRMIN_WEB_DIST` and
+    /// `IRMIN_WEB_READ_ONLY`, over the defaults of `new`
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::new();
+        if let Some(bind) = env::var("IRMIN_WEB_BIND").ok().filter(|b| !b.is_empty()) {
+            config.bind = bind
+                .parse()
+                .with_context(|| format!("IRMIN_WEB_BIND '{}' is not an address like {}", bind, DEFAULT_BIND))?;
+        }
+        if let Some(dist) = env::var("IRMIN_WEB_DIST").ok().filter(|d| !d.is_empty()) {
+            config.dist_dir = PathBuf::from(dist);
+        }
+        config.read_only = env::var("IRMIN_WEB_READ_ONLY").unwrap_or_default() != "false";
+        Ok(config)
+    }
+
+    pub fn with_bind(mut self, bind: SocketAddr) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    pub fn with_dist_dir(mut self, dist_dir: PathBuf) -> Self {
+        self.dist_dir = dist_dir;
+        self
+    }
+
+    pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
+        self.data_dir = data_dir;
+        self
+    }
+
+    pub fn allow_writes(mut self) -> Self {
+        self.read_only = false;
+        self
+    }
+
+    fn search_index_store(&self) -> SearchIndexStore {
+        SearchIndexStore::new(self.data_dir.join("search-index"))
+    }
+
+    /// Index store for `search_keys`, which would write to it
+    fn writable_search_index_store(&self) -> Option<SearchIndexStore> {
+        (!self.read_only).then(|| self.search_index_store())
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `frontend/dist` beside the executable when it was installed with one,
+/// otherwise the one of the source tree it was built from
+fn default_dist_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("frontend/dist")))
+        .filter(|dist| dist.is_dir())
+        .unwrap_or_else(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend/dist")))
+}
+
+/// Why an invocation did not produce a result
+#[derive(Debug, PartialEq)]
+pub enum InvokeError {
+    UnknownCommand(String),
+    /// The command streams its results and has no single reply
+    Streaming(String),
+    ReadOnly(String),
+    InvalidArguments(String),
+    /// The command ran and returned an error
+    Failed(String),
+}
+
+impl InvokeError {
+    fn status(&self) -> StatusCode {
+        match self {
+            InvokeError::UnknownCommand(_) => StatusCode::NOT_FOUND,
+            InvokeError::Streaming(_) => StatusCode::BAD_REQUEST,
+            InvokeError::ReadOnly(_) => StatusCode::FORBIDDEN,
+            InvokeError::InvalidArguments(_) => StatusCode::BAD_REQUEST,
+            InvokeError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            InvokeError::UnknownCommand(command) => format!("Unknown command '{}'", command),
+            InvokeError::Streaming(command) => format!("'{}' streams its results as NDJSON (see `invoke_stream`)", command),
+            InvokeError::ReadOnly(command) => format!("'{}' is not allowed: the server is read-only", command),
+            InvokeError::InvalidArguments(message) | InvokeError::Failed(message) => message.clone(),
+        }
+    }
+}
+
+/// Command arguments keyed by their snake_case name
+struct Args(Map<String, Value>);
+
+impl Args {
+    fn parse(body: &[u8]) -> Result<Self, InvokeError> {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Args(Map::new()));
+        }
+        match serde_json::from_slice(body) {
+            Ok(Value::Object(map)) => Ok(Args(map.into_iter().map(|(k, v)| (snake_case(&k), v)).collect())),
+            Ok(Value::Null) => Ok(Args(Map::new())),
+            Ok(_) => Err(InvokeError::InvalidArguments("Arguments must be a JSON object".to_string())),
+            Err(e) => Err(InvokeError::InvalidArguments(format!("Invalid JSON: {}", e))),
+        }
+    }
+
+    /// Argument `name`; missing arguments are `null`, which `Option`s accept
+    fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, InvokeError> {
+        let value = self.0.get(name).cloned().unwrap_or(Value::Null);
+        serde_json::from_value(value).map_err(|e| InvokeError::InvalidArguments(format!("Argument '{}': {}", name, e)))
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn reply<T: Serialize>(result: Result<T, String>) -> Result<Value, InvokeError> {
+    let value = result.map_err(InvokeError::Failed)?;
+    serde_json::to_value(value).map_err(|e| InvokeError::Failed(e.to_string()))
+}
+
+/// Run a command as the desktop app's `invoke` would
+pub async fn invoke(config: &WebConfig, command: &str, body: &[u8]) -> Result<Value, InvokeError> {
+    let args = Args::parse(body)?;
+    let writes_file = command == "export_patch" && !args.0.get("destination").unwrap_or(&Value::Null).is_null();
+    if config.read_only && (WRITE_COMMANDS.contains(&command) || writes_file) {
+        return Err(InvokeError::ReadOnly(command.to_string()));
+    }
+
+    match command {
+        "get_tree" => reply(commands::get_tree(args.get("operation_id")?).await),
+        "get_commits" => reply(commands::get_commits().await),
+        "get_branches" => reply(commands::get_branches().await),
+        "get_commit_diff" => reply(
+            commands::get_commit_diff(args.get("from_commit")?, args.get("to_commit")?, args.get("operation_id")?).await,
+        ),
+        "search_keys" => reply(
+            commands::search_keys_with(
+                config.writable_search_index_store().as_ref(),
+                args.get("query")?,
+                args.get("revision")?,
+                args.get("filter")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "build_search_index" => {
+            reply(commands::build_search_index_in(&config.search_index_store(), args.get("revision")?).await)
+        }
+        "filter_tree" => reply(
+            commands::filter_tree(args.get("filter")?, args.get("revision")?, args.get("operation_id")?).await,
+        ),
+        "query_values" => reply(
+            commands::query_values(
+                args.get("path_glob")?,
+                args.get("expression")?,
+                args.get("revision")?,
+                args.get("max_keys")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "search_history" => reply(
+            commands::search_history(
+                args.get("query")?,
+                args.get("branch")?,
+                args.get("since")?,
+                args.get("until")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "store_stats" => reply(
+            commands::store_stats(
+                args.get("revision")?,
+                args.get("path")?,
+                args.get("largest")?,
+                args.get("history")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "render_value" => reply(commands::render_value(args.get("path")?, args.get("revision")?).await),
+        "validate_store" => reply(commands::validate_store(args.get("revision")?).await),
+        "export_tree" => reply(
+            commands::export_tree_with(
+                args.get("path")?,
+                args.get("revision")?,
+                args.get("format")?,
+                args.get("destination")?,
+                args.get("operation_id")?,
+                |_| {},
+            )
+            .await,
+        ),
+        "import_tree" => reply(
+            commands::import_tree(
+                args.get("source")?,
+                args.get("branch")?,
+                args.get("prefix")?,
+                args.get("message")?,
+                args.get("replace")?,
+                args.get("dry_run")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "export_commits" => reply(
+            commands::export_commits(
+                args.get("branch")?,
+                args.get("range")?,
+                args.get("format")?,
+                args.get("destination")?,
+                args.get("stats")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "export_patch" => reply(
+            commands::export_patch(args.get("from_commit")?, args.get("to_commit")?, args.get("destination")?).await,
+        ),
+        "apply_patch" => reply(
+            commands::apply_patch(
+                args.get("patch_file")?,
+                args.get("branch")?,
+                args.get("reverse")?,
+                args.get("dry_run")?,
+                args.get("partial")?,
+                args.get("message")?,
+                args.get("operation_id")?,
+            )
+            .await,
+        ),
+        "connect_to_irmin_store" => reply(commands::connect_to_irmin_store(args.get("store_path")?).await),
+        "create_snapshot" => reply(
+            commands::create_snapshot(args.get("destination")?, args.get("full_history")?, args.get("operation_id")?)
+                .await,
+        ),
+        "open_snapshot" => reply(commands::open_snapshot(args.get("path")?).await),
+        "check_irmin_availability" => reply(commands::check_irmin_availability().await),
+        "cancel_operation" => reply(commands::cancel_operation(args.get("operation_id")?).await),
+        "cache_stats" => reply(commands::cache_stats().await),
+        "refresh_heads" => reply(commands::refresh_heads().await),
+        "clear_cache" => reply(commands::clear_cache().await),
+        "toggle_theme" => reply(ui::commands::toggle_theme().await),
+        "stream_tree" | "watch_store" => Err(InvokeError::Streaming(command.to_string())),
+        _ => Err(InvokeError::UnknownCommand(command.to_string())),
+    }
+}
+
+/// Run a command that streams its results, sending them to `lines` as NDJSON
+/// lines until it finishes or `lines` is closed. `None` for commands with a
+/// single reply.
+pub async fn invoke_stream(command: &str, body: &[u8], lines: mpsc::Sender<String>) -> Option<Result<(), InvokeError>> {
+    let args = match Args::parse(body) {
+        Ok(args) => args,
+        Err(e) => return Some(Err(e)),
+    };
+    match command {
+        "stream_tree" => Some(stream_tree(args, lines).await),
+        "watch_store" => Some(watch_store(lines).await),
+        _ => None,
+    }
+}
+
+async fn stream_tree(args: Args, lines: mpsc::Sender<String>) -> Result<(), InvokeError> {
+    let (commit, operation_id) = (args.get("commit")?, args.get("operation_id")?);
+    let mut started = false;
+    let streamed = commands::stream_tree_with(commit, operation_id, |batch| {
+        started = true;
+        send_waiting(&lines, json_line(&batch)).context("Client went away")
+    })
+    .await;
+    match streamed {
+        Ok(_) => Ok(()),
+        Err(e) if !started => Err(InvokeError::Failed(e)),
+        Err(e) => {
+            let _ = lines.send(json_line(&serde_json::json!({ "error": e }))).await;
+            Ok(())
+        }
+    }
+}
+
+/// Watch the store for this client only, until it disconnects
+async fn watch_store(lines: mpsc::Sender<String>) -> Result<(), InvokeError> {
+    let updates = lines.clone();
+    let handle = watcher::Watcher::new(IrminConfig::new())
+        .start(move |update| {
+            let _ = send_waiting(&updates, json_line(&update));
+        })
+        .await
+        .map_err(|e| InvokeError::Failed(format!("Failed to watch store: {}", e)))?;
+    let _ = lines.send(json_line(&serde_json::json!({ "polling": handle.is_polling() }))).await;
+    lines.closed().await;
+    Ok(())
+}
+
+/// Send from a command's synchronous callback, waiting while the client's
+/// buffer is full. Needs the multi-threaded runtime the server runs on.
+fn send_waiting(lines: &mpsc::Sender<String>, line: String) -> Result<(), mpsc::error::SendError<String>> {
+    tokio::task::block_in_place(|| lines.blocking_send(line))
+}
+
+fn json_line<T: Serialize>(value: &T) -> String {
+    let mut line = serde_json::to_string(value).unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string());
+    line.push('\n');
+    line
+}
+
+async fn invoke_handler(State(config): State<Arc<WebConfig>>, UrlPath(command): UrlPath<String>, body: Bytes) -> Response {
+    if let Some(response) = stream_response(&command, body.clone()).await {
+        return response;
+    }
+    match invoke(&config, &command, &body).await {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => error_response(&e),
+    }
+}
+
+fn error_response(error: &InvokeError) -> Response {
+    (error.status(), Json(Value::String(error.message()))).into_response()
+}
+
+/// NDJSON response of a streaming command, or `None` for other commands.
+/// Errors before the first line answer a status like other commands.
+async fn stream_response(command: &str, body: Bytes) -> Option<Response> {
+    if !matches!(command, "stream_tree" | "watch_store") {
+        return None;
+    }
+    let (lines, mut received) = mpsc::channel(STREAM_LINES);
+    let command = command.to_string();
+    let task = tokio::spawn(async move { invoke_stream(&command, &body, lines).await });
+    let first = match received.recv().await {
+        Some(first) => first,
+        None => {
+            let error = match task.await {
+                Ok(Some(Err(e))) => e,
+                Ok(_) => InvokeError::Failed("The command sent nothing".to_string()),
+                Err(e) => InvokeError::Failed(e.to_string()),
+            };
+            return Some(error_response(&error));
+        }
+    };
+    let rest = futures::stream::unfold(received, |mut received| async move {
+        received.recv().await.map(|line| (Ok::<_, std::convert::Infallible>(line), received))
+    });
+    let body = futures::stream::once(async move { Ok(first) }).chain(rest);
+    Some(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(body)).into_response())
+}
+
+/// `index.html` with the invoke shim loaded ahead of the app's scripts
+async fn index_handler(State(config): State<Arc<WebConfig>>) -> Response {
+    match tokio::fs::read_to_string(config.dist_dir.join("index.html")).await {
+        Ok(html) => {
+            let tag = format!("<script src=\"{}\"></script>\n    ", SHIM_PATH);
+            let html = match html.find("<script") {
+                Some(at) => format!("{}{}{}", &html[..at], tag, &html[at..]),
+                None => html.replace("</body>", &format!("{}</body>", tag)),
+            };
+            Html(html).into_response()
+        }
+        Err(e) => (StatusCode::NOT_FOUND, format!("No frontend at {}: {}", config.dist_dir.display(), e)).into_response(),
+    }
+}
+
+async fn shim_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/javascript")], SHIM)
+}
+
+pub fn router(config: WebConfig) -> Router {
+    let dist = ServeDir::new(&config.dist_dir);
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/index.html", get(index_handler))
+        .route(SHIM_PATH, get(shim_handler))
+        .route("/invoke/{command}", post(invoke_handler))
+        .fallback_service(dist)
+        .with_state(Arc::new(config))
+}
+
+/// Serve until the process is stopped
+pub async fn serve(config: WebConfig) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    println!(
+        "IrminView web mode on http://{} ({})",
+        listener.local_addr()?,
+        if config.read_only { "read-only" } else { "writes allowed" }
+    );
+    axum::serve(listener, router(config)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irmin::types::TreeBatch;
+
+    #[tokio::test]
+    async fn test_invoke_accepts_tauri_argument_names_and_enforces_read_only() {
+        let config = WebConfig::new();
+        let branches = invoke(&config, "get_branches", b"").await.unwrap();
+        assert!(branches.as_array().is_some_and(|b| !b.is_empty()));
+
+        let rendered = invoke(&config, "render_value", br#"{"path": "/README.md"}"#).await;
+        assert!(!matches!(rendered, Err(InvokeError::InvalidArguments(_))));
+        assert_eq!(snake_case("fromCommit"), "from_commit");
+
+        assert_eq!(
+            invoke(&config, "apply_patch", br#"{"patchFile": "x.patch", "branch": "main"}"#).await,
+            Err(InvokeError::ReadOnly("apply_patch".to_string()))
+        );
+        assert!(matches!(
+            invoke(&config, "get_commit_diff", br#"{"from_commit": 1}"#).await,
+            Err(InvokeError::InvalidArguments(_))
+        ));
+        assert!(matches!(invoke(&config, "drop_store", b"{}").await, Err(InvokeError::UnknownCommand(_))));
+        assert!(matches!(invoke(&config, "stream_tree", b"{}").await, Err(InvokeError::Streaming(_))));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_search_saves_no_index() {
+        let data_dir = std::env::temp_dir().join(format!("irmin-view-web-{}", uuid::Uuid::new_v4()));
+        let config = WebConfig::new().with_data_dir(data_dir.clone());
+        assert_eq!(
+            invoke(&config, "build_search_index", br#"{"revision": "main"}"#).await,
+            Err(InvokeError::ReadOnly("build_search_index".to_string()))
+        );
+        let results = invoke(&config, "search_keys", br#"{"query": "readme"}"#).await.unwrap();
+        assert!(results.as_array().is_some_and(|r| !r.is_empty()));
+        assert!(!data_dir.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serves_frontend_with_shim() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(WebConfig::new())).await });
+
+        let index = reqwest::get(format!("{}/", url)).await.unwrap().text().await.unwrap();
+        assert!(index.contains(SHIM_PATH));
+        let styles = reqwest::get(format!("{}/styles.css", url)).await.unwrap();
+        assert!(styles.status().is_success());
+
+        let client = reqwest::Client::new();
+        let response = client.post(format!("{}/invoke/get_commits", url)).send().await.unwrap();
+        assert!(response.status().is_success());
+        let forbidden = client
+            .post(format!("{}/invoke/clear_cache", url))
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forbidden.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let streamed = client
+            .post(format!("{}/invoke/stream_tree", url))
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(streamed.headers()["content-type"], "application/x-ndjson");
+        let text = streamed.text().await.unwrap();
+        let batches: Vec<TreeBatch> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let last = batches.last().unwrap();
+        assert!(last.finished && last.entries_sent > 0);
+        assert_eq!(batches.iter().map(|b| b.entries.len()).sum::<usize>(), last.entries_sent);
+    }
+}
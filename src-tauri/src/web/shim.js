@@ -0,0 +1,120 @@
+// Injected into index.html by the IrminView web server. Provides the parts of
+// the Tauri API used by app.js on top of the server's /invoke endpoints.
+(function () {
+    if (window.__TAURI__) {
+        return;
+    }
+
+    // Event listeners by event name
+    const listeners = {};
+
+    function emit(event, payload) {
+        for (const handler of listeners[event] || []) {
+            handler({ event, payload });
+        }
+    }
+
+    async function listen(event, handler) {
+        (listeners[event] = listeners[event] || []).push(handler);
+        return () => {
+            listeners[event] = listeners[event].filter(h => h !== handler);
+        };
+    }
+
+    // Receives the messages of a streaming command, like Tauri's Channel
+    class Channel {
+        constructor() {
+            this.onmessage = () => {};
+        }
+
+        toJSON() {
+            return null;
+        }
+    }
+
+    async function post(cmd, args) {
+        const response = await fetch(`/invoke/${encodeURIComponent(cmd)}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(args || {}),
+        });
+        if (!response.ok) {
+            throw await response.json();
+        }
+        return response;
+    }
+
+    // Call onLine with each NDJSON line of a response as it arrives
+    async function readLines(response, onLine) {
+        const reader = response.body.getReader();
+        const decoder = new TextDecoder();
+        let buffered = '';
+        for (;;) {
+            const { done, value } = await reader.read();
+            buffered += decoder.decode(value || new Uint8Array(), { stream: !done });
+            const lines = buffered.split('\n');
+            buffered = lines.pop();
+            for (const line of lines.filter(line => line.trim())) {
+                const message = JSON.parse(line);
+                if (message.error !== undefined) {
+                    throw message.error;
+                }
+                onLine(message);
+            }
+            if (done) {
+                return;
+            }
+        }
+    }
+
+    // Stream the tree to the onBatch channel; resolves to the entries sent
+    async function streamTree(args) {
+        const response = await post('stream_tree', args);
+        let sent = 0;
+        await readLines(response, (batch) => {
+            sent = batch.entries_sent;
+            args.onBatch.onmessage(batch);
+        });
+        return sent;
+    }
+
+    // Deliver branch-updated events while the page is open; resolves to
+    // whether the server polls for changes
+    async function watchStore(args) {
+        const response = await post('watch_store', args);
+        return new Promise((resolve, reject) => {
+            let started = false;
+            readLines(response, (message) => {
+                if (!started) {
+                    started = true;
+                    resolve(message.polling);
+                } else {
+                    emit('branch-updated', message);
+                }
+            }).catch((error) => {
+                if (!started) {
+                    reject(error);
+                } else {
+                    console.log('Stopped watching the store:', error);
+                }
+            });
+        });
+    }
+
+    async function invoke(cmd, args) {
+        if (cmd === 'stream_tree') {
+            return streamTree(args || {});
+        }
+        if (cmd === 'watch_store') {
+            return watchStore(args || {});
+        }
+        const response = await post(cmd, args);
+        return response.json();
+    }
+
+    window.__TAURI__ = {
+        core: { invoke, Channel },
+        // Export and operation progress events are not delivered in web mode
+        event: { listen },
+    };
+})();
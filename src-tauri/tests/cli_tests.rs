@@ -0,0 +1,106 @@
+//! Runs the `irmin-view-cli` binary against the built-in `demo` fixtures.
+
+use serde_json::Value;
+use std::process::{Command, Output, Stdio};
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_irmin-view-cli"))
+        .args(["--fixtures", "demo", "--no-cache"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn text(args: &[&str]) -> String {
+    let output = cli(args);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn json(args: &[&str]) -> Value {
+    let args: Vec<&str> = args.iter().copied().chain(["--format", "json"]).collect();
+    serde_json::from_str(&text(&args)).unwrap()
+}
+
+#[test]
+fn test_tree_and_ls() {
+    let tree = text(&["tree"]);
+    assert!(tree.starts_with("/\n"));
+    assert!(tree.contains("  config/\n    app.json ("));
+    let node = json(&["tree", "/config"]);
+    assert_eq!(node["node_type"], "Directory");
+    assert!(node["children"]["app.json"].is_object());
+
+    let listing = text(&["ls"]);
+    assert!(listing.lines().any(|line| line.ends_with("  config/")));
+    let entries = json(&["ls", "/config"]);
+    let names: Vec<&str> = entries.as_array().unwrap().iter().map(|e| e["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["app.json", "auth.json", "database.json"]);
+    assert_eq!(entries[0]["path"], "/config/app.json");
+}
+
+#[test]
+fn test_cat_log_and_branches() {
+    let value = text(&["cat", "/README.md"]);
+    assert!(value.ends_with('\n') && !value.trim().is_empty());
+    let shown = json(&["cat", "/README.md"]);
+    assert_eq!(shown["path"], "/README.md");
+    assert_eq!(format!("{}\n", shown["value"].as_str().unwrap().trim_end_matches('\n')), value);
+
+    let log = text(&["log", "-n", "2"]);
+    assert_eq!(log.lines().count(), 2);
+    assert!(log.starts_with("a1b2c3d4  "));
+    let commits = json(&["log"]);
+    assert_eq!(commits[0]["hash"].as_str().unwrap()[..8], *"a1b2c3d4");
+
+    let branches = text(&["branches"]);
+    assert!(branches.lines().any(|line| line.starts_with("main ")));
+    let branches = json(&["branches"]);
+    assert!(branches.as_array().unwrap().iter().any(|b| b["name"] == "main"));
+}
+
+#[test]
+fn test_diff_search_and_stats() {
+    let diff = text(&["diff", "98765432", "a1b2c3d4"]);
+    assert!(!diff.is_empty());
+    assert!(diff.lines().all(|line| ["A /", "M /", "D /"].iter().any(|tag| line.starts_with(tag))));
+    let changes = json(&["diff", "98765432", "a1b2c3d4"]);
+    assert_eq!(changes["changes"].as_array().unwrap().len(), diff.lines().count());
+
+    let found = text(&["search", "readme"]);
+    assert!(found.lines().next().unwrap().ends_with("/README.md"));
+    let results = json(&["search", "readme"]);
+    assert_eq!(results[0]["path"], "/README.md");
+
+    let stats = text(&["stats"]);
+    assert!(stats.starts_with("/ at HEAD: "));
+    let report = json(&["stats", "/config"]);
+    assert_eq!(report["key_count"], 3);
+}
+
+#[test]
+fn test_missing_path_fails() {
+    for command in ["tree", "ls", "cat", "stats"] {
+        let output = cli(&[command, "/no/such/key"]);
+        assert!(!output.status.success(), "{} succeeded", command);
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("/no/such/key"));
+    }
+}
+
+#[test]
+fn test_closed_stdout_is_a_clean_exit() {
+    for args in [&["log", "--format", "json"][..], &["export-commits", "--format", "ndjson"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_irmin-view-cli"))
+            .args(["--fixtures", "demo", "--no-cache"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        drop(child.stdout.take());
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        assert!(output.stderr.is_empty());
+    }
+}
@@ -46,4 +46,202 @@ async fn test_fallback_to_mock_data() {
     
     let commits_result = integration::get_irmin_commits(&config).await;
     assert!(commits_result.is_err(), "Should fail with invalid path");
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_http_integration_with_reference_server() {
+    use irmin_view::irmin::reference_server::{Fault, ReferenceServer};
+
+    let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+    let config = integration::IrminConfig::new()
+        .with_http_server(running.url.clone())
+        .without_cache();
+
+    let branches = integration::get_irmin_branches(&config).await.unwrap();
+    let head = integration::resolve_irmin_revision(&config, "main").await.unwrap();
+    assert!(branches.iter().any(|b| b.head_commit == head));
+
+    let request = CommitRequest {
+        branch: "main".to_string(),
+        message: "Update config".to_string(),
+        author: "Tester".to_string(),
+        changes: vec![DiffChange {
+            path: "/notes.txt".to_string(),
+            change_type: ChangeType::Added,
+            old_value: None,
+            new_value: Some("hello".to_string()),
+        }],
+    };
+    let result = integration::commit_irmin_changes(&config, &request).await.unwrap();
+    let tree = integration::get_irmin_branch_tree(&config, "main").await.unwrap().unwrap();
+    assert!(tree.children.contains_key("notes.txt"));
+    assert_eq!(integration::resolve_irmin_revision(&config, "main").await.unwrap(), result.hash);
+
+    running.server.inject("/api/commits", Fault::Error(500, "Irmin store unavailable".to_string()));
+    let error = integration::get_irmin_commits(&config).await.unwrap_err();
+    assert!(error.to_string().contains("Irmin store unavailable"));
+}
+
+#[tokio::test]
+async fn test_cancel_aborts_http_request() {
+    use irmin_view::irmin::operations;
+    use irmin_view::irmin::reference_server::{Fault, ReferenceServer};
+    use std::time::{Duration, Instant};
+
+    let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+    running.server.inject("/api/tree", Fault::Latency(Duration::from_secs(30)));
+    let config = integration::IrminConfig::new()
+        .with_http_server(running.url.clone())
+        .without_cache();
+
+    let started = Instant::now();
+    let (result, _) = tokio::join!(
+        operations::run(Some("http-tree".to_string()), integration::get_irmin_tree(&config)),
+        async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(operations::cancel("http-tree"));
+        }
+    );
+    assert!(operations::is_cancelled(&result.unwrap_err()));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_cancel_kills_bridge_process() {
+    use irmin_view::irmin::operations;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    // A bridge that records its pid and hangs
+    let dir = std::env::temp_dir().join(format!("irmin-cancel-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pid_file = dir.join("pid");
+    let bridge = dir.join("bridge.sh");
+    std::fs::write(&bridge, format!("#!/bin/sh\necho $$ > {}\nexec sleep 30\n", pid_file.display())).unwrap();
+    std::fs::set_permissions(&bridge, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = integration::IrminConfig::new().without_cache();
+    config.use_http = false;
+    config.fixtures = None;
+    config.snapshot = None;
+    config.bridge_executable = bridge.display().to_string();
+
+    let (result, _) = tokio::join!(
+        operations::run(Some("bridge-tree".to_string()), integration::get_irmin_tree(&config)),
+        async {
+            while !pid_file.exists() || std::fs::read_to_string(&pid_file).unwrap().trim().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(operations::cancel("bridge-tree"));
+        }
+    );
+    assert!(operations::is_cancelled(&result.unwrap_err()));
+
+    // Killed processes linger as zombies until reaped
+    let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+    let stat = format!("/proc/{}/stat", pid);
+    let mut gone = false;
+    for _ in 0..200 {
+        match std::fs::read_to_string(&stat) {
+            Ok(stat) if !stat.contains(") Z ") => tokio::time::sleep(Duration::from_millis(10)).await,
+            _ => {
+                gone = true;
+                break;
+            }
+        }
+    }
+    assert!(gone, "bridge process {} is still running", pid);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_operations_report_progress() {
+    use irmin_view::irmin::progress::{self, Recorder};
+    use irmin_view::irmin::reference_server::ReferenceServer;
+    use irmin_view::irmin::operations;
+    use std::sync::Arc;
+
+    let recorder = Recorder::new();
+    progress::set_sink(Arc::new(recorder.clone()));
+    let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+    let config = integration::IrminConfig::new()
+        .with_http_server(running.url.clone())
+        .without_cache();
+
+    operations::run(Some("progress-tree".to_string()), integration::get_irmin_tree(&config)).await.unwrap();
+    let mut entries = 0;
+    operations::run(
+        Some("progress-stream".to_string()),
+        integration::stream_irmin_tree(&config, None, |batch| {
+            entries += batch.len();
+            Ok(())
+        }),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(recorder.phases("progress-tree"), vec!["Downloading tree"]);
+    let events = recorder.events_for("progress-tree");
+    let last = events.last().unwrap();
+    assert!(last.finished);
+    assert!(last.bytes > 0);
+
+    let last = recorder.events_for("progress-stream").pop().unwrap();
+    assert_eq!(recorder.phases("progress-stream"), vec!["Streaming tree"]);
+    assert!(last.finished);
+    assert_eq!(last.items_done, entries as u64);
+}
+
+#[tokio::test]
+async fn test_watcher_polls_http_branch_heads() {
+    use irmin_view::irmin::reference_server::ReferenceServer;
+    use irmin_view::irmin::watcher::Watcher;
+    use std::time::Duration;
+
+    let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+    let config = integration::IrminConfig::new()
+        .with_http_server(running.url.clone())
+        .without_cache();
+    let old_head = integration::resolve_irmin_revision(&config, "main").await.unwrap();
+
+    let (sender, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    let handle = Watcher::new(config.clone())
+        .with_poll_interval(Duration::from_millis(50))
+        .start(move |update| {
+            let _ = sender.send(update);
+        })
+        .await
+        .unwrap();
+    assert!(handle.is_polling());
+
+    for message in ["First", "Second"] {
+        let request = CommitRequest {
+            branch: "main".to_string(),
+            message: message.to_string(),
+            author: "Tester".to_string(),
+            changes: vec![DiffChange {
+                path: format!("/{}.txt", message),
+                change_type: ChangeType::Added,
+                old_value: None,
+                new_value: Some(message.to_string()),
+            }],
+        };
+        integration::commit_irmin_changes(&config, &request).await.unwrap();
+    }
+    let new_head = integration::resolve_irmin_revision(&config, "main").await.unwrap();
+
+    // The two commits may be seen by one poll or by two
+    let mut first_old_head = None;
+    let mut new_commits = 0;
+    loop {
+        let update = tokio::time::timeout(Duration::from_secs(10), updates.recv()).await.unwrap().unwrap();
+        assert_eq!(update.branch, "main");
+        first_old_head.get_or_insert(update.old_head.clone());
+        new_commits += update.new_commits;
+        if update.new_head.as_deref() == Some(new_head.as_str()) {
+            break;
+        }
+    }
+    assert_eq!(first_old_head, Some(Some(old_head)));
+    assert_eq!(new_commits, 2);
+}
@@ -0,0 +1,138 @@
+//! Contract tests against output captured from the OCaml bridge.
+//!
+//! `tests/golden/bridge/<name>.json` is what the bridge printed;
+//! `<name>.expected.json` is what IrminView makes of it, serialized again.
+//! Run with `IRMIN_UPDATE_GOLDEN=1` to rewrite the expected files after an
+//! intended change, and review the diff. The captured output must also match
+//! the generated JSON Schemas, and the reference server must answer in the
+//! same shapes.
+
+use irmin_view::irmin::{reference_server::ReferenceServer, types::*, wire};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/bridge")
+}
+
+/// Decode a captured response and compare it with its expected file
+fn check<T: DeserializeOwned + Serialize + JsonSchema>(name: &str) -> T {
+    let captured = std::fs::read_to_string(golden_dir().join(format!("{}.json", name))).unwrap();
+    let violations = wire::validate::<T>(&serde_json::from_str(&captured).unwrap());
+    assert!(violations.is_empty(), "{} does not match its schema: {:#?}", name, violations);
+    let decoded: T = wire::decode(&captured).unwrap_or_else(|e| panic!("{}: {:#}", name, e));
+    let actual = serde_json::to_value(&decoded).unwrap();
+
+    let expected_path = golden_dir().join(format!("{}.expected.json", name));
+    if std::env::var("IRMIN_UPDATE_GOLDEN").is_ok() {
+        std::fs::write(&expected_path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+    }
+    let expected: Value = serde_json::from_str(&std::fs::read_to_string(&expected_path).unwrap()).unwrap();
+    assert_eq!(actual, expected, "{} no longer decodes as {}", name, expected_path.display());
+    decoded
+}
+
+#[test]
+fn test_bridge_tree() {
+    let tree: IrminNode = check("tree");
+    assert_eq!(tree.key, "root");
+    assert_eq!(tree.children["config"].node_type, NodeType::Directory);
+    assert_eq!(tree.children["users"].children["alice"].value.as_deref(), Some("Alice Smith"));
+}
+
+#[test]
+fn test_bridge_commits_with_epoch_timestamps() {
+    let commits: Vec<IrminCommit> = check("commits");
+    assert_eq!(commits[0].timestamp.to_rfc3339(), "2024-01-15T10:25:00+00:00");
+    assert!(commits.windows(2).all(|w| w[0].parents == vec![w[1].hash.clone()]));
+}
+
+#[test]
+fn test_bridge_branches_search_diff_and_commit() {
+    let branches: Vec<IrminBranch> = check("branches");
+    assert_eq!(branches.len(), 2);
+    let results: Vec<SearchResult> = check("search");
+    assert_eq!(results[0].relevance_score, 1.0);
+    let diff: IrminDiff = check("diff");
+    assert_eq!(diff.changes[0].change_type, ChangeType::Modified);
+    let result: CommitResult = check("commit");
+    assert_eq!(result.hash.len(), 40);
+}
+
+/// Kinds of JSON value seen at each path of `value`, with `*` for the keys of
+/// `children` maps and `[]` for array elements
+fn shape(value: &Value, path: &str, kinds: &mut BTreeMap<String, BTreeSet<&'static str>>) {
+    let kind = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+    kinds.entry(path.to_string()).or_default().insert(kind);
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| shape(item, &format!("{}[]", path), kinds)),
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let key = if path.ends_with(".children") { "*" } else { key.as_str() };
+                shape(field, &format!("{}.{}", path, key), kinds);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Check that `served` has the fields of the capture `name`, with values of
+/// the kinds the bridge printed there, plus the fields named in `added`
+/// that the bridge writes since the capture was taken
+fn assert_bridge_shape(name: &str, served: &Value, added: &[&str]) {
+    let captured: Value = serde_json::from_str(&std::fs::read_to_string(golden_dir().join(format!("{}.json", name))).unwrap()).unwrap();
+    let (mut expected, mut actual) = (BTreeMap::new(), BTreeMap::new());
+    shape(&captured, "", &mut expected);
+    shape(served, "", &mut actual);
+    for field in added {
+        let suffix = format!(".{}", field);
+        assert!(actual.keys().any(|path| path.ends_with(&suffix)), "{} has no {}", name, field);
+        actual.retain(|path, _| !path.ends_with(&suffix));
+    }
+    assert_eq!(actual.keys().collect::<Vec<_>>(), expected.keys().collect::<Vec<_>>(), "{} has other fields than the bridge's", name);
+    for (path, kinds) in &actual {
+        let unexpected: Vec<_> = kinds.iter().filter(|kind| **kind != "null" && !expected[path].contains(*kind)).collect();
+        assert!(unexpected.is_empty(), "{}: {} is {:?}, the bridge prints {:?}", name, path, unexpected, expected[path]);
+    }
+}
+
+async fn get(url: &str) -> String {
+    reqwest::get(url).await.unwrap().text().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_reference_server_answers_like_the_bridge() {
+    let running = ReferenceServer::from_fixtures("sample").unwrap().start().await.unwrap();
+    let get_json = |path: &str| {
+        let url = format!("{}{}", running.url, path);
+        async move { serde_json::from_str::<Value>(&get(&url).await).unwrap() }
+    };
+
+    let tree = get_json("/api/tree").await;
+    assert_bridge_shape("tree", &tree, &["hash"]);
+    assert_eq!(tree["key"], "root");
+    let commits = get_json("/api/commits").await;
+    assert_bridge_shape("commits", &commits, &[]);
+    assert!(commits[0]["timestamp"].as_str().unwrap().parse::<i64>().is_ok());
+    assert_bridge_shape("branches", &get_json("/api/branches").await, &[]);
+    assert_bridge_shape("search", &get_json("/api/search?q=user").await, &[]);
+    let (from, to) = (commits[1]["hash"].as_str().unwrap(), commits[0]["hash"].as_str().unwrap());
+    assert_bridge_shape("diff", &get_json(&format!("/api/diff?from={}&to={}", from, to)).await, &[]);
+
+    let stream = get(&format!("{}/api/tree/stream", running.url)).await;
+    let first: Value = serde_json::from_str(stream.lines().next().unwrap()).unwrap();
+    let fields: Vec<&String> = first.as_object().unwrap().keys().collect();
+    assert_eq!(fields, ["hash", "path", "size", "type"]);
+    let entries: Vec<TreeEntry> = stream.lines().map(|line| wire::decode(line).unwrap()).collect();
+    assert!(entries.iter().all(|entry| entry.hash.is_some()));
+}